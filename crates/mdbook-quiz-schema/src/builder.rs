@@ -0,0 +1,397 @@
+//! Constructors and builder methods for the schema types, so that quizzes can be
+//! written in Rust rather than TOML.
+//!
+//! ```
+//! use mdbook_quiz_schema::*;
+//!
+//! let quiz = Quiz::new([
+//!   Question::from(ShortAnswer::new("What is 1 + 1?", "2").with_id("add")),
+//!   Question::from(
+//!     MultipleChoice::new("What is 2 + 2?", "4", ["3", "5"]).with_context("Count carefully."),
+//!   ),
+//! ]);
+//! assert_eq!(quiz.questions.len(), 2);
+//! ```
+
+use crate::*;
+
+impl From<&str> for Markdown {
+  fn from(s: &str) -> Self {
+    Markdown(s.to_string())
+  }
+}
+
+impl From<String> for Markdown {
+  fn from(s: String) -> Self {
+    Markdown(s)
+  }
+}
+
+impl Quiz {
+  /// Creates a quiz with the given questions.
+  pub fn new(questions: impl IntoIterator<Item = Question>) -> Self {
+    Quiz {
+      questions: questions.into_iter().collect(),
+      ..Default::default()
+    }
+  }
+}
+
+impl From<ShortAnswer> for Question {
+  fn from(q: ShortAnswer) -> Self {
+    Question::ShortAnswer(q)
+  }
+}
+
+impl From<Tracing> for Question {
+  fn from(q: Tracing) -> Self {
+    Question::Tracing(q)
+  }
+}
+
+impl From<MultipleChoice> for Question {
+  fn from(q: MultipleChoice) -> Self {
+    Question::MultipleChoice(q)
+  }
+}
+
+impl<Prompt, Answer> QuestionFields<Prompt, Answer> {
+  /// Creates a question with a prompt and an answer, and no other fields.
+  pub fn new(prompt: Prompt, answer: Answer) -> Self {
+    QuestionFields {
+      id: None,
+      multipart: None,
+      tags: None,
+      difficulty: None,
+      objectives: None,
+      points: None,
+      prompt,
+      answer,
+      hints: None,
+      context: None,
+      prompt_explanation: None,
+    }
+  }
+}
+
+/// Builder methods for the [`QuestionFields`] shared by every question type.
+pub trait QuestionBuilder: Sized {
+  /// The type of the question's prompt.
+  type Prompt;
+
+  /// The type of the question's answer.
+  type Answer;
+
+  /// Returns the fields of the question.
+  fn fields_mut(&mut self) -> &mut QuestionFields<Self::Prompt, Self::Answer>;
+
+  /// Sets the [`QuestionFields::id`].
+  fn with_id(mut self, id: impl Into<String>) -> Self {
+    self.fields_mut().id = Some(id.into());
+    self
+  }
+
+  /// Sets the [`QuestionFields::multipart`].
+  fn with_multipart(mut self, multipart: impl Into<String>) -> Self {
+    self.fields_mut().multipart = Some(multipart.into());
+    self
+  }
+
+  /// Sets the [`QuestionFields::tags`].
+  fn with_tags(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+    self.fields_mut().tags = Some(tags.into_iter().map(Into::into).collect());
+    self
+  }
+
+  /// Sets the [`QuestionFields::difficulty`].
+  fn with_difficulty(mut self, difficulty: Difficulty) -> Self {
+    self.fields_mut().difficulty = Some(difficulty);
+    self
+  }
+
+  /// Sets the [`QuestionFields::objectives`].
+  fn with_objectives(mut self, objectives: impl IntoIterator<Item = impl Into<String>>) -> Self {
+    self.fields_mut().objectives = Some(objectives.into_iter().map(Into::into).collect());
+    self
+  }
+
+  /// Sets the [`QuestionFields::points`].
+  fn with_points(mut self, points: u32) -> Self {
+    self.fields_mut().points = Some(points);
+    self
+  }
+
+  /// Sets the [`QuestionFields::hints`].
+  fn with_hints(mut self, hints: impl IntoIterator<Item = impl Into<Markdown>>) -> Self {
+    self.fields_mut().hints = Some(hints.into_iter().map(Into::into).collect());
+    self
+  }
+
+  /// Sets the [`QuestionFields::context`].
+  fn with_context(mut self, context: impl Into<Markdown>) -> Self {
+    self.fields_mut().context = Some(context.into());
+    self
+  }
+
+  /// Sets the [`QuestionFields::prompt_explanation`].
+  fn with_prompt_explanation(mut self, prompt_explanation: bool) -> Self {
+    self.fields_mut().prompt_explanation = Some(prompt_explanation);
+    self
+  }
+}
+
+impl<Prompt, Answer> QuestionBuilder for QuestionFields<Prompt, Answer> {
+  type Prompt = Prompt;
+  type Answer = Answer;
+
+  fn fields_mut(&mut self) -> &mut QuestionFields<Prompt, Answer> {
+    self
+  }
+}
+
+macro_rules! question_builder {
+  ($question:ty, $prompt:ty, $answer:ty) => {
+    impl QuestionBuilder for $question {
+      type Prompt = $prompt;
+      type Answer = $answer;
+
+      fn fields_mut(&mut self) -> &mut QuestionFields<$prompt, $answer> {
+        &mut self.0
+      }
+    }
+  };
+}
+
+question_builder!(ShortAnswer, ShortAnswerPrompt, ShortAnswerAnswer);
+question_builder!(Tracing, TracingPrompt, TracingAnswer);
+question_builder!(MultipleChoice, MultipleChoicePrompt, MultipleChoiceAnswer);
+
+impl ShortAnswer {
+  /// Creates a short answer question whose answer is exactly `answer`.
+  pub fn new(prompt: impl Into<Markdown>, answer: impl Into<String>) -> Self {
+    let prompt = ShortAnswerPrompt {
+      prompt: prompt.into(),
+      response: None,
+    };
+    let answer = ShortAnswerAnswer {
+      answer: answer.into(),
+      alternatives: None,
+    };
+    ShortAnswer(QuestionFields::new(prompt, answer))
+  }
+
+  /// Sets the [`ShortAnswerPrompt::response`] format.
+  pub fn with_response(mut self, response: ShortAnswerResponseFormat) -> Self {
+    self.0.prompt.response = Some(response);
+    self
+  }
+
+  /// Sets the [`ShortAnswerAnswer::alternatives`].
+  pub fn with_alternatives(
+    mut self,
+    alternatives: impl IntoIterator<Item = impl Into<String>>,
+  ) -> Self {
+    self.0.answer.alternatives = Some(alternatives.into_iter().map(Into::into).collect());
+    self
+  }
+}
+
+impl TracingAnswer {
+  /// An answer for a program that compiles and prints `stdout`.
+  pub fn compiles(stdout: impl Into<String>) -> Self {
+    TracingAnswer {
+      does_compile: true,
+      stdout: Some(stdout.into()),
+      line_number: None,
+    }
+  }
+
+  /// An answer for a program that fails to compile because of the code at `line_number`.
+  pub fn does_not_compile(line_number: usize) -> Self {
+    TracingAnswer {
+      does_compile: false,
+      stdout: None,
+      line_number: Some(line_number),
+    }
+  }
+}
+
+impl Tracing {
+  /// Creates a tracing question for `program`.
+  pub fn new(program: impl Into<String>, answer: TracingAnswer) -> Self {
+    let prompt = TracingPrompt {
+      program: program.into(),
+      program_file: None,
+    };
+    Tracing(QuestionFields::new(prompt, answer))
+  }
+
+  /// Creates a tracing question whose program is read from a Rust file,
+  /// see [`TracingPrompt::program_file`].
+  pub fn from_file(program_file: impl Into<String>, answer: TracingAnswer) -> Self {
+    let prompt = TracingPrompt {
+      program: String::new(),
+      program_file: Some(program_file.into()),
+    };
+    Tracing(QuestionFields::new(prompt, answer))
+  }
+}
+
+impl Distractor {
+  /// Creates a distractor without feedback.
+  pub fn new(text: impl Into<Markdown>) -> Self {
+    Distractor::Text(text.into())
+  }
+
+  /// Sets the feedback shown to users who chose this distractor.
+  pub fn with_feedback(self, feedback: impl Into<Markdown>) -> Self {
+    let (Distractor::Text(text) | Distractor::WithFeedback { text, .. }) = self;
+    Distractor::WithFeedback {
+      text,
+      feedback: feedback.into(),
+    }
+  }
+}
+
+impl From<&str> for Distractor {
+  fn from(text: &str) -> Self {
+    Distractor::new(text)
+  }
+}
+
+impl From<String> for Distractor {
+  fn from(text: String) -> Self {
+    Distractor::new(text)
+  }
+}
+
+impl From<Markdown> for Distractor {
+  fn from(text: Markdown) -> Self {
+    Distractor::Text(text)
+  }
+}
+
+impl From<&str> for MultipleChoiceAnswerFormat {
+  fn from(answer: &str) -> Self {
+    MultipleChoiceAnswerFormat::Single(answer.into())
+  }
+}
+
+impl From<String> for MultipleChoiceAnswerFormat {
+  fn from(answer: String) -> Self {
+    MultipleChoiceAnswerFormat::Single(answer.into())
+  }
+}
+
+impl<T: Into<Markdown>> From<Vec<T>> for MultipleChoiceAnswerFormat {
+  fn from(answers: Vec<T>) -> Self {
+    MultipleChoiceAnswerFormat::Multi(answers.into_iter().map(Into::into).collect())
+  }
+}
+
+impl MultipleChoice {
+  /// Creates a multiple choice question with the correct `answer` and incorrect `distractors`.
+  ///
+  /// `answer` is either one answer, or a `Vec` of answers that must all be selected.
+  pub fn new(
+    prompt: impl Into<Markdown>,
+    answer: impl Into<MultipleChoiceAnswerFormat>,
+    distractors: impl IntoIterator<Item = impl Into<Distractor>>,
+  ) -> Self {
+    let prompt = MultipleChoicePrompt {
+      prompt: prompt.into(),
+      distractors: distractors.into_iter().map(Into::into).collect(),
+      answer_index: None,
+      sort_answers: None,
+    };
+    let answer = MultipleChoiceAnswer {
+      answer: answer.into(),
+    };
+    MultipleChoice(QuestionFields::new(prompt, answer))
+  }
+
+  /// Sets the [`MultipleChoicePrompt::answer_index`].
+  pub fn with_answer_index(mut self, answer_index: usize) -> Self {
+    self.0.prompt.answer_index = Some(answer_index);
+    self
+  }
+
+  /// Sets [`MultipleChoicePrompt::sort_answers`].
+  pub fn with_sort_answers(mut self, sort_answers: bool) -> Self {
+    self.0.prompt.sort_answers = Some(sort_answers);
+    self
+  }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+  use super::*;
+
+  #[test]
+  fn to_toml_string() -> anyhow::Result<()> {
+    let mut quiz = Quiz::new([
+      ShortAnswer::new("What is the keyword for a mutable variable?", "mut")
+        .with_id("keyword")
+        .with_alternatives(["`mut`"])
+        .into(),
+      Tracing::new(
+        "fn main() {\n  let x = 1;\n  x += 1;\n}\n",
+        TracingAnswer::does_not_compile(3),
+      )
+      .with_context("Line 3 mutates `x`.")
+      .into(),
+      MultipleChoice::new(
+        "What is 1 + 1?",
+        "2",
+        [Distractor::new("3").with_feedback("Too many"), "11".into()],
+      )
+      .with_multipart("math")
+      .with_tags(["arithmetic"])
+      .with_difficulty(Difficulty::Easy)
+      .into(),
+    ]);
+    quiz.multipart = Some(HashMap::from([
+      ("math".to_string(), "Some math.".into()),
+      ("alpha".to_string(), "Some more.".into()),
+    ]));
+
+    let toml = quiz.to_toml_string()?;
+    assert_eq!(
+      toml,
+      r#"[[questions]]
+id = "keyword"
+type = "ShortAnswer"
+prompt.prompt = "What is the keyword for a mutable variable?"
+answer.answer = "mut"
+answer.alternatives = ["`mut`"]
+
+[[questions]]
+type = "Tracing"
+prompt.program = """
+fn main() {
+  let x = 1;
+  x += 1;
+}
+"""
+answer.doesCompile = false
+answer.lineNumber = 3
+context = "Line 3 mutates `x`."
+
+[[questions]]
+type = "MultipleChoice"
+multipart = "math"
+tags = ["arithmetic"]
+difficulty = "easy"
+prompt.prompt = "What is 1 + 1?"
+prompt.distractors = [{ text = "3", feedback = "Too many" }, "11"]
+answer.answer = "2"
+
+[multipart]
+alpha = "Some more."
+math = "Some math."
+"#
+    );
+    assert_eq!(toml::from_str::<Quiz>(&toml)?, quiz);
+    Ok(())
+  }
+}
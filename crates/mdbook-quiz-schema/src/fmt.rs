@@ -0,0 +1,610 @@
+//! Writes quizzes as TOML in the style of the examples in the mdbook-quiz README.
+//!
+//! This is the style that `mdbook-quiz fmt` gives quiz files: each question is written as a
+//! `[[questions]]` table whose nested tables use dotted keys, with its keys in a fixed order.
+//! Strings containing newlines are written as multi-line strings. Comments are kept next to the
+//! key or table they precede.
+
+use std::fmt;
+
+use anyhow::Result;
+use serde::{
+  Deserialize, Deserializer,
+  de::{self, MapAccess, SeqAccess, Visitor},
+};
+use toml_edit::{Array, ArrayOfTables, Decor, Document, InlineTable, Item, Key, Table, Value};
+
+use crate::Quiz;
+
+/// The order of the keys of a question. Other keys follow in their original order.
+const QUESTION_KEY_ORDER: [&str; 11] = [
+  "id",
+  "type",
+  "multipart",
+  "tags",
+  "difficulty",
+  "objectives",
+  "points",
+  "prompt",
+  "answer",
+  "hints",
+  "context",
+];
+
+/// Arrays that would make a line longer than this are written with one element per line.
+const MAX_WIDTH: usize = 80;
+
+const INDENT: &str = "  ";
+
+/// Returns the comment lines in a decor prefix, e.g. the lines above a key.
+fn leading_comments(decor: &Decor) -> Vec<String> {
+  let prefix = decor.prefix().and_then(|s| s.as_str()).unwrap_or("");
+  prefix
+    .lines()
+    .map(str::trim)
+    .filter(|line| line.starts_with('#'))
+    .map(String::from)
+    .collect()
+}
+
+/// Returns the comment in a decor suffix, e.g. at the end of the line of a value.
+fn trailing_comment(decor: &Decor) -> Option<String> {
+  let suffix = decor.suffix().and_then(|s| s.as_str()).unwrap_or("");
+  suffix
+    .find('#')
+    .map(|idx| suffix[idx..].trim_end().to_string())
+}
+
+fn format_key(key: &str) -> String {
+  let is_bare = !key.is_empty()
+    && key
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+  if is_bare {
+    key.to_string()
+  } else {
+    format_string(key, false)
+  }
+}
+
+/// Formats a string as a basic string, or as a literal string if it contains backslashes
+/// that would otherwise need to be escaped.
+fn format_string(s: &str, multiline: bool) -> String {
+  let delim = if multiline { "'''" } else { "'" };
+  let has_control = s
+    .chars()
+    .any(|c| c.is_control() && c != '\t' && (c != '\n' || !multiline));
+  if s.contains('\\') && !s.contains(delim) && !has_control {
+    return if multiline {
+      format!("'''\n{s}'''")
+    } else {
+      format!("'{s}'")
+    };
+  }
+
+  let mut escaped = String::with_capacity(s.len());
+  let mut quotes = 0;
+  for c in s.chars() {
+    quotes = if c == '"' { quotes + 1 } else { 0 };
+    match c {
+      '\\' => escaped.push_str("\\\\"),
+      // In a multi-line string, only a run of three quotes must be escaped.
+      '"' if !multiline || quotes == 3 => {
+        escaped.push_str("\\\"");
+        quotes = 0;
+      }
+      '\n' if multiline => escaped.push('\n'),
+      '\n' => escaped.push_str("\\n"),
+      '\t' => escaped.push('\t'),
+      '\r' => escaped.push_str("\\r"),
+      c if c.is_control() => escaped.push_str(&format!("\\u{:04X}", c as u32)),
+      c => escaped.push(c),
+    }
+  }
+
+  if multiline {
+    // A quote right before the closing delimiter would merge with it.
+    if escaped.ends_with('"') {
+      escaped.pop();
+      escaped.push_str("\\\"");
+    }
+    format!("\"\"\"\n{escaped}\"\"\"")
+  } else {
+    format!("\"{escaped}\"")
+  }
+}
+
+fn has_comments(array: &Array) -> bool {
+  let decor_has_comment = |decor: &Decor| {
+    [decor.prefix(), decor.suffix()]
+      .into_iter()
+      .flatten()
+      .any(|s| s.as_str().is_some_and(|s| s.contains('#')))
+  };
+  array.iter().any(|value| decor_has_comment(value.decor()))
+    || array.trailing().as_str().is_some_and(|s| s.contains('#'))
+}
+
+/// Formats a value that starts at column `indent` of a line.
+fn format_value(value: &Value, indent: usize) -> String {
+  match value {
+    Value::String(s) => {
+      let s = s.value();
+      format_string(s, s.contains('\n'))
+    }
+    Value::Integer(n) => n.display_repr().into_owned(),
+    Value::Float(n) => n.display_repr().into_owned(),
+    Value::Boolean(b) => b.display_repr().into_owned(),
+    Value::Datetime(dt) => dt.display_repr().into_owned(),
+    Value::Array(array) => format_array(array, indent),
+    Value::InlineTable(table) => format_inline_table(table),
+  }
+}
+
+fn format_array(array: &Array, indent: usize) -> String {
+  if has_comments(array) {
+    // Don't risk losing comments between elements.
+    let mut array = array.clone();
+    array.decor_mut().clear();
+    return array.to_string();
+  }
+
+  let elements = array
+    .iter()
+    .map(|value| format_value(value, indent + INDENT.len()))
+    .collect::<Vec<_>>();
+  let one_line = format!("[{}]", elements.join(", "));
+  if indent + one_line.len() <= MAX_WIDTH && !one_line.contains('\n') {
+    return one_line;
+  }
+
+  let mut output = String::from("[\n");
+  for (i, element) in elements.iter().enumerate() {
+    output.push_str(INDENT);
+    output.push_str(element);
+    if i + 1 < elements.len() {
+      output.push(',');
+    }
+    output.push('\n');
+  }
+  output.push(']');
+  output
+}
+
+fn format_inline_table(table: &InlineTable) -> String {
+  let entries = table
+    .iter()
+    .map(|(key, value)| format!("{} = {}", format_key(key), format_value(value, 0)))
+    .collect::<Vec<_>>();
+  if entries.is_empty() {
+    "{}".to_string()
+  } else {
+    format!("{{ {} }}", entries.join(", "))
+  }
+}
+
+/// A `key = value` line within a table, where the key may be dotted.
+struct Entry<'a> {
+  path: Vec<&'a str>,
+  value: Value,
+  comments: Vec<String>,
+  trailing: Option<String>,
+}
+
+/// Flattens the item at `path` into entries with dotted keys.
+fn flatten<'a>(path: Vec<&'a str>, key: &Key, item: &'a Item, entries: &mut Vec<Entry<'a>>) {
+  let mut comments = leading_comments(key.decor());
+  match item {
+    Item::None => {}
+    Item::Value(Value::InlineTable(table)) => {
+      comments.extend(leading_comments(table.decor()));
+      let start = entries.len();
+      for (child_key, _) in table.iter() {
+        let (child_key_full, child_item) = table.get_key_value(child_key).unwrap();
+        let mut child_path = path.clone();
+        child_path.push(child_key);
+        flatten(child_path, child_key_full, child_item, entries);
+      }
+      attach_comments(entries, start, comments);
+    }
+    Item::Value(value) => entries.push(Entry {
+      path,
+      value: value.clone(),
+      comments,
+      trailing: trailing_comment(value.decor()),
+    }),
+    Item::Table(table) => {
+      comments.extend(leading_comments(table.decor()));
+      let start = entries.len();
+      for (child_key, child) in table.iter() {
+        let (child_key_full, _) = table.get_key_value(child_key).unwrap();
+        let mut child_path = path.clone();
+        child_path.push(child_key);
+        flatten(child_path, child_key_full, child, entries);
+      }
+      attach_comments(entries, start, comments);
+    }
+    Item::ArrayOfTables(tables) => {
+      let array = tables.clone().into_array();
+      entries.push(Entry {
+        path,
+        value: Value::Array(array),
+        comments,
+        trailing: None,
+      });
+    }
+  }
+}
+
+/// Attaches the comments of a table to the first entry flattened from it.
+fn attach_comments(entries: &mut [Entry], start: usize, mut comments: Vec<String>) {
+  if let Some(first) = entries.get_mut(start) {
+    comments.append(&mut first.comments);
+    first.comments = comments;
+  }
+}
+
+fn write_entries(output: &mut String, entries: &[Entry]) {
+  for entry in entries {
+    for comment in &entry.comments {
+      output.push_str(comment);
+      output.push('\n');
+    }
+    let key = entry
+      .path
+      .iter()
+      .map(|k| format_key(k))
+      .collect::<Vec<_>>()
+      .join(".");
+    let prefix = format!("{key} = ");
+    output.push_str(&prefix);
+    output.push_str(&format_value(&entry.value, prefix.len()));
+    if let Some(comment) = &entry.trailing {
+      output.push(' ');
+      output.push_str(comment);
+    }
+    output.push('\n');
+  }
+}
+
+fn table_entries<'a>(table: &'a Table, order: &[&str]) -> Vec<Entry<'a>> {
+  let rank = |key: &str| order.iter().position(|k| *k == key).unwrap_or(order.len());
+  let mut keys = table.iter().map(|(key, _)| key).collect::<Vec<_>>();
+  keys.sort_by_key(|key| rank(key));
+
+  let mut entries = Vec::new();
+  for key in keys {
+    let (key_full, item) = table.get_key_value(key).unwrap();
+    flatten(vec![key], key_full, item, &mut entries);
+  }
+  entries
+}
+
+/// Writes a `[header]` or `[[header]]` line, preceded by a blank line and any comments.
+fn write_header(output: &mut String, header: &str, decor: &Decor) {
+  if !output.is_empty() {
+    output.push('\n');
+  }
+  for comment in leading_comments(decor) {
+    output.push_str(&comment);
+    output.push('\n');
+  }
+  output.push_str(header);
+  if let Some(comment) = trailing_comment(decor) {
+    output.push(' ');
+    output.push_str(&comment);
+  }
+  output.push('\n');
+}
+
+/// Writes a quiz document in the style of the README, keeping its comments.
+fn write_document(doc: &Document) -> String {
+  let root = doc.as_table();
+  let mut output = String::new();
+
+  // Top-level values like `spellcheckIgnore` come first, then the questions, then other tables.
+  let mut values = Vec::new();
+  let mut tables = Vec::new();
+  for (key, item) in root.iter() {
+    let (key_full, _) = root.get_key_value(key).unwrap();
+    match item {
+      Item::Value(Value::InlineTable(table)) if key != "questions" => {
+        tables.push((key, key_full, table.clone().into_table()))
+      }
+      Item::Value(_) => flatten(vec![key], key_full, item, &mut values),
+      Item::Table(table) if table.is_dotted() => flatten(vec![key], key_full, item, &mut values),
+      Item::Table(table) => tables.push((key, key_full, table.clone())),
+      Item::ArrayOfTables(_) | Item::None => {}
+    }
+  }
+  write_entries(&mut output, &values);
+
+  for (key, item) in root.iter() {
+    let Item::ArrayOfTables(array) = item else {
+      continue;
+    };
+    let order = if key == "questions" {
+      &QUESTION_KEY_ORDER[..]
+    } else {
+      &[]
+    };
+    for table in array.iter() {
+      write_header(
+        &mut output,
+        &format!("[[{}]]", format_key(key)),
+        table.decor(),
+      );
+      write_entries(&mut output, &table_entries(table, order));
+    }
+  }
+
+  for (key, key_full, table) in &tables {
+    let mut decor = table.decor().clone();
+    let mut prefix = leading_comments(key_full.decor());
+    prefix.extend(leading_comments(&decor));
+    decor.set_prefix(
+      prefix
+        .iter()
+        .map(|line| format!("{line}\n"))
+        .collect::<String>(),
+    );
+    write_header(&mut output, &format!("[{}]", format_key(key)), &decor);
+    write_entries(&mut output, &table_entries(table, &[]));
+  }
+
+  let trailing = doc.trailing().as_str().unwrap_or("");
+  let trailing = trailing
+    .lines()
+    .map(str::trim)
+    .filter(|line| line.starts_with('#'))
+    .collect::<Vec<_>>();
+  if !trailing.is_empty() {
+    output.push('\n');
+    for comment in trailing {
+      output.push_str(comment);
+      output.push('\n');
+    }
+  }
+
+  output
+}
+
+/// Formats the TOML source of a quiz in the style of [`Quiz::to_toml_string`], keeping its
+/// comments.
+pub fn format_document(contents: &str) -> Result<String> {
+  let doc = contents.parse::<Document>()?;
+  Ok(write_document(&doc))
+}
+
+/// Returns the tables of `value` if it is an array of tables, like `questions`.
+fn array_of_tables(value: &Value) -> Option<Vec<&InlineTable>> {
+  let Value::Array(array) = value else {
+    return None;
+  };
+  let tables = array
+    .iter()
+    .map(Value::as_inline_table)
+    .collect::<Option<Vec<_>>>()?;
+  (!tables.is_empty()).then_some(tables)
+}
+
+/// A TOML value that is deserialized with its keys in their original order.
+///
+/// `None` for a null, which TOML can't represent, so that it is left out.
+struct OrderedValue(Option<Value>);
+
+impl<'de> Deserialize<'de> for OrderedValue {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer.deserialize_any(OrderedValueVisitor)
+  }
+}
+
+struct OrderedValueVisitor;
+
+impl<'de> Visitor<'de> for OrderedValueVisitor {
+  type Value = OrderedValue;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("a TOML value")
+  }
+
+  fn visit_bool<E>(self, b: bool) -> Result<OrderedValue, E> {
+    Ok(OrderedValue(Some(b.into())))
+  }
+
+  fn visit_i64<E>(self, n: i64) -> Result<OrderedValue, E> {
+    Ok(OrderedValue(Some(n.into())))
+  }
+
+  fn visit_u64<E: de::Error>(self, n: u64) -> Result<OrderedValue, E> {
+    let n = i64::try_from(n).map_err(|_| E::custom(format!("{n} is too large for TOML")))?;
+    Ok(OrderedValue(Some(n.into())))
+  }
+
+  fn visit_f64<E>(self, n: f64) -> Result<OrderedValue, E> {
+    Ok(OrderedValue(Some(n.into())))
+  }
+
+  fn visit_str<E>(self, s: &str) -> Result<OrderedValue, E> {
+    Ok(OrderedValue(Some(s.into())))
+  }
+
+  fn visit_unit<E>(self) -> Result<OrderedValue, E> {
+    Ok(OrderedValue(None))
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<OrderedValue, A::Error> {
+    let mut array = Array::new();
+    while let Some(OrderedValue(value)) = seq.next_element()? {
+      array.extend(value);
+    }
+    Ok(OrderedValue(Some(Value::Array(array))))
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<OrderedValue, A::Error> {
+    let mut table = InlineTable::new();
+    while let Some((key, OrderedValue(value))) = map.next_entry::<String, _>()? {
+      if let Some(value) = value {
+        table.insert(&key, value);
+      }
+    }
+    Ok(OrderedValue(Some(Value::InlineTable(table))))
+  }
+}
+
+impl Quiz {
+  /// Serializes the quiz as TOML in the style of the examples in the mdbook-quiz README,
+  /// which is the style written by `mdbook-quiz fmt`.
+  pub fn to_toml_string(&self) -> Result<String> {
+    // JSON keeps the order of each struct's fields, unlike a `toml::Value`.
+    let json = serde_json::to_string(self)?;
+    let Some(Value::InlineTable(root)) = serde_json::from_str::<OrderedValue>(&json)?.0 else {
+      unreachable!("a quiz is serialized as a table")
+    };
+
+    let mut doc = Document::new();
+    for (key, value) in root.iter() {
+      let item = if let Some(tables) = array_of_tables(value) {
+        let mut array = ArrayOfTables::new();
+        for table in tables {
+          array.push(table.clone().into_table());
+        }
+        Item::ArrayOfTables(array)
+      } else if let Value::InlineTable(table) = value {
+        // Maps like `multipart` are serialized in an arbitrary order.
+        let mut table = table.clone();
+        table.sort_values();
+        Item::Table(table.into_table())
+      } else {
+        Item::Value(value.clone())
+      };
+      doc.insert(key, item);
+    }
+    Ok(write_document(&doc))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn format_normalizes_style() -> Result<()> {
+    let contents = r#"
+# Questions about variables
+[[questions]]
+answer = { doesCompile = false, lineNumber = 4 }
+context = "Line 4 mutates `x`." # why it fails
+type = "Tracing"
+
+[questions.prompt]
+# The program
+program = "fn main() {\n  let x = 1;\n  x += 1;\n}\n"
+
+[[questions]]
+type   =   'ShortAnswer'
+prompt.prompt = "What is the keyword used after `let` to indicate that a variable can be mutated?"
+answer.answer = "mut"
+id = "dbbaea6a"
+multipart = "a"
+
+[multipart]
+a = """Some context"""
+"#;
+    let expected = r#"# Questions about variables
+[[questions]]
+type = "Tracing"
+# The program
+prompt.program = """
+fn main() {
+  let x = 1;
+  x += 1;
+}
+"""
+answer.doesCompile = false
+answer.lineNumber = 4
+context = "Line 4 mutates `x`." # why it fails
+
+[[questions]]
+id = "dbbaea6a"
+type = "ShortAnswer"
+multipart = "a"
+prompt.prompt = "What is the keyword used after `let` to indicate that a variable can be mutated?"
+answer.answer = "mut"
+
+[multipart]
+a = "Some context"
+"#;
+    let formatted = format_document(contents)?;
+    assert_eq!(formatted, expected);
+    assert_eq!(
+      format_document(&formatted)?,
+      formatted,
+      "formatting is not idempotent"
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn format_strings_and_arrays() -> Result<()> {
+    assert_eq!(format_string("a \"b\"", false), r#""a \"b\"""#);
+    assert_eq!(format_string(r"`\n`", false), r"'`\n`'");
+    assert_eq!(format_string("x\n\"", true), "\"\"\"\nx\n\\\"\"\"\"");
+    assert_eq!(
+      format_string("println!(\"\\n\");\n", true),
+      "'''\nprintln!(\"\\n\");\n'''"
+    );
+
+    let contents = r#"
+[[questions]]
+type = "MultipleChoice"
+prompt.prompt = "Which is true?"
+prompt.distractors = ["`x` is stored in the immutable region of memory.", "After being defined, `x` can be changed at most once."]
+answer.answer = ["a","b"]
+"#;
+    let formatted = format_document(contents)?;
+    assert!(formatted.contains(
+      "prompt.distractors = [\n  \"`x` is stored in the immutable region of memory.\",\n"
+    ));
+    assert!(formatted.contains("answer.answer = [\"a\", \"b\"]\n"));
+    Ok(())
+  }
+
+  #[test]
+  fn format_keeps_to_toml_string() -> Result<()> {
+    use crate::*;
+
+    // `Quiz::to_toml_string` writes quizzes in the style of this formatter.
+    let mut quiz = Quiz::new([
+      ShortAnswer::new("What is the keyword for a mutable variable?", "mut")
+        .with_id("keyword")
+        .into(),
+      Tracing::new(
+        "fn main() {\n  let x = 1;\n  x += 1;\n}\n",
+        TracingAnswer::does_not_compile(3),
+      )
+      .with_context("Line 3 mutates `x`.")
+      .into(),
+      MultipleChoice::new(
+        "Which is true?",
+        "`x` is stored in the immutable region of memory.",
+        [
+          Distractor::new("After being defined, `x` can be changed at most once.")
+            .with_feedback("Not quite"),
+          "`x` is a reference.".into(),
+        ],
+      )
+      .with_multipart("vars")
+      .with_tags(["variables"])
+      .into(),
+    ]);
+    quiz.multipart = Some(std::collections::HashMap::from([(
+      "vars".to_string(),
+      "Some context.".into(),
+    )]));
+
+    let toml = quiz.to_toml_string()?;
+    assert_eq!(format_document(&toml)?, toml);
+    Ok(())
+  }
+}
@@ -15,6 +15,13 @@
 //!
 //! Note that all Rust identifiers with multiple words (e.g. `does_compile`) use camelCase keys,
 //! so should be written as `doesCompile` in the TOML.
+//!
+//! Quizzes can also be built in Rust with constructors like [`ShortAnswer::new`] and the methods
+//! of [`QuestionBuilder`]. With the `toml` feature, [`Quiz::to_toml_string`] writes a quiz as TOML
+//! in the style above, and [`format_document`] rewrites the TOML source of a quiz in that style.
+//!
+//! Tools that read or rewrite the text of a quiz can use [`Quiz::text_fields`] and
+//! [`Quiz::text_fields_mut`], which yield every Markdown and code field along with its path.
 
 #![warn(missing_docs)]
 
@@ -27,30 +34,185 @@ use ts_rs::TS;
 #[cfg(feature = "json-schema")]
 use schemars::JsonSchema;
 
+mod builder;
+#[cfg(feature = "toml")]
+mod fmt;
+pub mod visit;
+
+pub use builder::QuestionBuilder;
+#[cfg(feature = "toml")]
+pub use fmt::format_document;
+pub use visit::{PathSegment, QuizPath, TextField, TextFieldMut};
+
+/// The latest version of the quiz format, see [`Quiz::version`].
+pub const QUIZ_VERSION: u32 = 1;
+
 /// A quiz is the top-level data structure in mdbook-quiz.
 /// It represents a sequence of questions.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 pub struct Quiz {
+  /// The version of the quiz format that the quiz is written in.
+  ///
+  /// Quizzes without a version are read as version 1. Quizzes in an older version can be
+  /// upgraded to [`QUIZ_VERSION`] with `mdbook-quiz migrate`.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub version: Option<u32>,
+
   /// The questions of the quiz.
+  #[serde(default)]
   pub questions: Vec<Question>,
 
+  /// Questions reused from other quiz files, which are added to the quiz after its `questions`.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub include: Option<Vec<QuizInclude>>,
+
+  /// Pools of questions from other quiz files, from which a sample is added to the quiz
+  /// after its `questions` and included questions.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub pools: Option<Vec<QuestionPool>>,
+
   /// Context for multipart questions.
   ///
   /// Maps from a string key to a description of the question context.
   #[cfg_attr(feature = "ts", ts(optional))]
   pub multipart: Option<HashMap<String, Markdown>>,
+
+  /// Named pieces of text that are shared by the quiz's questions.
+  ///
+  /// A `{{snippet name}}` placeholder in any string of a question or a multipart context
+  /// is replaced by the snippet with that name.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub snippets: Option<HashMap<String, String>>,
+
+  /// Words that the spellchecker should accept in this quiz, in addition to the book's dictionary.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  #[serde(rename = "spellcheckIgnore")]
+  pub spellcheck_ignore: Option<Vec<String>>,
+
+  /// Settings for this quiz, which override the book's `[preprocessor.quiz]` config.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub settings: Option<QuizSettings>,
+}
+
+/// Settings for a single quiz. Unset settings fall back to the book's config.
+///
+/// Unknown settings are rejected.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS), ts(export))]
+#[cfg_attr(feature = "json-schema", derive(JsonSchema))]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct QuizSettings {
+  /// If true, then the quiz takes up the web page's full screen during use.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub fullscreen: Option<bool>,
+
+  /// If true, then a user's answers are cached in their browser and shown when they revisit the quiz.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub cache_answers: Option<bool>,
+
+  /// If true, then a user can retry the questions they answered incorrectly. True by default.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub allow_retry: Option<bool>,
+
+  /// If true, then the questions are shown in a random order for each user.
+  ///
+  /// The questions of a multipart group stay together and in order.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub shuffle: Option<bool>,
+
+  /// The percentage of points, from 0 to 100, that a user needs to pass the quiz.
+  ///
+  /// If set, then the user is told whether they passed after answering every question.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub passing_score: Option<u32>,
+}
+
+/// A selection of questions from another quiz file, which are reused by a quiz.
+///
+/// Included questions keep their IDs, so answers to a question are identified the same way
+/// in every quiz that uses it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS), ts(export))]
+#[cfg_attr(feature = "json-schema", derive(JsonSchema))]
+pub struct QuizInclude {
+  /// Path to the quiz file with the questions, relative to the quiz file.
+  pub path: String,
+
+  /// IDs of the questions to include.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub ids: Option<Vec<String>>,
+
+  /// If defined, questions with at least one of these tags are also included.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub tags: Option<Vec<String>>,
+}
+
+/// A set of candidate questions, of which `count` are randomly sampled into a quiz.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS), ts(export))]
+#[cfg_attr(feature = "json-schema", derive(JsonSchema))]
+pub struct QuestionPool {
+  /// Paths to quiz files whose questions are the candidates, relative to the quiz file.
+  pub files: Vec<String>,
+
+  /// The number of questions to sample.
+  pub count: usize,
+
+  /// If defined, only questions with at least one of these tags are candidates,
+  /// and the sample is spread evenly across the tags.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub tags: Option<Vec<String>>,
+
+  /// When the sample is drawn. Defaults to [`PoolSampling::Build`].
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub sample: Option<PoolSampling>,
+
+  /// The seed for sampling at build time.
+  ///
+  /// Defaults to a seed derived from the path of the quiz, so that each build draws the same sample.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub seed: Option<u32>,
+}
+
+impl QuestionPool {
+  /// Returns the number of questions to sample for each of [`QuestionPool::tags`],
+  /// spreading [`QuestionPool::count`] as evenly as possible with earlier tags getting any extra.
+  pub fn tag_counts(&self) -> Vec<(&str, usize)> {
+    let tags = self.tags.as_deref().unwrap_or_default();
+    tags
+      .iter()
+      .enumerate()
+      .map(|(i, tag)| {
+        let extra = usize::from(i < self.count % tags.len());
+        (tag.as_str(), self.count / tags.len() + extra)
+      })
+      .collect()
+  }
+}
+
+/// When the questions of a [`QuestionPool`] are sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS), ts(export))]
+#[cfg_attr(feature = "json-schema", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum PoolSampling {
+  /// Once per build, so every learner gets the same questions.
+  Build,
+
+  /// Separately for each learner, in the browser. All candidate questions are embedded in the page.
+  Learner,
 }
 
 /// A [Markdown](https://commonmark.org/help/) string.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 pub struct Markdown(pub String);
 
 /// An individual question. One of several fixed types.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 #[serde(tag = "type")]
@@ -63,8 +225,20 @@ pub enum Question {
   MultipleChoice(MultipleChoice),
 }
 
+impl Question {
+  /// Returns the [`QuestionFields::tags`] of the question.
+  pub fn tags(&self) -> &[String] {
+    let tags = match self {
+      Question::ShortAnswer(q) => &q.0.tags,
+      Question::Tracing(q) => &q.0.tags,
+      Question::MultipleChoice(q) => &q.0.tags,
+    };
+    tags.as_deref().unwrap_or_default()
+  }
+}
+
 /// Fields common to all question types.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 #[serde(rename_all = "camelCase")]
@@ -80,12 +254,39 @@ pub struct QuestionFields<Prompt, Answer> {
   #[cfg_attr(feature = "ts", ts(optional))]
   pub multipart: Option<String>,
 
+  /// Labels for grouping questions, e.g. when sampling from a [`QuestionPool`].
+  ///
+  /// If the book configures a vocabulary of tags, then every tag must be in it.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub tags: Option<Vec<String>>,
+
+  /// How difficult the question is.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub difficulty: Option<Difficulty>,
+
+  /// The learning objectives that the question assesses, e.g. for coverage reports.
+  ///
+  /// If the book configures a vocabulary of objectives, then every objective must be in it.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub objectives: Option<Vec<String>>,
+
+  /// How much the question is worth, for weighted scoring. Questions are worth 1 point by default.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub points: Option<u32>,
+
   /// The contents of the prompt. Depends on the question type.
   pub prompt: Prompt,
 
   /// The contents of the answer. Depends on the question type.
   pub answer: Answer,
 
+  /// Hints that help the user answer the question.
+  ///
+  /// Shown one at a time, in order, when the user asks for another hint before answering.
+  /// The number of hints the user opened is recorded with their answer.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub hints: Option<Vec<Markdown>>,
+
   /// Additional context that explains the correct answer.
   ///
   /// Only shown after the user has answered correctly or given up.
@@ -99,9 +300,23 @@ pub struct QuestionFields<Prompt, Answer> {
   pub prompt_explanation: Option<bool>,
 }
 
+/// How difficult a question is, set by [`QuestionFields::difficulty`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS), ts(export))]
+#[cfg_attr(feature = "json-schema", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+  /// An easy question.
+  Easy,
+  /// A question of medium difficulty.
+  Medium,
+  /// A hard question.
+  Hard,
+}
+
 /// The kind of response format (and subsequent input method) that accompanies
 /// a given short answer questions.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 #[serde(rename_all = "lowercase")]
@@ -117,7 +332,7 @@ pub enum ShortAnswerResponseFormat {
 }
 
 /// A prompt for a [`ShortAnswer`] question.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 pub struct ShortAnswerPrompt {
@@ -130,7 +345,7 @@ pub struct ShortAnswerPrompt {
 }
 
 /// An answer for a [`ShortAnswer`] question.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 pub struct ShortAnswerAnswer {
@@ -143,22 +358,33 @@ pub struct ShortAnswerAnswer {
 }
 
 /// A question where users type in a response.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 pub struct ShortAnswer(pub QuestionFields<ShortAnswerPrompt, ShortAnswerAnswer>);
 
 /// A prompt for a [`Tracing`] question.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
+#[serde(rename_all = "camelCase")]
 pub struct TracingPrompt {
   /// The contents of the program to trace.
+  ///
+  /// Can be omitted if the program is read from a file with `program_file`.
+  #[serde(default)]
   pub program: String,
+
+  /// A Rust file whose contents are the program, relative to the quiz file.
+  ///
+  /// Like mdBook's `{{#include}}`, the path can be followed by an anchor name
+  /// (`file.rs:name`) or a line range (`file.rs:2:10`). The program is inlined when the book is built.
+  #[cfg_attr(feature = "ts", ts(optional))]
+  pub program_file: Option<String>,
 }
 
 /// An answer for a [`Tracing`] question.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 #[serde(rename_all = "camelCase")]
@@ -176,13 +402,13 @@ pub struct TracingAnswer {
 }
 
 /// A question where users guess the output of a program.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 pub struct Tracing(pub QuestionFields<TracingPrompt, TracingAnswer>);
 
 /// A prompt for a [`MultipleChoice`] question.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 #[serde(rename_all = "camelCase")]
@@ -191,7 +417,7 @@ pub struct MultipleChoicePrompt {
   pub prompt: Markdown,
 
   /// An array of incorrect answers.
-  pub distractors: Vec<Markdown>,
+  pub distractors: Vec<Distractor>,
 
   /// If defined, don't randomize distractors and put answer at this index.
   #[cfg_attr(feature = "ts", ts(optional))]
@@ -202,8 +428,46 @@ pub struct MultipleChoicePrompt {
   pub sort_answers: Option<bool>,
 }
 
+/// An incorrect answer for a [`MultipleChoice`] question.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS), ts(export))]
+#[cfg_attr(feature = "json-schema", derive(JsonSchema))]
+#[serde(untagged)]
+pub enum Distractor {
+  /// Just the text of the answer.
+  Text(Markdown),
+
+  /// The text of the answer, with feedback that explains why it is incorrect.
+  ///
+  /// The feedback is shown to users who chose this answer.
+  WithFeedback {
+    /// The text of the answer.
+    text: Markdown,
+
+    /// Why the answer is incorrect.
+    feedback: Markdown,
+  },
+}
+
+impl Distractor {
+  /// Returns the text of the answer.
+  pub fn text(&self) -> &Markdown {
+    match self {
+      Distractor::Text(text) | Distractor::WithFeedback { text, .. } => text,
+    }
+  }
+
+  /// Returns the feedback for the answer, if any.
+  pub fn feedback(&self) -> Option<&Markdown> {
+    match self {
+      Distractor::Text(_) => None,
+      Distractor::WithFeedback { feedback, .. } => Some(feedback),
+    }
+  }
+}
+
 /// The type of response for a [`MultipleChoice`] question.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 #[serde(untagged)]
@@ -216,7 +480,7 @@ pub enum MultipleChoiceAnswerFormat {
 }
 
 /// An answer for a [`MultipleChoice`] question.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 pub struct MultipleChoiceAnswer {
@@ -225,7 +489,7 @@ pub struct MultipleChoiceAnswer {
 }
 
 /// A question where users select among several possible answers.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ts", derive(TS), ts(export))]
 #[cfg_attr(feature = "json-schema", derive(JsonSchema))]
 pub struct MultipleChoice(pub QuestionFields<MultipleChoicePrompt, MultipleChoiceAnswer>);
@@ -249,6 +513,74 @@ context = "Ok!"
     println!("{_quiz:#?}");
   }
 
+  #[test]
+  fn test_parse_distractors() {
+    let contents = r#"
+[[questions]]
+type = "MultipleChoice"
+prompt.prompt = "What's up"
+answer.answer = "A"
+prompt.distractors = ["B", { text = "C", feedback = "Not C" }]
+"#;
+    let quiz: Quiz = toml::from_str(contents).unwrap();
+    let Question::MultipleChoice(q) = &quiz.questions[0] else {
+      unreachable!()
+    };
+    let distractors = &q.0.prompt.distractors;
+    assert_eq!(distractors[0].text().0, "B");
+    assert!(distractors[0].feedback().is_none());
+    assert_eq!(distractors[1].text().0, "C");
+    assert_eq!(distractors[1].feedback().unwrap().0, "Not C");
+  }
+
+  #[test]
+  fn test_parse_metadata() {
+    let contents = r#"
+[[questions]]
+type = "ShortAnswer"
+tags = ["ownership"]
+difficulty = "hard"
+objectives = ["borrowck"]
+points = 2
+prompt.prompt = "What's up"
+answer.answer = "A"
+"#;
+    let quiz: Quiz = toml::from_str(contents).unwrap();
+    let Question::ShortAnswer(q) = &quiz.questions[0] else {
+      unreachable!()
+    };
+    assert_eq!(q.0.difficulty, Some(Difficulty::Hard));
+    assert_eq!(
+      q.0.objectives.as_deref(),
+      Some(&["borrowck".to_string()][..])
+    );
+    assert_eq!(q.0.points, Some(2));
+
+    let invalid = contents.replace("\"hard\"", "\"impossible\"");
+    assert!(toml::from_str::<Quiz>(&invalid).is_err());
+  }
+
+  #[test]
+  fn test_parse_settings() {
+    let contents = r#"
+settings.fullscreen = true
+settings.passingScore = 80
+
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "What's up"
+answer.answer = "A"
+"#;
+    let quiz: Quiz = toml::from_str(contents).unwrap();
+    let settings = quiz.settings.unwrap();
+    assert_eq!(settings.fullscreen, Some(true));
+    assert_eq!(settings.passing_score, Some(80));
+    assert_eq!(settings.shuffle, None);
+
+    let unknown = contents.replace("fullscreen", "fulscreen");
+    assert!(toml::from_str::<Quiz>(&unknown).is_err());
+  }
+
   #[test]
   fn test_parse_invalid_schema() {
     let contents = r#"
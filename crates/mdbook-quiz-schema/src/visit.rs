@@ -0,0 +1,321 @@
+//! Traverses the Markdown and code fields of a quiz, along with their paths in the quiz file.
+//!
+//! ```
+//! use mdbook_quiz_schema::*;
+//!
+//! let quiz = Quiz::new([MultipleChoice::new("What is 1 + 1?", "2", ["3"]).into()]);
+//! let paths = quiz
+//!   .text_fields()
+//!   .map(|(path, _)| path.to_string())
+//!   .collect::<Vec<_>>();
+//! assert_eq!(
+//!   paths,
+//!   [
+//!     "questions[0].prompt.prompt",
+//!     "questions[0].prompt.distractors[0]",
+//!     "questions[0].answer.answer"
+//!   ]
+//! );
+//! ```
+
+use std::fmt;
+
+use crate::*;
+
+/// A segment of a path from the root of a quiz file to one of its values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+  /// A key in a table.
+  Key(String),
+
+  /// An index into an array.
+  Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PathSegment::Key(key) => write!(f, ".{key}"),
+      PathSegment::Index(idx) => write!(f, "[{idx}]"),
+    }
+  }
+}
+
+/// The path from the root of a quiz file to one of its values,
+/// displayed like `questions[3].prompt.distractors[1]`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QuizPath(pub Vec<PathSegment>);
+
+impl QuizPath {
+  /// Returns the path to `key` in the table at this path.
+  pub fn key(&self, key: &str) -> Self {
+    let mut path = self.clone();
+    path.0.push(PathSegment::Key(key.to_string()));
+    path
+  }
+
+  /// Returns the path to element `index` of the array at this path.
+  pub fn index(&self, index: usize) -> Self {
+    let mut path = self.clone();
+    path.0.push(PathSegment::Index(index));
+    path
+  }
+}
+
+impl fmt::Display for QuizPath {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, segment) in self.0.iter().enumerate() {
+      match segment {
+        PathSegment::Key(key) if i == 0 => write!(f, "{key}")?,
+        segment => write!(f, "{segment}")?,
+      }
+    }
+    Ok(())
+  }
+}
+
+/// A field of a quiz that contains text, yielded by [`Quiz::text_fields`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField<'a> {
+  /// A Markdown field, like a prompt or a context.
+  Markdown(&'a Markdown),
+
+  /// A Rust program, like the program of a Tracing question.
+  Code(&'a str),
+}
+
+/// A field of a quiz that contains text, yielded by [`Quiz::text_fields_mut`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum TextFieldMut<'a> {
+  /// A Markdown field, like a prompt or a context.
+  Markdown(&'a mut Markdown),
+
+  /// A Rust program, like the program of a Tracing question.
+  Code(&'a mut String),
+}
+
+/// Pushes the text fields of `$question` under `$path` onto `$out`, as `$field`s.
+/// The fields are borrowed mutably if `mut` is given.
+macro_rules! question_text_fields {
+  ($question:expr, $path:expr, $out:expr, $field:ident $(, $m:tt)?) => {{
+    let path: &QuizPath = $path;
+    let out = $out;
+    let prompt_path = path.key("prompt");
+    let answer_path = path.key("answer");
+    let (hints, context) = match $question {
+      Question::ShortAnswer(ShortAnswer(QuestionFields {
+        prompt,
+        hints,
+        context,
+        ..
+      })) => {
+        out.push((
+          prompt_path.key("prompt"),
+          $field::Markdown(&$($m)? prompt.prompt),
+        ));
+        (hints, context)
+      }
+      Question::Tracing(Tracing(QuestionFields {
+        prompt,
+        hints,
+        context,
+        ..
+      })) => {
+        // A program read from a file is not in the quiz.
+        if prompt.program_file.is_none() {
+          out.push((
+            prompt_path.key("program"),
+            $field::Code(&$($m)? prompt.program),
+          ));
+        }
+        (hints, context)
+      }
+      Question::MultipleChoice(MultipleChoice(QuestionFields {
+        prompt,
+        answer,
+        hints,
+        context,
+        ..
+      })) => {
+        out.push((
+          prompt_path.key("prompt"),
+          $field::Markdown(&$($m)? prompt.prompt),
+        ));
+        let distractors_path = prompt_path.key("distractors");
+        for (i, distractor) in IntoIterator::into_iter(&$($m)? prompt.distractors).enumerate() {
+          let distractor_path = distractors_path.index(i);
+          match distractor {
+            Distractor::Text(text) => out.push((distractor_path, $field::Markdown(text))),
+            Distractor::WithFeedback { text, feedback } => {
+              out.push((distractor_path.key("text"), $field::Markdown(text)));
+              out.push((distractor_path.key("feedback"), $field::Markdown(feedback)));
+            }
+          }
+        }
+        let answers_path = answer_path.key("answer");
+        match &$($m)? answer.answer {
+          MultipleChoiceAnswerFormat::Single(answer) => {
+            out.push((answers_path, $field::Markdown(answer)))
+          }
+          MultipleChoiceAnswerFormat::Multi(answers) => {
+            for (i, answer) in IntoIterator::into_iter(answers).enumerate() {
+              out.push((answers_path.index(i), $field::Markdown(answer)));
+            }
+          }
+        }
+        (hints, context)
+      }
+    };
+
+    if let Some(hints) = hints {
+      let hints_path = path.key("hints");
+      for (i, hint) in IntoIterator::into_iter(hints).enumerate() {
+        out.push((hints_path.index(i), $field::Markdown(hint)));
+      }
+    }
+    if let Some(context) = context {
+      out.push((path.key("context"), $field::Markdown(context)));
+    }
+  }};
+}
+
+impl Question {
+  /// Returns every Markdown and code field of the question, with its path from the question.
+  pub fn text_fields(&self) -> impl Iterator<Item = (QuizPath, TextField<'_>)> {
+    let mut fields = Vec::new();
+    question_text_fields!(self, &QuizPath::default(), &mut fields, TextField);
+    fields.into_iter()
+  }
+
+  /// Like [`Question::text_fields`], but the fields can be changed.
+  pub fn text_fields_mut(&mut self) -> impl Iterator<Item = (QuizPath, TextFieldMut<'_>)> {
+    let mut fields = Vec::new();
+    question_text_fields!(self, &QuizPath::default(), &mut fields, TextFieldMut, mut);
+    fields.into_iter()
+  }
+}
+
+impl Quiz {
+  /// Returns every Markdown and code field of the quiz's questions and multipart contexts,
+  /// with its path from the root of the quiz, in the order they appear in the quiz.
+  ///
+  /// Questions that are included or sampled from pools are not part of the quiz.
+  pub fn text_fields(&self) -> impl Iterator<Item = (QuizPath, TextField<'_>)> {
+    let mut fields = Vec::new();
+    let questions_path = QuizPath::default().key("questions");
+    for (i, question) in self.questions.iter().enumerate() {
+      question_text_fields!(question, &questions_path.index(i), &mut fields, TextField);
+    }
+
+    let multipart_path = QuizPath::default().key("multipart");
+    let mut multipart = self.multipart.iter().flatten().collect::<Vec<_>>();
+    multipart.sort_by_key(|(key, _)| *key);
+    for (key, context) in multipart {
+      fields.push((multipart_path.key(key), TextField::Markdown(context)));
+    }
+
+    fields.into_iter()
+  }
+
+  /// Like [`Quiz::text_fields`], but the fields can be changed.
+  pub fn text_fields_mut(&mut self) -> impl Iterator<Item = (QuizPath, TextFieldMut<'_>)> {
+    let mut fields = Vec::new();
+    let questions_path = QuizPath::default().key("questions");
+    for (i, question) in self.questions.iter_mut().enumerate() {
+      question_text_fields!(
+        question,
+        &questions_path.index(i),
+        &mut fields,
+        TextFieldMut,
+        mut
+      );
+    }
+
+    let multipart_path = QuizPath::default().key("multipart");
+    let mut multipart = self.multipart.iter_mut().flatten().collect::<Vec<_>>();
+    multipart.sort_by_key(|(key, _)| *key);
+    for (key, context) in multipart {
+      fields.push((multipart_path.key(key), TextFieldMut::Markdown(context)));
+    }
+
+    fields.into_iter()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn quiz() -> Quiz {
+    let mut quiz = Quiz::new([
+      Tracing::new("fn main() {}", TracingAnswer::compiles(""))
+        .with_hints(["Look at `main`"])
+        .into(),
+      MultipleChoice::new(
+        "Pick",
+        vec!["a", "b"],
+        [Distractor::new("c").with_feedback("Not c")],
+      )
+      .with_multipart("m")
+      .with_context("Because")
+      .into(),
+    ]);
+    quiz.multipart = Some(HashMap::from([("m".to_string(), "Context".into())]));
+    quiz
+  }
+
+  #[test]
+  fn text_field_paths() {
+    let quiz = quiz();
+    let fields = quiz
+      .text_fields()
+      .map(|(path, field)| {
+        let text = match field {
+          TextField::Markdown(md) => md.0.as_str(),
+          TextField::Code(code) => code,
+        };
+        (path.to_string(), text)
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      fields,
+      [
+        ("questions[0].prompt.program".to_string(), "fn main() {}"),
+        ("questions[0].hints[0]".to_string(), "Look at `main`"),
+        ("questions[1].prompt.prompt".to_string(), "Pick"),
+        ("questions[1].prompt.distractors[0].text".to_string(), "c"),
+        (
+          "questions[1].prompt.distractors[0].feedback".to_string(),
+          "Not c"
+        ),
+        ("questions[1].answer.answer[0]".to_string(), "a"),
+        ("questions[1].answer.answer[1]".to_string(), "b"),
+        ("questions[1].context".to_string(), "Because"),
+        ("multipart.m".to_string(), "Context"),
+      ]
+    );
+  }
+
+  #[test]
+  fn text_fields_mut_matches_text_fields() {
+    let mut quiz = quiz();
+    let paths = quiz.text_fields().map(|(path, _)| path).collect::<Vec<_>>();
+    for (path, field) in quiz.text_fields_mut() {
+      match field {
+        TextFieldMut::Markdown(md) => md.0 = path.to_string(),
+        TextFieldMut::Code(code) => *code = path.to_string(),
+      }
+    }
+    for (path, field) in quiz.text_fields() {
+      let text = match field {
+        TextField::Markdown(md) => md.0.as_str(),
+        TextField::Code(code) => code,
+      };
+      assert_eq!(text, path.to_string());
+    }
+    assert_eq!(
+      quiz.text_fields().map(|(path, _)| path).collect::<Vec<_>>(),
+      paths
+    );
+  }
+}
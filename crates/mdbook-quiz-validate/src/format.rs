@@ -0,0 +1,706 @@
+//! Parsing of quiz files written in TOML, JSON, or YAML.
+//!
+//! TOML is parsed by the `toml` crate. JSON and YAML are parsed into a tree of [`Node`]s which
+//! remembers the span of each value, so that validation diagnostics can point into the source.
+//! A [`Node`] can be deserialized into a [`Quiz`](mdbook_quiz_schema::Quiz) or a [`toml::Value`],
+//! and converted into a [`SpannedValue`].
+
+use std::{collections::HashMap, fmt, ops::Range, path::Path};
+
+use saphyr_parser::{Event, Parser, ScalarStyle, Span};
+use serde::de::{self, DeserializeOwned, IntoDeserializer};
+
+use crate::value::{Spanned, SpannedValue, ValueKind};
+
+/// The format of a quiz file, determined by its extension.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QuizFormat {
+  /// A `.toml` file, or a file with any other extension.
+  #[default]
+  Toml,
+
+  /// A `.json` file.
+  Json,
+
+  /// A `.yaml` or `.yml` file.
+  Yaml,
+}
+
+impl QuizFormat {
+  /// Returns the format of the quiz file at `path`.
+  pub fn from_path(path: &Path) -> Self {
+    match path.extension().and_then(|ext| ext.to_str()) {
+      Some("json") => QuizFormat::Json,
+      Some("yaml" | "yml") => QuizFormat::Yaml,
+      _ => QuizFormat::Toml,
+    }
+  }
+
+  /// Parses `contents` in this format as a `T`, e.g. a [`toml::Value`].
+  pub fn parse<T: DeserializeOwned>(self, contents: &str) -> anyhow::Result<T> {
+    Ok(self.parse_spanned(contents)?)
+  }
+
+  /// Parses `contents` in this format as a [`SpannedValue`], which remembers where each value is.
+  pub fn parse_value(self, contents: &str) -> anyhow::Result<SpannedValue> {
+    Ok(match self {
+      QuizFormat::Toml => self
+        .parse_spanned::<toml_spanned_value::SpannedValue>(contents)?
+        .into(),
+      QuizFormat::Json => JsonParser::new(contents).parse()?.into_value()?,
+      QuizFormat::Yaml => parse_yaml(contents)?.into_value()?,
+    })
+  }
+
+  /// Like [`QuizFormat::parse`], but errors have the span of the offending source.
+  pub(crate) fn parse_spanned<T: DeserializeOwned>(self, contents: &str) -> Result<T, FormatError> {
+    match self {
+      QuizFormat::Toml => toml::from_str(contents).map_err(|err| {
+        // `line_col` is a zero-indexed line and a byte offset within that line.
+        let span = err.line_col().map(|(line, col)| {
+          let line_start = contents
+            .split_inclusive('\n')
+            .take(line)
+            .map(str::len)
+            .sum::<usize>();
+          let offset = (line_start + col).min(contents.len());
+          offset..offset
+        });
+        FormatError {
+          message: err.to_string(),
+          span,
+        }
+      }),
+      QuizFormat::Json => T::deserialize(&JsonParser::new(contents).parse()?),
+      QuizFormat::Yaml => T::deserialize(&parse_yaml(contents)?),
+    }
+  }
+}
+
+impl fmt::Display for QuizFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      QuizFormat::Toml => "TOML",
+      QuizFormat::Json => "JSON",
+      QuizFormat::Yaml => "YAML",
+    })
+  }
+}
+
+/// An error while parsing a quiz, located at `span` in the source if known.
+#[derive(Debug)]
+pub(crate) struct FormatError {
+  pub message: String,
+  pub span: Option<Range<usize>>,
+}
+
+impl FormatError {
+  fn new(message: impl fmt::Display, span: Range<usize>) -> Self {
+    FormatError {
+      message: message.to_string(),
+      span: Some(span),
+    }
+  }
+
+  /// Locates the error at `span`, unless a more precise span is already known.
+  fn or_span(mut self, span: &Range<usize>) -> Self {
+    self.span.get_or_insert_with(|| span.clone());
+    self
+  }
+}
+
+impl fmt::Display for FormatError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.message)
+  }
+}
+
+impl std::error::Error for FormatError {}
+
+impl de::Error for FormatError {
+  fn custom<T: fmt::Display>(msg: T) -> Self {
+    FormatError {
+      message: msg.to_string(),
+      span: None,
+    }
+  }
+}
+
+/// A JSON or YAML value, with the byte range of its source.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+  span: Range<usize>,
+  kind: NodeKind,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+  Null,
+  Bool(bool),
+  Int(i64),
+  Float(f64),
+  Str(String),
+  Seq(Vec<Node>),
+  Map(Vec<(Node, Node)>),
+}
+
+impl Node {
+  fn map(span: Range<usize>, entries: Vec<(Node, Node)>) -> Result<Self, FormatError> {
+    for (i, (key, _)) in entries.iter().enumerate() {
+      let NodeKind::Str(name) = &key.kind else {
+        return Err(FormatError::new("Keys must be strings", key.span.clone()));
+      };
+      if entries[..i]
+        .iter()
+        .any(|(other, _)| matches!(&other.kind, NodeKind::Str(other) if other == name))
+      {
+        return Err(FormatError::new(
+          format!("Duplicate key `{name}`"),
+          key.span.clone(),
+        ));
+      }
+    }
+    Ok(Node {
+      span,
+      kind: NodeKind::Map(entries),
+    })
+  }
+
+  /// Converts the node into a [`SpannedValue`].
+  fn into_value(self) -> Result<SpannedValue, FormatError> {
+    let kind = match self.kind {
+      NodeKind::Null => return Err(FormatError::new("Unexpected null", self.span)),
+      NodeKind::Bool(b) => ValueKind::Boolean(b),
+      NodeKind::Int(n) => ValueKind::Integer(n),
+      NodeKind::Float(n) => ValueKind::Float(n),
+      NodeKind::Str(s) => ValueKind::String(s),
+      NodeKind::Seq(items) => ValueKind::Array(
+        items
+          .into_iter()
+          .map(Node::into_value)
+          .collect::<Result<_, _>>()?,
+      ),
+      // TOML has no null, so null entries are treated as missing.
+      NodeKind::Map(entries) => ValueKind::Table(
+        entries
+          .into_iter()
+          .filter(|(_, value)| !matches!(value.kind, NodeKind::Null))
+          .map(|(key, value)| {
+            let NodeKind::Str(name) = key.kind else {
+              unreachable!("keys are checked by `Node::map`")
+            };
+            Ok((Spanned::new(key.span, name), value.into_value()?))
+          })
+          .collect::<Result<_, FormatError>>()?,
+      ),
+    };
+    Ok(Spanned::new(self.span, kind))
+  }
+}
+
+/// A parser for JSON that records the span of each value.
+struct JsonParser<'a> {
+  src: &'a str,
+  pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+  fn new(src: &'a str) -> Self {
+    JsonParser { src, pos: 0 }
+  }
+
+  fn parse(mut self) -> Result<Node, FormatError> {
+    let node = self.value()?;
+    self.skip_whitespace();
+    if self.pos < self.src.len() {
+      return Err(self.error("Trailing characters after the end of the document"));
+    }
+    Ok(node)
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.src.as_bytes().get(self.pos).copied()
+  }
+
+  fn error(&self, message: impl fmt::Display) -> FormatError {
+    FormatError::new(message, self.pos..self.pos)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+      self.pos += 1;
+    }
+  }
+
+  /// Consumes `c` after any whitespace, returning true if it was there.
+  fn eat(&mut self, c: u8) -> bool {
+    self.skip_whitespace();
+    let found = self.peek() == Some(c);
+    if found {
+      self.pos += 1;
+    }
+    found
+  }
+
+  fn expect(&mut self, c: u8) -> Result<(), FormatError> {
+    if self.eat(c) {
+      Ok(())
+    } else {
+      Err(self.error(format!("Expected `{}`", c as char)))
+    }
+  }
+
+  fn value(&mut self) -> Result<Node, FormatError> {
+    self.skip_whitespace();
+    let start = self.pos;
+    let kind = match self.peek() {
+      Some(b'{') => {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        if !self.eat(b'}') {
+          loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+              return Err(self.error("Expected a string key"));
+            }
+            let key = self.value()?;
+            self.expect(b':')?;
+            entries.push((key, self.value()?));
+            if !self.eat(b',') {
+              self.expect(b'}')?;
+              break;
+            }
+          }
+        }
+        return Node::map(start..self.pos, entries);
+      }
+      Some(b'[') => {
+        self.pos += 1;
+        let mut items = Vec::new();
+        if !self.eat(b']') {
+          loop {
+            items.push(self.value()?);
+            if !self.eat(b',') {
+              self.expect(b']')?;
+              break;
+            }
+          }
+        }
+        NodeKind::Seq(items)
+      }
+      Some(b'"') => {
+        self.pos += 1;
+        loop {
+          match self.peek() {
+            Some(b'"') => break,
+            Some(b'\\') => self.pos += 2,
+            Some(_) => self.pos += 1,
+            None => return Err(FormatError::new("Unterminated string", start..start)),
+          }
+        }
+        self.pos += 1;
+        let s = serde_json::from_str(&self.src[start..self.pos])
+          .map_err(|err| FormatError::new(err, start..self.pos))?;
+        NodeKind::Str(s)
+      }
+      Some(b'-' | b'0'..=b'9') => {
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+          self.pos += 1;
+        }
+        let number = serde_json::from_str::<serde_json::Number>(&self.src[start..self.pos])
+          .map_err(|_| FormatError::new("Invalid number", start..self.pos))?;
+        match number.as_i64() {
+          Some(n) => NodeKind::Int(n),
+          None => NodeKind::Float(number.as_f64().unwrap()),
+        }
+      }
+      _ => {
+        let rest = &self.src[self.pos..];
+        let (len, kind) = [
+          ("true", NodeKind::Bool(true)),
+          ("false", NodeKind::Bool(false)),
+          ("null", NodeKind::Null),
+        ]
+        .into_iter()
+        .find(|(word, _)| rest.starts_with(word))
+        .map(|(word, kind)| (word.len(), kind))
+        .ok_or_else(|| self.error("Expected a value"))?;
+        self.pos += len;
+        kind
+      }
+    };
+    Ok(Node {
+      span: start..self.pos,
+      kind,
+    })
+  }
+}
+
+/// Resolves the type of a plain (unquoted) YAML scalar, following the YAML 1.2 core schema.
+fn resolve_plain(s: &str) -> NodeKind {
+  match s {
+    "" | "~" | "null" | "Null" | "NULL" => return NodeKind::Null,
+    "true" | "True" | "TRUE" => return NodeKind::Bool(true),
+    "false" | "False" | "FALSE" => return NodeKind::Bool(false),
+    ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => {
+      return NodeKind::Float(f64::INFINITY);
+    }
+    "-.inf" | "-.Inf" | "-.INF" => return NodeKind::Float(f64::NEG_INFINITY),
+    ".nan" | ".NaN" | ".NAN" => return NodeKind::Float(f64::NAN),
+    _ => {}
+  }
+
+  let int = if let Some(hex) = s.strip_prefix("0x") {
+    i64::from_str_radix(hex, 16).ok()
+  } else if let Some(oct) = s.strip_prefix("0o") {
+    i64::from_str_radix(oct, 8).ok()
+  } else {
+    s.parse::<i64>().ok()
+  };
+  if let Some(n) = int {
+    return NodeKind::Int(n);
+  }
+
+  // Rust accepts floats like "inf" that YAML doesn't, so check the characters first.
+  let is_number = s.bytes().any(|b| b.is_ascii_digit())
+    && s
+      .bytes()
+      .all(|b| matches!(b, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E'));
+  match s.parse::<f64>() {
+    Ok(n) if is_number => NodeKind::Float(n),
+    _ => NodeKind::Str(s.to_string()),
+  }
+}
+
+/// Parses the first document of a YAML stream.
+fn parse_yaml(src: &str) -> Result<Node, FormatError> {
+  let mut parser = YamlParser {
+    src,
+    events: Parser::new_from_str(src),
+    anchors: HashMap::new(),
+  };
+  loop {
+    match parser.next()? {
+      (Event::StreamStart | Event::DocumentStart(_), _) => continue,
+      (Event::StreamEnd, _) => {
+        return Ok(Node {
+          span: 0..0,
+          kind: NodeKind::Null,
+        });
+      }
+      (event, span) => return parser.node(event, span),
+    }
+  }
+}
+
+struct YamlParser<'a> {
+  src: &'a str,
+  events: Parser<'a, saphyr_parser::StrInput<'a>>,
+  anchors: HashMap<usize, Node>,
+}
+
+impl<'a> YamlParser<'a> {
+  fn next(&mut self) -> Result<(Event<'a>, Span), FormatError> {
+    match self.events.next_event() {
+      Some(Ok(event)) => Ok(event),
+      Some(Err(err)) => {
+        let offset = err.marker().index();
+        Err(FormatError::new(err.info(), offset..offset))
+      }
+      None => Err(FormatError::new(
+        "Unexpected end of document",
+        self.src.len()..self.src.len(),
+      )),
+    }
+  }
+
+  /// The end of a collection whose end event is at `span`, and whose last child is `last`.
+  fn collection_end(span: Span, start: usize, last: Option<&Node>) -> usize {
+    // Flow collections end with a `]` or `}`, while block collections end after their last child.
+    if span.end.index() > span.start.index() {
+      span.end.index()
+    } else {
+      last.map_or(start, |node| node.span.end)
+    }
+  }
+
+  fn node(&mut self, event: Event<'a>, span: Span) -> Result<Node, FormatError> {
+    let start = span.start.index();
+    let (node, anchor) = match event {
+      Event::Scalar(value, style, anchor, tag) => {
+        let mut end = span.end.index();
+        if matches!(style, ScalarStyle::Literal | ScalarStyle::Folded) {
+          // Block scalars include the whitespace up to the next token.
+          end = start + self.src[start..end].trim_end().len();
+        }
+        let kind = if style == ScalarStyle::Plain && tag.is_none() {
+          resolve_plain(&value)
+        } else {
+          NodeKind::Str(value.into_owned())
+        };
+        (
+          Node {
+            span: start..end,
+            kind,
+          },
+          anchor,
+        )
+      }
+      Event::SequenceStart(anchor, _) => {
+        let mut items = Vec::new();
+        let end = loop {
+          match self.next()? {
+            (Event::SequenceEnd, span) => break Self::collection_end(span, start, items.last()),
+            (event, span) => items.push(self.node(event, span)?),
+          }
+        };
+        (
+          Node {
+            span: start..end,
+            kind: NodeKind::Seq(items),
+          },
+          anchor,
+        )
+      }
+      Event::MappingStart(anchor, _) => {
+        let mut entries = Vec::new();
+        let end = loop {
+          let (key, value) = match self.next()? {
+            (Event::MappingEnd, span) => {
+              break Self::collection_end(span, start, entries.last().map(|(_, v)| v));
+            }
+            (Event::Scalar(key, _, _, _), span) => (
+              Node {
+                span: span.start.index()..span.end.index(),
+                kind: NodeKind::Str(key.into_owned()),
+              },
+              self.next()?,
+            ),
+            (_, span) => {
+              let offset = span.start.index();
+              return Err(FormatError::new("Keys must be strings", offset..offset));
+            }
+          };
+          entries.push((key, self.node(value.0, value.1)?));
+        };
+        (Node::map(start..end, entries)?, anchor)
+      }
+      Event::Alias(id) => {
+        let node = self.anchors.get(&id).cloned();
+        let node =
+          node.ok_or_else(|| FormatError::new("Unknown alias", start..span.end.index()))?;
+        (node, 0)
+      }
+      _ => return Err(FormatError::new("Unexpected YAML", start..start)),
+    };
+    if anchor != 0 {
+      self.anchors.insert(anchor, node.clone());
+    }
+    Ok(node)
+  }
+}
+
+impl<'de> de::Deserializer<'de> for &Node {
+  type Error = FormatError;
+
+  fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, FormatError> {
+    match &self.kind {
+      NodeKind::Null => visitor.visit_unit(),
+      NodeKind::Bool(b) => visitor.visit_bool(*b),
+      NodeKind::Int(n) => visitor.visit_i64(*n),
+      NodeKind::Float(n) => visitor.visit_f64(*n),
+      NodeKind::Str(s) => visitor.visit_str(s),
+      NodeKind::Seq(items) => visitor.visit_seq(SeqAccess(items.iter())),
+      // TOML has no null, so null entries are treated as missing.
+      NodeKind::Map(entries) => visitor.visit_map(MapAccess {
+        entries: entries
+          .iter()
+          .filter(|(_, value)| !matches!(value.kind, NodeKind::Null))
+          .collect::<Vec<_>>()
+          .into_iter(),
+        value: None,
+      }),
+    }
+    .map_err(|err| err.or_span(&self.span))
+  }
+
+  fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, FormatError> {
+    match self.kind {
+      NodeKind::Null => visitor.visit_none(),
+      _ => visitor.visit_some(self),
+    }
+  }
+
+  fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+    self,
+    _name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, FormatError> {
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_enum<V: de::Visitor<'de>>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, FormatError> {
+    match &self.kind {
+      NodeKind::Str(s) => visitor
+        .visit_enum(s.as_str().into_deserializer())
+        .map_err(|err: FormatError| err.or_span(&self.span)),
+      _ => self.deserialize_any(visitor),
+    }
+  }
+
+  serde::forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+  }
+}
+
+struct SeqAccess<'a>(std::slice::Iter<'a, Node>);
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'_> {
+  type Error = FormatError;
+
+  fn next_element_seed<T: de::DeserializeSeed<'de>>(
+    &mut self,
+    seed: T,
+  ) -> Result<Option<T::Value>, FormatError> {
+    self
+      .0
+      .next()
+      .map(|node| {
+        seed
+          .deserialize(node)
+          .map_err(|err| err.or_span(&node.span))
+      })
+      .transpose()
+  }
+}
+
+struct MapAccess<'a> {
+  entries: std::vec::IntoIter<&'a (Node, Node)>,
+  value: Option<&'a Node>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_> {
+  type Error = FormatError;
+
+  fn next_key_seed<K: de::DeserializeSeed<'de>>(
+    &mut self,
+    seed: K,
+  ) -> Result<Option<K::Value>, FormatError> {
+    let Some((key, value)) = self.entries.next() else {
+      return Ok(None);
+    };
+    self.value = Some(value);
+    seed
+      .deserialize(key)
+      .map(Some)
+      .map_err(|err| err.or_span(&key.span))
+  }
+
+  fn next_value_seed<V: de::DeserializeSeed<'de>>(
+    &mut self,
+    seed: V,
+  ) -> Result<V::Value, FormatError> {
+    let value = self.value.take().expect("value requested before key");
+    seed
+      .deserialize(value)
+      .map_err(|err| err.or_span(&value.span))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use mdbook_quiz_schema::Quiz;
+
+  const JSON: &str = r#"{
+  "questions": [
+    {
+      "type": "ShortAnswer",
+      "prompt": { "prompt": "What is \"1 + 1\"? 🤔" },
+      "answer": { "answer": "2" },
+      "context": null
+    }
+  ]
+}"#;
+
+  const YAML: &str = r#"
+questions:
+  - type: MultipleChoice
+    prompt:
+      prompt: |
+        What is 1 + 1?
+      distractors: [1, "3"]
+      answerIndex: 0x1
+    answer: { answer: 2 }
+"#;
+
+  #[test]
+  fn parse_formats() -> anyhow::Result<()> {
+    let quiz = QuizFormat::Json.parse::<Quiz>(JSON)?;
+    let toml = toml::Value::try_from(&quiz)?;
+    assert_eq!(
+      toml["questions"][0]["prompt"]["prompt"].as_str(),
+      Some("What is \"1 + 1\"? 🤔")
+    );
+    assert!(toml["questions"][0].get("context").is_none());
+
+    let value = QuizFormat::Yaml.parse::<toml::Value>(YAML)?;
+    let question = &value["questions"][0];
+    assert_eq!(
+      question["prompt"]["prompt"].as_str(),
+      Some("What is 1 + 1?\n")
+    );
+    assert_eq!(question["prompt"]["answerIndex"].as_integer(), Some(1));
+    assert_eq!(question["answer"]["answer"].as_integer(), Some(2));
+    assert!(QuizFormat::Yaml.parse::<Quiz>(YAML).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn parse_spans() -> anyhow::Result<()> {
+    for (format, contents, prompt) in [
+      (QuizFormat::Json, JSON, r#""What is \"1 + 1\"? 🤔""#),
+      (QuizFormat::Yaml, YAML, "What is 1 + 1?"),
+    ] {
+      let value = format.parse_value(contents)?;
+      let question = &value.get_ref().as_table().unwrap()["questions"]
+        .get_ref()
+        .as_array()
+        .unwrap()[0];
+      let prompt_value = &question.get_ref().as_table().unwrap()["prompt"]
+        .get_ref()
+        .as_table()
+        .unwrap()["prompt"];
+      assert_eq!(
+        &contents[prompt_value.start()..prompt_value.end()],
+        prompt,
+        "{format}"
+      );
+    }
+
+    let span_of = |format: QuizFormat, contents: &str| {
+      let err = format.parse_spanned::<Quiz>(contents).unwrap_err();
+      let span = err.span.unwrap();
+      contents[span].to_string()
+    };
+    // Questions are internally tagged, so errors within them are located at the whole question.
+    assert!(span_of(QuizFormat::Yaml, YAML).starts_with("type: MultipleChoice"));
+    assert_eq!(span_of(QuizFormat::Json, r#"{"questions": 5}"#), "5");
+    assert_eq!(span_of(QuizFormat::Json, r#"{"a": 1, "a": 2}"#), r#""a""#);
+    assert_eq!(span_of(QuizFormat::Json, r#"{"questions": [}"#), "");
+
+    // Null entries of a table are missing, but other nulls can't be represented.
+    let value = QuizFormat::Json.parse_value(r#"{"a": null}"#)?;
+    assert!(value.get_ref().get("a").is_none());
+    assert!(QuizFormat::Yaml.parse_value("a: [~]").is_err());
+
+    Ok(())
+  }
+}
@@ -0,0 +1,171 @@
+use std::fs;
+
+use crate::{
+  SpannedValue, SpannedValueExt, Validate, ValidationContext, cxensure, include::has_tag, tomlcast,
+};
+use mdbook_quiz_schema::*;
+use miette::miette;
+
+impl Validate for QuizInclude {
+  fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
+    let path_val = tomlcast!(value.table["path"]);
+    cxensure!(
+      cx,
+      self.ids.as_ref().is_some_and(|ids| !ids.is_empty())
+        || self.tags.as_ref().is_some_and(|tags| !tags.is_empty()),
+      labels = vec![value.labeled_span()],
+      "Include must select questions by `ids` or `tags`"
+    );
+
+    let path = cx.path.parent().unwrap().join(&self.path);
+    let contents = match fs::read_to_string(&path) {
+      Ok(contents) => contents,
+      Err(err) => {
+        cx.error(miette!(
+          labels = vec![path_val.labeled_span()],
+          "Failed to read included file {}: {err}",
+          path.display()
+        ));
+        return;
+      }
+    };
+
+    // Included questions are validated once in their own file, so their IDs are not duplicates
+    // of the questions that they are included as.
+    cx.validate_dependency(&path, &contents);
+
+    let included =
+      match crate::resolve_include(&cx.path, self, &mut |path| Ok(fs::read_to_string(path)?)) {
+        Ok(included) => included,
+        Err(err) => {
+          cx.error(miette!(labels = vec![path_val.labeled_span()], "{err}"));
+          return;
+        }
+      };
+
+    if let Some(ids) = &self.ids {
+      let ids_val = tomlcast!(value.table["ids"].array);
+      for (id, id_val) in ids.iter().zip(ids_val) {
+        let found = included
+          .iter()
+          .any(|q| q.get("id").and_then(toml::Value::as_str) == Some(id));
+        cxensure!(
+          cx,
+          found,
+          labels = vec![id_val.labeled_span()],
+          "Included quiz {} has no question with ID `{id}`",
+          self.path
+        );
+      }
+    }
+
+    if let Some(tags) = &self.tags {
+      let tags_val = tomlcast!(value.table["tags"].array);
+      for (tag, tag_val) in tags.iter().zip(tags_val) {
+        let found = included.iter().any(|q| has_tag(q, tag));
+        cxensure!(
+          cx,
+          found,
+          labels = vec![tag_val.labeled_span()],
+          "Included quiz {} has no questions tagged `{tag}`",
+          self.path
+        );
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::Path;
+
+  use super::*;
+  use crate::{Validated, ValidationOptions, ValidationReport, test::messages};
+
+  const BANK: &str = r#"
+[[questions]]
+id = "one"
+type = "ShortAnswer"
+prompt.prompt = "One"
+answer.answer = "1"
+
+[[questions]]
+id = "two"
+type = "ShortAnswer"
+tags = ["hard"]
+prompt.prompt = "Two"
+answer.answer = "2"
+"#;
+
+  fn validate_file(
+    dir: &Path,
+    name: &str,
+    validated: &Validated,
+  ) -> anyhow::Result<ValidationReport> {
+    let path = dir.join(name);
+    let contents = fs::read_to_string(&path)?;
+    let report =
+      crate::validate_report(&path, &contents, validated, &ValidationOptions::default())?;
+    Ok(report.unwrap())
+  }
+
+  #[test]
+  fn validate_includes() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let dir = dir.path();
+    fs::write(dir.join("bank.toml"), BANK)?;
+    fs::write(
+      dir.join("a.toml"),
+      "[[include]]\npath = \"bank.toml\"\nids = [\"one\"]\ntags = [\"hard\"]",
+    )?;
+    fs::write(
+      dir.join("b.toml"),
+      "[[include]]\npath = \"bank.toml\"\nids = [\"one\"]",
+    )?;
+
+    // Reusing a question in several quizzes is not a duplicate ID.
+    let validated = Validated::default();
+    for name in ["a.toml", "b.toml"] {
+      let report = validate_file(dir, name, &validated)?;
+      assert!(report.diagnostics.is_empty(), "{:?}", messages(&report));
+    }
+
+    fs::write(
+      dir.join("c.toml"),
+      "[[include]]\npath = \"bank.toml\"\nids = [\"three\"]\ntags = [\"easy\"]",
+    )?;
+    let report = validate_file(dir, "c.toml", &validated)?;
+    assert_eq!(
+      messages(&report),
+      [
+        "Included quiz bank.toml has no question with ID `three`",
+        "Included quiz bank.toml has no questions tagged `easy`"
+      ]
+    );
+
+    fs::write(dir.join("d.toml"), "[[include]]\npath = \"bank.toml\"")?;
+    let report = validate_file(dir, "d.toml", &validated)?;
+    assert_eq!(
+      messages(&report),
+      ["Include must select questions by `ids` or `tags`"]
+    );
+
+    fs::write(
+      dir.join("e.toml"),
+      "[[include]]\npath = \"f.toml\"\nids = [\"one\"]",
+    )?;
+    fs::write(
+      dir.join("f.toml"),
+      "[[include]]\npath = \"e.toml\"\nids = [\"one\"]",
+    )?;
+    let report = validate_file(dir, "e.toml", &Validated::default())?;
+    assert!(report.is_fatal());
+    assert!(
+      messages(&report)
+        .iter()
+        .all(|msg| msg.starts_with("Include cycle"))
+    );
+
+    Ok(())
+  }
+}
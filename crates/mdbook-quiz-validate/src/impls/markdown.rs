@@ -1,68 +1,179 @@
-use crate::{SpannedValue, Validate, ValidationContext};
+use std::path::Path;
+
+use crate::{SpannedValue, Validate, ValidationContext, spellcheck::Spellchecker};
 use markdown::{ParseOptions, mdast::Node};
 use mdbook_quiz_schema::*;
 use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
 
 #[derive(Error, Diagnostic, Debug)]
-#[error("Spelling error: `{word}`")]
-struct SpellingError {
-  word: String,
+#[error("Code block is missing a closing fence")]
+#[diagnostic(severity(Warning), help("add a closing {fence} line after the code"))]
+struct UnclosedFence {
+  fence: String,
 
   #[label]
   span: SourceSpan,
 }
 
-impl Validate for Markdown {
-  fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
-    let root = markdown::to_mdast(&self.0, &ParseOptions::default()).unwrap();
+#[derive(Error, Diagnostic, Debug)]
+#[error("Raw HTML is inserted into the page without sanitization")]
+#[diagnostic(severity(Warning), help("prefer Markdown syntax where possible"))]
+struct RawHtml {
+  #[label]
+  span: SourceSpan,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Image is missing alt text")]
+#[diagnostic(
+  severity(Warning),
+  help("describe the image for screen readers, e.g. `![a diagram of the stack](...)`")
+)]
+struct MissingAltText {
+  #[label]
+  span: SourceSpan,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Headings inside a quiz break the structure of the surrounding page")]
+#[diagnostic(severity(Warning), help("use **bold** text instead"))]
+struct Heading {
+  #[label]
+  span: SourceSpan,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Relative {kind} does not resolve to a file in the book: `{url}`")]
+#[diagnostic(severity(Warning))]
+struct BrokenLink {
+  kind: &'static str,
+  url: String,
+
+  #[label]
+  span: SourceSpan,
+}
+
+fn collect_nodes(root: &Node) -> Vec<&Node> {
+  let mut queue = vec![root];
+  let mut nodes = vec![];
+  while let Some(node) = queue.pop() {
+    nodes.push(node);
+    if let Some(children) = node.children() {
+      queue.extend(children);
+    }
+  }
+  nodes
+}
+
+/// If `source` is a fenced code block, returns the opening fence if the block is unclosed.
+fn unclosed_fence(source: &str) -> Option<&str> {
+  let first = source.lines().next()?.trim_start();
+  let fence_char = first.chars().next().filter(|c| *c == '`' || *c == '~')?;
+  let fence_len = first.chars().take_while(|c| *c == fence_char).count();
+  if fence_len < 3 {
+    return None;
+  }
+
+  let fence = &first[..fence_len];
+  let closed = source.lines().skip(1).last().is_some_and(|last| {
+    let last = last.trim();
+    last.len() >= fence_len && last.chars().all(|c| c == fence_char)
+  });
+  (!closed).then_some(fence)
+}
+
+/// Returns true if `url` is a relative path that should point to a file in the book.
+fn is_relative_url(url: &str) -> bool {
+  let has_scheme = url
+    .split_once(':')
+    .is_some_and(|(scheme, _)| !scheme.contains('/'));
+  !(url.is_empty() || has_scheme || url.starts_with('#') || url.starts_with('/'))
+}
 
-    fn collect_nodes(root: &Node) -> Vec<&Node> {
-      let mut queue = vec![root];
-      let mut nodes = vec![];
-      while let Some(node) = queue.pop() {
-        nodes.push(node);
-        if let Some(children) = node.children() {
-          queue.extend(children);
+/// Returns true if the relative `url` resolves to a file from `chapter_dir`.
+///
+/// Links to `.html` files are also resolved against the corresponding `.md` source.
+fn url_resolves(chapter_dir: &Path, url: &str) -> bool {
+  let path = url.split(['#', '?']).next().unwrap_or(url);
+  if path.is_empty() {
+    return true;
+  }
+  let target = chapter_dir.join(path);
+  target.exists()
+    || (target.extension().is_some_and(|ext| ext == "html") && target.with_extension("md").exists())
+}
+
+fn lint(md: &str, cx: &mut ValidationContext, value: &SpannedValue, nodes: &[&Node]) {
+  for node in nodes {
+    let Some(pos) = node.position() else {
+      continue;
+    };
+    let span = cx.string_span(value, pos.start.offset..pos.end.offset);
+    match node {
+      Node::Code(_) => {
+        if let Some(fence) = unclosed_fence(&md[pos.start.offset..pos.end.offset]) {
+          let fence = fence.to_string();
+          cx.warning(UnclosedFence { fence, span });
         }
       }
-      nodes
+      Node::Html(_) => cx.warning(RawHtml { span }),
+      Node::Heading(_) => cx.warning(Heading { span }),
+      Node::Image(image) if image.alt.trim().is_empty() => cx.warning(MissingAltText { span }),
+      Node::ImageReference(image) if image.alt.trim().is_empty() => {
+        cx.warning(MissingAltText { span })
+      }
+      _ => {}
     }
 
-    if cx.spellcheck {
-      let nodes = collect_nodes(&root);
-      let dict = crate::spellcheck::dictionary();
-      let open_quote = &cx.contents()[value.start()..];
-      let quote_size = if let Some(next) = open_quote.strip_prefix(r#"""""#) {
-        if next.starts_with('\n') { 4 } else { 3 }
-      } else {
-        1
-      };
-      let base = value.start() + quote_size;
-      for node in nodes {
-        if let (Node::Text(text), Some(pos)) = (node, node.position()) {
-          let errors = dict
-            .check_indices(&text.value)
-            .filter(|(_, s)| *s != "-")
-            .filter(|(_, s)| s.parse::<isize>().is_err())
-            .filter(|(_, s)| s.parse::<f32>().is_err());
-          for (idx, substr) in errors {
-            // base: location of string literal in TOML file
-            // pos.start.offset: location of markdown text node in the string
-            // idx: location of error in text node
-            let span = (base + pos.start.offset + idx, substr.len());
-            let error = SpellingError {
-              word: substr.to_string(),
-              span: span.into(),
-            };
-            cx.warning(error);
-          }
-        }
+    let link = match node {
+      Node::Link(link) => Some(("link", &link.url)),
+      Node::Image(image) => Some(("image", &image.url)),
+      Node::Definition(def) => Some(("link", &def.url)),
+      _ => None,
+    };
+    if let (Some((kind, url)), Some(chapter_dir)) = (link, &cx.options().chapter_dir)
+      && is_relative_url(url)
+      && !url_resolves(chapter_dir, url)
+    {
+      let url = url.clone();
+      cx.warning(BrokenLink { kind, url, span });
+    }
+  }
+}
+
+fn spellcheck(
+  cx: &mut ValidationContext,
+  spellchecker: &Spellchecker,
+  value: &SpannedValue,
+  nodes: &[&Node],
+) {
+  for node in nodes {
+    if let (Node::Text(text), Some(pos)) = (node, node.position()) {
+      for (idx, substr) in spellchecker.check(&text.value) {
+        // pos.start.offset: location of markdown text node in the string
+        // idx: location of error in text node
+        let start = pos.start.offset + idx;
+        let span = cx.string_span(value, start..start + substr.len());
+        cx.misspelling(substr, span);
       }
     }
   }
 }
 
+impl Validate for Markdown {
+  fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
+    let root = markdown::to_mdast(&self.0, &ParseOptions::default()).unwrap();
+    let nodes = collect_nodes(&root);
+
+    lint(&self.0, cx, value, &nodes);
+
+    if let Some(spellchecker) = cx.spellchecker.clone() {
+      spellcheck(cx, &spellchecker, value, &nodes);
+    }
+  }
+}
+
 #[test]
 fn validate_markdown_passes() {
   let contents = r#"
@@ -87,3 +198,60 @@ prompt.distractors = [""]
   // TODO: right now this test is just verified looking at stderr
   assert!(crate::test::harness(contents).is_ok());
 }
+
+#[test]
+fn markdown_lint_helpers() {
+  assert_eq!(unclosed_fence("```rust\nfn main() {}\n"), Some("```"));
+  assert_eq!(unclosed_fence("```rust\nfn main() {}\n```"), None);
+  assert_eq!(unclosed_fence("~~~~\nx\n~~~"), Some("~~~~"));
+  assert_eq!(unclosed_fence("    indented code"), None);
+
+  assert!(is_relative_url("../img/stack.svg"));
+  assert!(is_relative_url("ch04-01.html#references"));
+  assert!(!is_relative_url("https://doc.rust-lang.org"));
+  assert!(!is_relative_url("mailto:someone@example.com"));
+  assert!(!is_relative_url("#anchor"));
+
+  let dir = tempfile::tempdir().unwrap();
+  std::fs::write(dir.path().join("ch01.md"), "").unwrap();
+  assert!(url_resolves(dir.path(), "ch01.md"));
+  assert!(url_resolves(dir.path(), "ch01.html#section"));
+  assert!(!url_resolves(dir.path(), "ch02.html"));
+}
+
+#[test]
+fn validate_markdown_lints_are_warnings() {
+  let contents = r#"
+[[questions]]
+type = "MultipleChoice"
+prompt.prompt = """
+# Question
+
+![](missing.png) <b>bold</b>
+
+```rust
+fn main() {}
+"""
+answer.answer = ""
+prompt.distractors = [""]
+"#;
+  let report = crate::test::report(contents);
+  assert!(
+    report
+      .diagnostics
+      .iter()
+      .all(|d| !d.fatal && d.error.severity() == Some(miette::Severity::Warning))
+  );
+  let mut messages = crate::test::messages(&report);
+  messages.sort();
+  assert_eq!(
+    messages,
+    [
+      "Code block is missing a closing fence",
+      "Headings inside a quiz break the structure of the surrounding page",
+      "Image is missing alt text",
+      "Raw HTML is inserted into the page without sanitization",
+      "Raw HTML is inserted into the page without sanitization"
+    ]
+  );
+}
@@ -1,9 +1,13 @@
 use crate::{SpannedValue, SpannedValueExt, Validate, ValidationContext, cxensure, tomlcast};
 use fluid_let::{fluid_let, fluid_set};
-use mdbook_quiz_schema::{Question, QuestionFields, Quiz};
+use mdbook_quiz_schema::{QUIZ_VERSION, Question, QuestionFields, Quiz, TextField};
+use miette::{Severity, miette};
+use uuid::Uuid;
 
+mod include;
 mod markdown;
 mod multiple_choice;
+mod pool;
 mod short_answer;
 mod tracing;
 
@@ -13,24 +17,79 @@ impl Validate for Quiz {
   fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
     fluid_set!(QUIZ, self);
 
+    // Quizzes without a `version` are in version 1.
+    let version = self.version.unwrap_or(1);
+    let version_val = tomlcast!(value.table).get("version").unwrap_or(value);
     cxensure!(
       cx,
-      !self.questions.is_empty(),
+      version <= QUIZ_VERSION,
+      labels = vec![version_val.labeled_span()],
+      help = "Upgrade mdbook-quiz to build this quiz",
+      "Quiz version {version} is newer than the latest supported version, {QUIZ_VERSION}"
+    );
+    cxensure!(
+      cx,
+      version >= QUIZ_VERSION,
+      labels = vec![version_val.labeled_span()],
+      help = "Run `mdbook-quiz migrate` to upgrade this quiz",
+      "Quiz version {version} is no longer supported"
+    );
+
+    cxensure!(
+      cx,
+      !self.questions.is_empty() || self.include.is_some() || self.pools.is_some(),
       labels = vec![value.labeled_span()],
       "Quiz must have at least one question"
     );
 
-    let table = tomlcast!(value.table["questions"].array);
-    for (q, qvalue) in self.questions.iter().zip(table.iter()) {
-      q.validate(cx, qvalue);
+    if !self.questions.is_empty() {
+      let table = tomlcast!(value.table["questions"].array);
+      for (q, qvalue) in self.questions.iter().zip(table.iter()) {
+        q.validate(cx, qvalue);
+      }
     }
 
-    if let Some(multipart) = &self.multipart {
-      let multipart_val = tomlcast!(value.table["multipart"]);
-      for (k, v) in multipart {
-        v.validate(cx, multipart_val.get_ref().get(k).unwrap());
+    // Markdown is checked in one pass over the quiz, which also covers the multipart contexts.
+    for (path, field) in self.text_fields() {
+      if let TextField::Markdown(md) = field {
+        md.validate(cx, value.lookup(&path.0).unwrap());
       }
     }
+
+    if let Some(includes) = &self.include {
+      let table = tomlcast!(value.table["include"].array);
+      for (include, include_value) in includes.iter().zip(table.iter()) {
+        include.validate(cx, include_value);
+      }
+    }
+
+    if let Some(pools) = &self.pools {
+      let pools_val = tomlcast!(value.table["pools"]);
+      cxensure!(
+        cx,
+        !pools.is_empty(),
+        labels = vec![pools_val.labeled_span()],
+        "`pools` must contain at least one pool"
+      );
+      for (pool, pool_value) in pools.iter().zip(tomlcast!(pools_val.array)) {
+        pool.validate(cx, pool_value);
+      }
+    }
+
+    if let Some(passing_score) = self.settings.as_ref().and_then(|s| s.passing_score) {
+      let score_val = tomlcast!(value.table["settings"].table["passingScore"]);
+      cxensure!(
+        cx,
+        passing_score <= 100,
+        labels = vec![score_val.labeled_span()],
+        "Passing score must be a percentage from 0 to 100"
+      );
+    }
+
+    if let Some(spellchecker) = cx.spellchecker.clone() {
+      let ignore = self.spellcheck_ignore.as_deref().unwrap_or_default();
+      crate::spellcheck::report_misspellings(cx, &spellchecker, ignore);
+    }
   }
 }
 
@@ -41,6 +100,37 @@ impl Validate for Question {
       Question::ShortAnswer(q) => q.validate(cx, value),
       Question::Tracing(q) => q.validate(cx, value),
     }
+
+    let id = match self {
+      Question::MultipleChoice(q) => &q.0.id,
+      Question::ShortAnswer(q) => &q.0.id,
+      Question::Tracing(q) => &q.0.id,
+    };
+    if let Some(registry) = cx.options().id_registry.clone()
+      && let Some(id) = id
+    {
+      registry.check(cx, self, id, value);
+    }
+  }
+}
+
+/// Checks that each of `words` is in the book's vocabulary `allowed`, if it has one.
+fn check_vocabulary(
+  cx: &mut ValidationContext,
+  kind: &str,
+  words: &[String],
+  allowed: Option<Vec<String>>,
+  value: &SpannedValue,
+) {
+  let Some(allowed) = allowed else { return };
+  for (word, word_val) in words.iter().zip(tomlcast!(value.array)) {
+    cxensure!(
+      cx,
+      allowed.contains(word),
+      labels = vec![word_val.labeled_span()],
+      help = format!("The book allows: {}", allowed.join(", ")),
+      "{kind} `{word}` is not in the book's vocabulary"
+    );
   }
 }
 
@@ -48,6 +138,20 @@ impl<Prompt: Validate, Answer: Validate> Validate for QuestionFields<Prompt, Ans
   fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
     if let Some(id) = &self.id {
       cx.check_id(id, tomlcast!(value.table["id"]));
+    } else if cx.options().require_ids {
+      let error = miette!(
+        severity = Severity::Warning,
+        labels = vec![tomlcast!(value.table["type"]).labeled_span()],
+        help = "IDs are used to identify questions in telemetry, so every question should have one",
+        "Question is missing an ID"
+      );
+      let fix = cx.fix(
+        value,
+        "id",
+        Some(toml::Value::String(Uuid::new_v4().to_string())),
+        "add an ID",
+      );
+      cx.add_diagnostic(error, false, Some(fix));
     }
 
     if let Some(multipart) = &self.multipart {
@@ -68,12 +172,28 @@ impl<Prompt: Validate, Answer: Validate> Validate for QuestionFields<Prompt, Ans
       });
     }
 
+    if let Some(tags) = &self.tags {
+      let allowed = cx.options().allowed_tags.clone();
+      check_vocabulary(cx, "Tag", tags, allowed, tomlcast!(value.table["tags"]));
+    }
+
+    if let Some(objectives) = &self.objectives {
+      let allowed = cx.options().allowed_objectives.clone();
+      let objectives_val = tomlcast!(value.table["objectives"]);
+      check_vocabulary(cx, "Objective", objectives, allowed, objectives_val);
+    }
+
     self.prompt.validate(cx, tomlcast!(value.table["prompt"]));
 
     self.answer.validate(cx, tomlcast!(value.table["answer"]));
 
-    if let Some(context) = &self.context {
-      context.validate(cx, tomlcast!(value.table["context"]));
+    if let Some(hints) = &self.hints {
+      cxensure!(
+        cx,
+        !hints.is_empty(),
+        labels = vec![tomlcast!(value.table["hints"]).labeled_span()],
+        "Question must have at least one hint, or no `hints` key"
+      );
     }
   }
 }
@@ -98,6 +218,23 @@ prompt.distractors = [""]
   assert!(crate::test::harness(contents).is_err());
 }
 
+#[test]
+fn validate_missing_id() {
+  let contents = r#"
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = ""
+answer.answer = ""
+"#;
+  let options = crate::ValidationOptions {
+    require_ids: true,
+    ..Default::default()
+  };
+  let report = crate::test::report_at(std::path::Path::new("dummy.rs"), contents, &options);
+  assert!(!report.is_fatal());
+  assert_eq!(report.fixes().count(), 1);
+}
+
 #[test]
 fn validate_multipart_spelling() {
   let contents = r#"
@@ -136,3 +273,156 @@ prompt.distractors = [""]
 "#;
   assert!(crate::test::harness(contents).is_err());
 }
+
+#[test]
+fn validate_spellcheck_ignore() {
+  let messages = |ignore: &str| -> Vec<String> {
+    let contents = format!(
+      r#"
+spellcheckIgnore = {ignore}
+
+[[questions]]
+type = "MultipleChoice"
+prompt.prompt = "Hello wrold, from Wrold and the mosst"
+answer.answer = "wrold"
+prompt.distractors = [""]
+"#
+    );
+    crate::test::messages(&crate::test::report(&contents))
+  };
+
+  assert_eq!(messages(r#"["wrold"]"#), ["Spelling error: `mosst`"]);
+  let mut unignored = messages("[]");
+  unignored.sort();
+  assert_eq!(
+    unignored,
+    [
+      "Spelling error: `Wrold`",
+      "Spelling error: `mosst`",
+      "Spelling error: `wrold`"
+    ]
+  );
+}
+
+#[test]
+fn validate_hints() {
+  let messages = |hints: &str| {
+    let contents = format!(
+      r#"
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Hello"
+answer.answer = "world"
+hints = {hints}
+"#
+    );
+    crate::test::messages(&crate::test::report(&contents))
+  };
+
+  assert!(messages(r#"["Look at the prompt"]"#).is_empty());
+  assert_eq!(
+    messages("[]"),
+    ["Question must have at least one hint, or no `hints` key"]
+  );
+  assert_eq!(messages(r#"["Hello wrold"]"#).len(), 1);
+}
+
+#[test]
+fn validate_vocabulary() {
+  let contents = r#"
+[[questions]]
+type = "ShortAnswer"
+tags = ["ownership", "lifetimes"]
+objectives = ["borrowck"]
+difficulty = "easy"
+points = 2
+prompt.prompt = "Hello"
+answer.answer = "world"
+"#;
+  let messages = |options: &crate::ValidationOptions| {
+    let report = crate::test::report_at(std::path::Path::new("dummy.toml"), contents, options);
+    crate::test::messages(&report)
+  };
+
+  assert!(messages(&crate::ValidationOptions::default()).is_empty());
+  let options = crate::ValidationOptions {
+    allowed_tags: Some(vec!["ownership".into()]),
+    allowed_objectives: Some(vec!["traits".into()]),
+    ..Default::default()
+  };
+  assert_eq!(
+    messages(&options),
+    [
+      "Tag `lifetimes` is not in the book's vocabulary",
+      "Objective `borrowck` is not in the book's vocabulary"
+    ]
+  );
+}
+
+#[test]
+fn validate_settings() {
+  let report = |settings: &str| {
+    let contents = format!(
+      r#"
+[settings]
+{settings}
+
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Hello"
+answer.answer = "world"
+"#
+    );
+    crate::test::report(&contents)
+  };
+
+  assert!(
+    report("shuffle = true\npassingScore = 80")
+      .diagnostics
+      .is_empty()
+  );
+  assert_eq!(
+    crate::test::messages(&report("passingScore = 120")),
+    ["Passing score must be a percentage from 0 to 100"]
+  );
+
+  let unknown = report("shufle = true");
+  assert!(unknown.is_fatal());
+  assert!(crate::test::messages(&unknown)[0].contains("unknown field `shufle`"));
+}
+
+#[test]
+fn validate_version() {
+  let messages = |version: u32| {
+    let contents = format!(
+      r#"
+version = {version}
+
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Hello"
+answer.answer = "world"
+"#
+    );
+    crate::test::messages(&crate::test::report(&contents))
+  };
+
+  assert!(messages(QUIZ_VERSION).is_empty());
+  assert_eq!(
+    messages(QUIZ_VERSION + 1),
+    [format!(
+      "Quiz version {} is newer than the latest supported version, {QUIZ_VERSION}",
+      QUIZ_VERSION + 1
+    )]
+  );
+  assert_eq!(messages(0), ["Quiz version 0 is no longer supported"]);
+
+  let unversioned = r#"
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Hello"
+answer.answer = "world"
+"#;
+  let report = crate::test::report(unversioned);
+  assert!(report.diagnostics.is_empty(), "version 1 is the latest");
+}
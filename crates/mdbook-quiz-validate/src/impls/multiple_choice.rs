@@ -1,5 +1,6 @@
 use crate::{SpannedValue, SpannedValueExt, Validate, ValidationContext, cxensure, tomlcast};
 use mdbook_quiz_schema::*;
+use miette::miette;
 
 impl Validate for MultipleChoice {
   fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
@@ -9,20 +10,21 @@ impl Validate for MultipleChoice {
 
 impl Validate for MultipleChoicePrompt {
   fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
-    self.prompt.validate(cx, tomlcast!(value.table["prompt"]));
-
-    let distractors = tomlcast!(value.table["distractors"]);
-    for (d, dv) in self.distractors.iter().zip(tomlcast!(distractors.array)) {
-      d.validate(cx, dv);
-    }
-
-    if let Some(idx) = self.answer_index {
-      cxensure!(
-        cx,
-        idx <= self.distractors.len(),
+    if let Some(idx) = self.answer_index
+      && idx > self.distractors.len()
+    {
+      let error = miette!(
         labels = vec![tomlcast!(value.table["answerIndex"]).labeled_span()],
         "Answer index is too large"
       );
+      let last = self.distractors.len();
+      let fix = cx.fix(
+        value,
+        "answerIndex",
+        Some(toml::Value::Integer(last as i64)),
+        format!("set answerIndex to {last}"),
+      );
+      cx.error_with_fix(error, fix);
     }
 
     cxensure!(
@@ -37,30 +39,16 @@ impl Validate for MultipleChoicePrompt {
   }
 }
 
-impl Validate for MultipleChoiceAnswerFormat {
-  fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
-    match self {
-      MultipleChoiceAnswerFormat::Single(ans) => {
-        ans.validate(cx, value);
-      }
-      MultipleChoiceAnswerFormat::Multi(v_ans) => {
-        cxensure!(
-          cx,
-          !v_ans.is_empty(),
-          labels = vec![value.labeled_span()],
-          "Must be at least one correct answer"
-        );
-        for (ans, ansv) in v_ans.iter().zip(tomlcast!(value.array)) {
-          ans.validate(cx, ansv);
-        }
-      }
-    }
-  }
-}
-
 impl Validate for MultipleChoiceAnswer {
   fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
-    self.answer.validate(cx, tomlcast!(value.table["answer"]))
+    if let MultipleChoiceAnswerFormat::Multi(answers) = &self.answer {
+      cxensure!(
+        cx,
+        !answers.is_empty(),
+        labels = vec![tomlcast!(value.table["answer"]).labeled_span()],
+        "Must be at least one correct answer"
+      );
+    }
   }
 }
 
@@ -89,3 +77,26 @@ prompt.sortAnswers = true
 "#;
   assert!(crate::test::harness(contents).is_err());
 }
+
+#[test]
+fn validate_mcq_distractor_feedback() {
+  let contents = r#"
+[[questions]]
+type = "MultipleChoice"
+prompt.prompt = "Pick one"
+answer.answer = "Right"
+prompt.distractors = ["Left", { text = "Up", feedback = "Up is wrnog" }]
+"#;
+  let report = crate::test::report(contents);
+  assert_eq!(report.diagnostics.len(), 1);
+  let label = report.diagnostics[0]
+    .error
+    .labels()
+    .unwrap()
+    .next()
+    .unwrap();
+  assert_eq!(
+    &contents[label.offset()..label.offset() + label.len()],
+    "wrnog"
+  );
+}
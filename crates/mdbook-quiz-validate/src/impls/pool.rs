@@ -0,0 +1,190 @@
+use std::fs;
+
+use crate::{
+  QuizFormat, SpannedValue, SpannedValueExt, Validate, ValidationContext, cxensure, tomlcast,
+};
+use mdbook_quiz_schema::*;
+use miette::miette;
+
+impl Validate for QuestionPool {
+  fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
+    let files_val = tomlcast!(value.table["files"]);
+    let count_val = tomlcast!(value.table["count"]);
+    cxensure!(
+      cx,
+      !self.files.is_empty(),
+      labels = vec![files_val.labeled_span()],
+      "Pool must have at least one file"
+    );
+    cxensure!(
+      cx,
+      self.count > 0,
+      labels = vec![count_val.labeled_span()],
+      "Pool must sample at least one question"
+    );
+
+    let quiz_dir = cx.path.parent().unwrap().to_path_buf();
+    let mut candidates = Vec::new();
+    for (file, file_val) in self.files.iter().zip(tomlcast!(files_val.array)) {
+      let path = quiz_dir.join(file);
+      let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) => {
+          cx.error(miette!(
+            labels = vec![file_val.labeled_span()],
+            "Failed to read pool file {}: {err}",
+            path.display()
+          ));
+          continue;
+        }
+      };
+
+      // Every question in the pool is validated, whether or not it ends up sampled.
+      cx.validate_dependency(&path, &contents);
+
+      // Errors in the pool file were reported above.
+      let Ok(pool_quiz) = QuizFormat::from_path(&path).parse::<Quiz>(&contents) else {
+        continue;
+      };
+      cxensure!(
+        cx,
+        pool_quiz.pools.is_none(),
+        labels = vec![file_val.labeled_span()],
+        "Pool file {file} has pools of its own, which are not supported"
+      );
+      candidates.extend(pool_quiz.questions);
+    }
+
+    // Questions with several of the pool's tags are only sampled once, so the sample can still
+    // fall short when tags share questions. The sampler reports that when the book is built.
+    let mut short = false;
+    if let Some(tags) = &self.tags {
+      let tags_val = tomlcast!(value.table["tags"]);
+      cxensure!(
+        cx,
+        !tags.is_empty(),
+        labels = vec![tags_val.labeled_span()],
+        "Pool tags must not be empty"
+      );
+
+      candidates.retain(|q| q.tags().iter().any(|t| tags.contains(t)));
+      for ((tag, n), tag_val) in self.tag_counts().into_iter().zip(tomlcast!(tags_val.array)) {
+        let available = candidates
+          .iter()
+          .filter(|q| q.tags().iter().any(|t| t == tag))
+          .count();
+        if available < n {
+          short = true;
+          cx.error(miette!(
+            labels = vec![tag_val.labeled_span()],
+            "Pool samples {n} question(s) tagged `{tag}`, but only {available} are available"
+          ));
+        }
+      }
+    }
+    if !short {
+      cxensure!(
+        cx,
+        candidates.len() >= self.count,
+        labels = vec![count_val.labeled_span()],
+        "Pool samples {} question(s), but only {} are available",
+        self.count,
+        candidates.len()
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::Path;
+
+  use super::*;
+  use crate::{
+    ValidationOptions, ValidationReport,
+    test::{messages, report_at},
+  };
+
+  const POOL: &str = r#"
+[[questions]]
+type = "ShortAnswer"
+tags = ["a"]
+prompt.prompt = "One"
+answer.answer = "1"
+
+[[questions]]
+type = "ShortAnswer"
+tags = ["a", "b"]
+prompt.prompt = "Two"
+answer.answer = "2"
+
+[[questions]]
+type = "ShortAnswer"
+tags = ["b"]
+prompt.prompt = "Three"
+answer.answer = "3"
+"#;
+
+  fn validate_pool(pool: &str, quiz: &str) -> anyhow::Result<ValidationReport> {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("pool.toml"), pool)?;
+    let quiz_path = dir.path().join("quiz.toml");
+    Ok(report_at(&quiz_path, quiz, &ValidationOptions::default()))
+  }
+
+  #[test]
+  fn validate_pools() -> anyhow::Result<()> {
+    let quiz = |pool: &str| format!("[[pools]]\nfiles = [\"pool.toml\"]\n{pool}");
+
+    let report = validate_pool(POOL, &quiz("count = 3"))?;
+    assert!(!report.is_fatal(), "{:?}", messages(&report));
+    let report = validate_pool(POOL, &quiz("count = 3\ntags = [\"a\", \"b\"]"))?;
+    assert!(!report.is_fatal(), "{:?}", messages(&report));
+
+    let report = validate_pool(POOL, &quiz("count = 4"))?;
+    assert_eq!(
+      messages(&report),
+      ["Pool samples 4 question(s), but only 3 are available"]
+    );
+    let report = validate_pool(POOL, &quiz("count = 5\ntags = [\"a\", \"b\"]"))?;
+    assert_eq!(
+      messages(&report),
+      ["Pool samples 3 question(s) tagged `a`, but only 2 are available"]
+    );
+    // "Two" has both tags, but can only be sampled once.
+    let report = validate_pool(POOL, &quiz("count = 4\ntags = [\"a\", \"b\"]"))?;
+    assert_eq!(
+      messages(&report),
+      ["Pool samples 4 question(s), but only 3 are available"]
+    );
+
+    let report = validate_pool(
+      &POOL.replace("prompt.prompt = \"Three\"\n", ""),
+      &quiz("count = 1"),
+    )?;
+    assert!(report.is_fatal());
+    assert!(messages(&report)[0].contains("TOML parse error"));
+
+    let report = validate_pool(POOL, "pools = []")?;
+    assert_eq!(
+      messages(&report),
+      ["`pools` must contain at least one pool"]
+    );
+    let span = report.diagnostics[0]
+      .error
+      .labels()
+      .unwrap()
+      .next()
+      .unwrap();
+    assert_eq!(
+      &"pools = []"[span.offset()..span.offset() + span.len()],
+      "[]"
+    );
+
+    let report = validate_pool(POOL, "[[pools]]\nfiles = [\"missing.toml\"]\ncount = 1")?;
+    assert!(messages(&report)[0].starts_with("Failed to read pool file"));
+    assert!(Path::new(&report.path).ends_with("quiz.toml"));
+
+    Ok(())
+  }
+}
@@ -1,10 +1,8 @@
-use crate::{SpannedValue, Validate, ValidationContext, tomlcast};
+use crate::{SpannedValue, Validate, ValidationContext};
 use mdbook_quiz_schema::*;
 
 impl Validate for ShortAnswerPrompt {
-  fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
-    self.prompt.validate(cx, tomlcast!(value.table["prompt"]))
-  }
+  fn validate(&self, _cx: &mut ValidationContext, _value: &SpannedValue) {}
 }
 
 impl Validate for ShortAnswerAnswer {
@@ -1,27 +1,216 @@
 use std::{
   fs,
+  path::PathBuf,
   process::{Command, Stdio},
 };
 use tempfile::TempDir;
 
-use crate::{SpannedValue, SpannedValueExt, Validate, ValidationContext, cxensure, tomlcast};
+use crate::{
+  SpannedValue, SpannedValueExt, Validate, ValidationContext, cxensure,
+  program_file::read_program_file, tomlcast,
+};
 use mdbook_quiz_schema::*;
+use miette::miette;
+
+/// Returns the prose within a Rust program, i.e. the contents of comments and string literals.
+///
+/// Each element is the byte offset of the prose in `program` along with its text. Escape sequences
+/// and comment markers are replaced by spaces so that offsets within the text are preserved.
+fn program_prose(program: &str) -> Vec<(usize, String)> {
+  let bytes = program.as_bytes();
+  let mut prose = Vec::new();
+  let mut i = 0;
+
+  let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+
+  while i < bytes.len() {
+    let rest = &program[i..];
+    if rest.starts_with("//") {
+      let end = rest.find('\n').map_or(program.len(), |n| i + n);
+      let body = program[i..end].trim_start_matches(['/', '!']);
+      let start = end - body.len();
+      prose.push((start, body.to_string()));
+      i = end;
+    } else if rest.starts_with("/*") {
+      let mut depth = 0;
+      let mut j = i;
+      while j < bytes.len() {
+        if program[j..].starts_with("/*") {
+          depth += 1;
+          j += 2;
+        } else if program[j..].starts_with("*/") {
+          depth -= 1;
+          j += 2;
+          if depth == 0 {
+            break;
+          }
+        } else {
+          j += program[j..].chars().next().unwrap().len_utf8();
+        }
+      }
+      let body = program[i..j].replace(['/', '*', '!'], " ");
+      prose.push((i, body));
+      i = j;
+    } else if bytes[i] == b'"' {
+      let mut text = String::new();
+      let mut j = i + 1;
+      while j < bytes.len() && bytes[j] != b'"' {
+        if bytes[j] == b'\\' {
+          // Lengths are in bytes, and must end on a char boundary since the escaped
+          // characters of an invalid program need not be ASCII.
+          let escape = &program[j..];
+          let len = match escape[1..].chars().next() {
+            Some('x') => escape
+              .char_indices()
+              .nth(4)
+              .map_or(escape.len(), |(n, _)| n),
+            Some('u') => escape.find('}').map_or(2, |n| n + 1),
+            Some(c) => 1 + c.len_utf8(),
+            None => 1,
+          };
+          text.push_str(&" ".repeat(len));
+          j += len;
+        } else {
+          let c = program[j..].chars().next().unwrap();
+          text.push(c);
+          j += c.len_utf8();
+        }
+      }
+      prose.push((i + 1, text));
+      i = (j + 1).min(bytes.len());
+    } else if bytes[i] == b'\'' {
+      // Either a char literal like 'a' or '\n', or a lifetime like 'a.
+      if bytes.get(i + 1) == Some(&b'\\') {
+        i += program[i + 2..]
+          .find('\'')
+          .map_or(bytes.len() - i, |n| n + 3);
+      } else {
+        let c_len = rest[1..].chars().next().map_or(0, char::len_utf8);
+        i += if bytes.get(i + 1 + c_len) == Some(&b'\'') {
+          c_len + 2
+        } else {
+          1
+        };
+      }
+    } else if is_ident(bytes[i]) {
+      let mut j = i;
+      while j < bytes.len() && is_ident(bytes[j]) {
+        j += 1;
+      }
+      let ident = &program[i..j];
+      let hashes = program[j..].bytes().take_while(|b| *b == b'#').count();
+      if matches!(ident, "r" | "br") && bytes.get(j + hashes) == Some(&b'"') {
+        let body_start = j + hashes + 1;
+        let close = format!("\"{}", "#".repeat(hashes));
+        let body_end = program[body_start..]
+          .find(&close)
+          .map_or(program.len(), |n| body_start + n);
+        prose.push((body_start, program[body_start..body_end].to_string()));
+        i = (body_end + close.len()).min(bytes.len());
+      } else {
+        // Note: a `b"..."` byte string is handled as a normal string on the next iteration.
+        i = j;
+      }
+    } else {
+      i += program[i..].chars().next().unwrap().len_utf8();
+    }
+  }
+
+  prose
+}
+
+/// Spellchecks the prose in `program`, whose source is `program_val`.
+///
+/// If the program was read from a file, then `program_val` is its `programFile`,
+/// and misspellings are reported at the whole value.
+fn spellcheck_program(
+  cx: &mut ValidationContext,
+  program: &str,
+  program_val: &SpannedValue,
+  from_file: bool,
+) {
+  let Some(spellchecker) = cx.spellchecker.clone() else {
+    return;
+  };
+  for (offset, text) in program_prose(program) {
+    for (idx, word) in spellchecker.check(&text) {
+      let start = offset + idx;
+      let span = if from_file {
+        (program_val.start(), program_val.end() - program_val.start()).into()
+      } else {
+        cx.string_span(program_val, start..start + word.len())
+      };
+      cx.misspelling(word, span);
+    }
+  }
+}
 
 impl Validate for Tracing {
   fn validate(&self, cx: &mut ValidationContext, value: &SpannedValue) {
     let QuestionFields {
-      prompt: TracingPrompt { program },
+      prompt: TracingPrompt {
+        program,
+        program_file,
+      },
       answer,
       ..
     } = &self.0;
+
+    let prompt_val = tomlcast!(value.table["prompt"]);
+    if program.is_empty() != program_file.is_some() {
+      cx.error(miette!(
+        labels = vec![prompt_val.labeled_span()],
+        "Tracing prompt must have exactly one of a `program` or a `programFile`"
+      ));
+      return;
+    }
+
+    // A program from a file is compiled with its lines aligned to the file, and rustc's messages
+    // refer to the file's path, so that errors can be found in it.
+    let (compiled, source_path) = match program_file {
+      Some(program_file) => {
+        let file_val = tomlcast!(prompt_val.table["programFile"]);
+        let quiz_dir = cx.path.parent().unwrap().to_path_buf();
+        match read_program_file(&quiz_dir, program_file) {
+          Ok(file) => {
+            spellcheck_program(cx, &file.program, file_val, true);
+            let file_path = program_file.split(':').next().unwrap();
+            (file.aligned, quiz_dir.join(file_path))
+          }
+          Err(err) => {
+            cx.error(miette!(labels = vec![file_val.labeled_span()], "{err:#}"));
+            return;
+          }
+        }
+      }
+      None => {
+        spellcheck_program(cx, program, tomlcast!(prompt_val.table["program"]), false);
+        (program.clone(), PathBuf::from("main.rs"))
+      }
+    };
+
     let mut inner = || -> anyhow::Result<()> {
       let dir = TempDir::new()?;
-      let src_path = dir.path().join("main.rs");
-      fs::write(&src_path, program)?;
+      let file_name = source_path.file_name().unwrap();
+      fs::write(dir.path().join(file_name), &compiled)?;
 
-      let rustc_output = Command::new("rustc")
-        .arg(src_path)
-        .args(["-A", "warnings"])
+      let mut rustc = Command::new("rustc");
+      match source_path.parent() {
+        Some(source_dir) if !source_dir.as_os_str().is_empty() => {
+          let mut remap = dir.path().as_os_str().to_owned();
+          remap.push("=");
+          remap.push(source_dir);
+          rustc
+            .arg(dir.path().join(file_name))
+            .arg("--remap-path-prefix")
+            .arg(remap);
+        }
+        _ => {
+          rustc.arg(file_name);
+        }
+      }
+      let rustc_output = rustc
+        .args(["-o", "main", "-A", "warnings"])
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .current_dir(dir.path())
@@ -31,19 +220,19 @@ impl Validate for Tracing {
       let answer_val = tomlcast!(value.table["answer"]);
 
       if rustc_output.status.success() {
-        cxensure!(
-          cx,
-          answer.does_compile,
-          labels = vec![tomlcast!(answer_val.table["doesCompile"]).labeled_span()],
-          "program compiles but doesCompile = false",
-        );
-
-        cxensure!(
-          cx,
-          answer.stdout.is_some(),
-          labels = vec![answer_val.labeled_span()],
-          "program compiles but stdout is missing"
-        );
+        if !answer.does_compile {
+          let error = miette!(
+            labels = vec![tomlcast!(answer_val.table["doesCompile"]).labeled_span()],
+            "program compiles but doesCompile = false",
+          );
+          let fix = cx.fix(
+            answer_val,
+            "doesCompile",
+            Some(toml::Value::Boolean(true)),
+            "set doesCompile to true",
+          );
+          cx.error_with_fix(error, fix);
+        }
 
         let exe_path = dir.path().join("main");
         let cmd_output = Command::new(exe_path)
@@ -62,30 +251,60 @@ impl Validate for Tracing {
           textwrap::indent(&cmd_stderr, "  ")
         );
 
-        let expected_stdout = answer.stdout.as_ref().unwrap();
-        cxensure!(
-          cx,
-          cmd_stdout.trim() == expected_stdout.trim(),
-          labels = vec![tomlcast!(answer_val.table["stdout"]).labeled_span()],
-          "expected stdout:\n{}\ndid not match actual stdout:\n{}",
-          textwrap::indent(expected_stdout, "  "),
-          textwrap::indent(&cmd_stdout, "  ")
-        );
+        let stdout_fix = |cx: &ValidationContext| {
+          let actual = toml::Value::String(cmd_stdout.trim_end().to_string());
+          cx.fix(
+            answer_val,
+            "stdout",
+            Some(actual),
+            "set stdout to the actual output",
+          )
+        };
+        match &answer.stdout {
+          None => {
+            let error = miette!(
+              labels = vec![answer_val.labeled_span()],
+              "program compiles but stdout is missing"
+            );
+            let fix = stdout_fix(cx);
+            cx.error_with_fix(error, fix);
+          }
+          Some(expected_stdout) if cmd_stdout.trim() != expected_stdout.trim() => {
+            let error = miette!(
+              labels = vec![tomlcast!(answer_val.table["stdout"]).labeled_span()],
+              "expected stdout:\n{}\ndid not match actual stdout:\n{}",
+              textwrap::indent(expected_stdout, "  "),
+              textwrap::indent(&cmd_stdout, "  ")
+            );
+            let fix = stdout_fix(cx);
+            cx.error_with_fix(error, fix);
+          }
+          Some(_) => {}
+        }
       } else {
-        cxensure!(
-          cx,
-          !answer.does_compile,
-          labels = vec![tomlcast!(answer_val.table["doesCompile"]).labeled_span()],
-          "program does not compile but doesCompile = true. rustc stderr:\n{}",
-          textwrap::indent(&rustc_stderr, "  ")
-        );
+        if answer.does_compile {
+          let error = miette!(
+            labels = vec![tomlcast!(answer_val.table["doesCompile"]).labeled_span()],
+            "program does not compile but doesCompile = true. rustc stderr:\n{}",
+            textwrap::indent(&rustc_stderr, "  ")
+          );
+          let fix = cx.fix(
+            answer_val,
+            "doesCompile",
+            Some(toml::Value::Boolean(false)),
+            "set doesCompile to false",
+          );
+          cx.error_with_fix(error, fix);
+        }
 
-        cxensure!(
-          cx,
-          answer.stdout.is_none(),
-          labels = vec![answer_val.labeled_span()],
-          "program does not compile but contains a stdout key"
-        );
+        if answer.stdout.is_some() {
+          let error = miette!(
+            labels = vec![answer_val.labeled_span()],
+            "program does not compile but contains a stdout key"
+          );
+          let fix = cx.fix(answer_val, "stdout", None, "remove stdout");
+          cx.error_with_fix(error, fix);
+        }
       }
 
       Ok(())
@@ -141,3 +360,198 @@ answer.stdout = "meep meep"
 "#;
   assert!(crate::test::harness(contents).is_err());
 }
+
+#[test]
+fn tracing_program_prose() {
+  let program = r##"
+// A commment
+fn main<'a>(x: &'a str) {
+  let c = '"';
+  let s = "Hello\nwrold {x}";
+  let r = r#"raw "text""#;
+  /* block /* nested */ */
+}
+"##;
+  let prose = program_prose(program);
+  let texts = prose.iter().map(|(_, t)| t.trim()).collect::<Vec<_>>();
+  assert_eq!(
+    texts,
+    vec![
+      "A commment",
+      "Hello  wrold {x}",
+      r#"raw "text""#,
+      "block    nested"
+    ]
+  );
+  for (offset, text) in &prose {
+    let word = text.split_whitespace().next().unwrap();
+    assert!(program[*offset..].contains(word));
+  }
+}
+
+#[test]
+fn tracing_program_prose_non_ascii_escapes() {
+  // Invalid escapes of non-ASCII characters, as in a program that intentionally fails to compile.
+  let program = r#"fn main() { let s = "\é"; let t = "\x1é café"; /* naïve */ }"#;
+  let prose = program_prose(program);
+  let texts = prose.iter().map(|(_, t)| t.trim()).collect::<Vec<_>>();
+  assert_eq!(texts, vec!["", "café", "naïve"]);
+  // Offsets within the text are offsets within the program.
+  for (offset, text) in &prose[1..] {
+    let word = text.trim();
+    let idx = offset + text.find(word).unwrap();
+    assert!(program[idx..].starts_with(word));
+  }
+}
+
+#[test]
+fn validate_tracing_spellcheck() {
+  let contents = r#"
+[[questions]]
+type = "Tracing"
+prompt.program = """
+fn main() {
+  // Prnt a greeting
+  println!("Hello wrold");
+}
+"""
+answer.doesCompile = true
+answer.stdout = "Hello wrold"
+"#;
+  let report = crate::test::report(contents);
+  let mut messages = crate::test::messages(&report);
+  messages.sort();
+  // Identifiers like `println` are not checked, and `stdout` is not prose.
+  assert_eq!(
+    messages,
+    ["Spelling error: `Prnt`", "Spelling error: `wrold`"]
+  );
+
+  // `wrold` is only flagged in the string literal, not in `answer.stdout`.
+  let wrold = report
+    .diagnostics
+    .iter()
+    .find(|d| d.error.to_string().contains("wrold"))
+    .unwrap();
+  let labels = wrold.error.labels().unwrap().collect::<Vec<_>>();
+  assert_eq!(labels.len(), 1);
+  assert_eq!(
+    &contents[labels[0].offset()..labels[0].offset() + labels[0].len()],
+    "wrold"
+  );
+  assert!(labels[0].offset() < contents.find("answer").unwrap());
+}
+
+#[test]
+fn validate_tracing_hints_spellcheck() {
+  let contents = r#"
+[[questions]]
+type = "Tracing"
+prompt.program = "fn main() {}"
+answer.doesCompile = true
+answer.stdout = ""
+hints = ["Look at the mian function"]
+context = "Nothing is prnted"
+"#;
+  let report = crate::test::report(contents);
+  let messages = crate::test::messages(&report);
+  assert_eq!(messages.len(), 2, "{messages:?}");
+  assert!(messages[0].contains("mian"));
+  assert!(messages[1].contains("prnted"));
+}
+
+#[test]
+fn validate_tracing_fixes() {
+  use crate::PathSegment;
+
+  let contents = r#"
+[[questions]]
+type = "Tracing"
+prompt.program = """
+fn main() {
+  println!("Hello world");
+}
+"""
+answer.doesCompile = false
+"#;
+  let report = crate::test::report(contents);
+  let fixes = report.fixes().collect::<Vec<_>>();
+  assert_eq!(fixes.len(), 2);
+  assert_eq!(
+    fixes[0].path,
+    vec![
+      PathSegment::Key("questions".into()),
+      PathSegment::Index(0),
+      PathSegment::Key("answer".into()),
+      PathSegment::Key("doesCompile".into())
+    ]
+  );
+  assert_eq!(fixes[0].value, Some(toml::Value::Boolean(true)));
+  assert_eq!(
+    fixes[1].value,
+    Some(toml::Value::String("Hello world".into()))
+  );
+}
+
+#[test]
+fn validate_tracing_program_file() -> anyhow::Result<()> {
+  use crate::{ValidationOptions, test::report_at};
+
+  let dir = tempfile::tempdir()?;
+  fs::write(
+    dir.path().join("program.rs"),
+    "// ANCHOR: main\nfn main() {\n  let x: u32 = \"no\";\n}\n// ANCHOR_END: main\n",
+  )?;
+  let contents = r#"
+[[questions]]
+type = "Tracing"
+prompt.programFile = "program.rs:main"
+answer.doesCompile = true
+"#;
+  let report = report_at(
+    &dir.path().join("quiz.toml"),
+    contents,
+    &ValidationOptions::default(),
+  );
+  let message = report.diagnostics[0].error.to_string();
+  let location = format!("--> {}:3:16", dir.path().join("program.rs").display());
+  assert!(message.contains(&location), "{message}");
+
+  let missing = contents.replace("program.rs:main", "program.rs:other");
+  let report = report_at(
+    &dir.path().join("missing.toml"),
+    &missing,
+    &ValidationOptions::default(),
+  );
+  assert_eq!(
+    crate::test::messages(&report),
+    ["Anchor `other` was not found"]
+  );
+
+  Ok(())
+}
+
+#[test]
+fn validate_tracing_missing_program() {
+  let both = r#"
+[[questions]]
+type = "Tracing"
+prompt.programFile = "program.rs"
+prompt.program = "fn main() {}"
+answer.doesCompile = true
+"#;
+  let neither = r#"
+[[questions]]
+type = "Tracing"
+prompt = {}
+answer.doesCompile = true
+"#;
+  for contents in [both, neither] {
+    let report = crate::test::report(contents);
+    assert_eq!(report.diagnostics.len(), 1);
+    assert_eq!(
+      report.diagnostics[0].error.to_string(),
+      "Tracing prompt must have exactly one of a `program` or a `programFile`"
+    );
+  }
+}
@@ -0,0 +1,107 @@
+//! Resolves the questions that a quiz reuses from other quiz files with [`QuizInclude`].
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use mdbook_quiz_schema::QuizInclude;
+
+use crate::QuizFormat;
+
+pub(crate) fn has_tag(question: &toml::Value, tag: &str) -> bool {
+  question
+    .get("tags")
+    .and_then(toml::Value::as_array)
+    .is_some_and(|tags| tags.iter().any(|t| t.as_str() == Some(tag)))
+}
+
+/// Returns true if `question` is selected by `include`, i.e. it has one of the include's IDs or tags.
+fn is_included(include: &QuizInclude, question: &toml::Value) -> bool {
+  let id = question.get("id").and_then(toml::Value::as_str);
+  let by_id = include
+    .ids
+    .iter()
+    .flatten()
+    .any(|included| Some(included.as_str()) == id);
+  let by_tag = include
+    .tags
+    .iter()
+    .flatten()
+    .any(|tag| has_tag(question, tag));
+  by_id || by_tag
+}
+
+fn canonical(path: &Path) -> PathBuf {
+  fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Returns the questions that the quiz at `quiz_path` includes with `include`, in the order they
+/// appear in the included quiz.
+///
+/// Includes are transitive, so the questions of the included quiz are followed by the questions
+/// that it includes in turn. Fails if the includes form a cycle.
+///
+/// `read` reads the contents of a quiz file, so that callers can e.g. add IDs to it first.
+pub fn resolve_include(
+  quiz_path: &Path,
+  include: &QuizInclude,
+  read: &mut dyn FnMut(&Path) -> anyhow::Result<String>,
+) -> anyhow::Result<Vec<toml::Value>> {
+  let path = quiz_path.parent().unwrap().join(&include.path);
+  let questions = load_questions(&path, &mut vec![canonical(quiz_path)], read)?;
+  Ok(
+    questions
+      .into_iter()
+      .filter(|q| is_included(include, q))
+      .collect(),
+  )
+}
+
+/// Loads the questions of the quiz at `path` and its includes.
+///
+/// `stack` contains the quizzes that (transitively) include this one.
+fn load_questions(
+  path: &Path,
+  stack: &mut Vec<PathBuf>,
+  read: &mut dyn FnMut(&Path) -> anyhow::Result<String>,
+) -> anyhow::Result<Vec<toml::Value>> {
+  let path_canonical = canonical(path);
+  if let Some(start) = stack.iter().position(|p| *p == path_canonical) {
+    let cycle = stack[start..]
+      .iter()
+      .chain([&path_canonical])
+      .map(|p| p.display().to_string())
+      .collect::<Vec<_>>()
+      .join(" -> ");
+    bail!("Include cycle: {cycle}");
+  }
+
+  let contents = read(path)?;
+  let mut quiz = QuizFormat::from_path(path)
+    .parse::<toml::Value>(&contents)
+    .with_context(|| format!("Failed to parse included quiz: {}", path.display()))?;
+  crate::expand_snippets(&mut quiz)?;
+  crate::inline_program_files(&mut quiz, path.parent().unwrap())?;
+  let quiz = quiz
+    .as_table_mut()
+    .context("Included quiz is not a table")?;
+  let mut questions = match quiz.remove("questions") {
+    Some(toml::Value::Array(questions)) => questions,
+    _ => Vec::new(),
+  };
+
+  if let Some(includes) = quiz.remove("include") {
+    let includes: Vec<QuizInclude> = includes.try_into()?;
+    let dir = path.parent().unwrap();
+    stack.push(path_canonical);
+    for include in &includes {
+      let included = load_questions(&dir.join(&include.path), stack, read)?;
+      questions.extend(included.into_iter().filter(|q| is_included(include, q)));
+    }
+    stack.pop();
+  }
+
+  Ok(questions)
+}
@@ -3,24 +3,40 @@
 #![warn(missing_docs)]
 
 use std::{
-  cell::RefCell,
-  collections::HashSet,
-  fmt,
+  collections::{BTreeMap, HashMap, HashSet},
+  ops::Range,
   path::{Path, PathBuf},
+  rc::Rc,
   sync::{Arc, Mutex},
 };
 
 use mdbook_quiz_schema::*;
-use miette::{
-  Diagnostic, EyreContext, LabeledSpan, MietteHandler, NamedSource, Result, SourceSpan, miette,
-};
+use miette::{Diagnostic, LabeledSpan, NamedSource, Result, SourceSpan, miette};
+use snippets::{Segment, SegmentSource};
 use thiserror::Error;
 
-pub use spellcheck::register_more_words;
-pub use toml_spanned_value::SpannedValue;
+pub use format::QuizFormat;
+pub use include::resolve_include;
+pub use mdbook_quiz_schema::PathSegment;
+pub use program_file::inline_program_files;
+pub use registry::IdRegistry;
+pub use report::{Fix, QuizDiagnostic, ValidationReport};
+pub use snippets::expand_snippets;
+pub use spellcheck::{
+  DEFAULT_LANGUAGE, load_dictionary, register_dictionary_dir, register_more_words,
+};
+pub use value::{Spanned, SpannedValue, Table, ValueKind};
 
+mod format;
 mod impls;
+mod include;
+mod program_file;
+mod registry;
+mod report;
+mod snippets;
+mod span;
 mod spellcheck;
+mod value;
 
 #[derive(Default)]
 struct ValidatedInner {
@@ -30,45 +46,170 @@ struct ValidatedInner {
 
 #[derive(Default, Clone)]
 /// A thread-safe mutable set of already-validated identifiers and paths.
+///
+/// A question included by other quizzes is only recorded for the file where it is written,
+/// so its ID is a reference from the other quizzes rather than a duplicate.
 pub struct Validated(Arc<Mutex<ValidatedInner>>);
 
-struct QuizDiagnostic {
-  error: miette::Error,
-  fatal: bool,
+impl Validated {
+  /// Records a newly generated question `id`, returning false if it is already used in this build.
+  pub fn insert_id(&self, id: &str) -> bool {
+    self.0.lock().unwrap().ids.insert(id.to_string())
+  }
+}
+
+/// Options that control which checks are run by [`validate`].
+#[derive(Debug, Default, Clone)]
+pub struct ValidationOptions {
+  /// If true, then run a spellchecker on all Markdown strings,
+  /// and on comments and string literals in Tracing programs.
+  pub spellcheck: bool,
+
+  /// The language of the spellcheck dictionary, e.g. `en` or `pt-BR`.
+  ///
+  /// Defaults to [`DEFAULT_LANGUAGE`].
+  pub language: Option<String>,
+
+  /// The directory of the chapter which embeds the quiz.
+  ///
+  /// Relative links and images in Markdown are resolved against this directory.
+  /// If `None`, then links and images are not checked.
+  pub chapter_dir: Option<PathBuf>,
+
+  /// A registry of question IDs that persists across builds.
+  ///
+  /// If `None`, then IDs are only checked for duplicates within a single build.
+  pub id_registry: Option<IdRegistry>,
+
+  /// If true, then warn about questions without an `id`, with a fix that generates one.
+  ///
+  /// Used when IDs can't be generated automatically, e.g. in read-only builds.
+  pub require_ids: bool,
+
+  /// The tags that questions may use. If `None`, then any tag is allowed.
+  pub allowed_tags: Option<Vec<String>>,
+
+  /// The learning objectives that questions may use. If `None`, then any objective is allowed.
+  pub allowed_objectives: Option<Vec<String>>,
 }
 
 pub(crate) struct ValidationContext {
-  diagnostics: RefCell<Vec<QuizDiagnostic>>,
+  diagnostics: Vec<QuizDiagnostic>,
   path: PathBuf,
+  format: QuizFormat,
   contents: String,
+  /// The text shown in diagnostics, which differs from `contents` for embedded quizzes.
+  source: String,
+  root: Option<Rc<SpannedValue>>,
   validated: Validated,
-  spellcheck: bool,
+  options: ValidationOptions,
+  spellchecker: Option<Arc<spellcheck::Spellchecker>>,
+  misspellings: BTreeMap<String, Vec<SourceSpan>>,
+  /// The segments of each string with snippets, keyed by the string's span in the source.
+  expansions: HashMap<(usize, usize), Vec<Segment>>,
 }
 
 impl ValidationContext {
-  pub fn new(path: &Path, contents: &str, validated: Validated, spellcheck: bool) -> Self {
-    ValidationContext {
+  pub fn new(
+    path: &Path,
+    contents: &str,
+    validated: Validated,
+    options: ValidationOptions,
+  ) -> anyhow::Result<Self> {
+    let spellchecker = if options.spellcheck {
+      let language = options.language.as_deref().unwrap_or(DEFAULT_LANGUAGE);
+      Some(spellcheck::spellchecker(language)?)
+    } else {
+      None
+    };
+    Ok(ValidationContext {
       diagnostics: Default::default(),
       path: path.to_owned(),
+      format: QuizFormat::from_path(path),
       contents: contents.to_owned(),
+      source: contents.to_owned(),
+      root: None,
       validated,
-      spellcheck,
-    }
+      options,
+      spellchecker,
+      misspellings: Default::default(),
+      expansions: Default::default(),
+    })
   }
 
-  pub fn add_diagnostic(&mut self, err: impl Into<miette::Error>, fatal: bool) {
-    self.diagnostics.borrow_mut().push(QuizDiagnostic {
-      error: err.into(),
+  pub fn add_diagnostic(&mut self, err: impl Into<miette::Error>, fatal: bool, fix: Option<Fix>) {
+    let src = NamedSource::new(self.path.to_string_lossy(), self.source.clone());
+    self.diagnostics.push(QuizDiagnostic {
+      error: err.into().with_source_code(src),
       fatal,
+      fix,
     });
   }
 
   pub fn error(&mut self, err: impl Into<miette::Error>) {
-    self.add_diagnostic(err, true);
+    self.add_diagnostic(err, true, None);
   }
 
   pub fn warning(&mut self, err: impl Into<miette::Error>) {
-    self.add_diagnostic(err, false);
+    self.add_diagnostic(err, false, None);
+  }
+
+  /// Adds a fatal diagnostic along with a mechanical fix for it.
+  pub fn error_with_fix(&mut self, err: impl Into<miette::Error>, fix: Fix) {
+    self.add_diagnostic(err, true, Some(fix));
+  }
+
+  /// Returns the path from the root of the quiz to `value`.
+  ///
+  /// `value` must be a reference into the value being validated.
+  pub fn path_to(&self, value: &SpannedValue) -> Vec<PathSegment> {
+    fn search(node: &SpannedValue, target: &SpannedValue, path: &mut Vec<PathSegment>) -> bool {
+      if std::ptr::eq(node, target) {
+        return true;
+      }
+      match node.get_ref() {
+        ValueKind::Table(table) => table.iter().any(|(k, v)| {
+          path.push(PathSegment::Key(k.get_ref().clone()));
+          let found = search(v, target, path);
+          if !found {
+            path.pop();
+          }
+          found
+        }),
+        ValueKind::Array(array) => array.iter().enumerate().any(|(i, v)| {
+          path.push(PathSegment::Index(i));
+          let found = search(v, target, path);
+          if !found {
+            path.pop();
+          }
+          found
+        }),
+        _ => false,
+      }
+    }
+
+    let mut path = Vec::new();
+    if let Some(root) = &self.root {
+      search(root, value, &mut path);
+    }
+    path
+  }
+
+  /// Creates a fix that sets `key` in the table `value` to `new_value`, or removes it if `None`.
+  pub fn fix(
+    &self,
+    value: &SpannedValue,
+    key: &str,
+    new_value: Option<toml::Value>,
+    title: impl Into<String>,
+  ) -> Fix {
+    let mut path = self.path_to(value);
+    path.push(PathSegment::Key(key.to_string()));
+    Fix {
+      title: title.into(),
+      path,
+      value: new_value,
+    }
   }
 
   pub fn check(&mut self, f: impl FnOnce() -> Result<()>) {
@@ -87,20 +228,89 @@ impl ValidationContext {
     }
   }
 
-  pub fn contents(&self) -> &str {
-    &self.contents
+  /// Validates another quiz file that this quiz depends on, like a pool or an included quiz,
+  /// and adds its diagnostics to this quiz's. Does nothing if the file was already validated.
+  pub fn validate_dependency(&mut self, path: &Path, contents: &str) {
+    match validate_report(path, contents, &self.validated, &self.options) {
+      Ok(Some(report)) => self
+        .diagnostics
+        .extend(report.diagnostics.into_iter().map(|mut d| {
+          // Fixes are relative to the other file, not this quiz.
+          d.fix = None;
+          d
+        })),
+      Ok(None) => {}
+      Err(err) => self.error(miette!("{err:?}")),
+    }
+  }
+
+  /// Records a misspelled word, to be reported once per quiz with all of its occurrences.
+  pub fn misspelling(&mut self, word: &str, span: SourceSpan) {
+    self
+      .misspellings
+      .entry(word.to_string())
+      .or_default()
+      .push(span);
+  }
+
+  pub fn options(&self) -> &ValidationOptions {
+    &self.options
   }
-}
 
-impl fmt::Debug for ValidationContext {
-  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    let handler = MietteHandler::default();
-    for diagnostic in self.diagnostics.borrow_mut().drain(..) {
-      let src = NamedSource::new(self.path.to_string_lossy(), self.contents.clone());
-      let report = diagnostic.error.with_source_code(src);
-      handler.debug(report.as_ref(), f)?;
+  /// Converts a byte range within the decoded string `value` into a span in the quiz source.
+  ///
+  /// If the string contains snippets, then ranges within a snippet are mapped to the snippet's
+  /// definition.
+  pub fn string_span(&self, value: &SpannedValue, range: Range<usize>) -> SourceSpan {
+    let span = (value.start(), value.end());
+    let segment = self.expansions.get(&span).and_then(|segments| {
+      segments
+        .iter()
+        .find(|segment| segment.range.contains(&range.start))
+        .or(segments.last())
+    });
+    let Some(segment) = segment else {
+      return self.source_string_span(span, range);
+    };
+
+    let clamp = |i: usize| i.clamp(segment.range.start, segment.range.end) - segment.range.start;
+    let (start, end) = (clamp(range.start), clamp(range.end));
+    match &segment.source {
+      SegmentSource::Original(offset) => {
+        self.source_string_span(span, offset + start..offset + end)
+      }
+      SegmentSource::Snippet(name) => {
+        let root = self.root.as_ref().unwrap();
+        let snippet = tomlcast!(root.table["snippets"].table)
+          .get(name.as_str())
+          .unwrap();
+        self.source_string_span((snippet.start(), snippet.end()), start..end)
+      }
     }
-    Ok(())
+  }
+
+  /// Converts a byte range within a decoded string into a span in the quiz source,
+  /// where `span` is the span of the string in the source.
+  ///
+  /// YAML strings that span multiple lines or use single quotes can't be mapped precisely,
+  /// so the span of the whole string is used instead.
+  fn source_string_span(
+    &self,
+    (str_start, str_end): (usize, usize),
+    range: Range<usize>,
+  ) -> SourceSpan {
+    let raw = &self.contents[str_start..str_end];
+    let offsets = match self.format {
+      QuizFormat::Yaml if raw.contains('\n') || raw.starts_with('\'') => {
+        return (str_start, raw.len()).into();
+      }
+      QuizFormat::Yaml if !raw.starts_with('"') => (0..=raw.len()).collect(),
+      _ => span::string_offsets(raw),
+    };
+    let last = offsets.len() - 1;
+    let start = str_start + offsets[range.start.min(last)];
+    let end = str_start + offsets[range.end.min(last)];
+    (start, end.saturating_sub(start)).into()
   }
 }
 
@@ -137,6 +347,9 @@ pub(crate) trait Validate {
 
 pub(crate) trait SpannedValueExt {
   fn labeled_span(&self) -> LabeledSpan;
+
+  /// Returns the value at `path` from this value, if it exists.
+  fn lookup(&self, path: &[PathSegment]) -> Option<&SpannedValue>;
 }
 
 impl SpannedValueExt for SpannedValue {
@@ -144,54 +357,137 @@ impl SpannedValueExt for SpannedValue {
     let span = self.start()..self.end();
     LabeledSpan::new_with_span(None, span)
   }
+
+  fn lookup(&self, path: &[PathSegment]) -> Option<&SpannedValue> {
+    path
+      .iter()
+      .try_fold(self, |value, segment| match (segment, value.get_ref()) {
+        (PathSegment::Key(key), ValueKind::Table(table)) => table.get(key.as_str()),
+        (PathSegment::Index(i), ValueKind::Array(array)) => array.get(*i),
+        _ => None,
+      })
+  }
 }
 
 #[derive(Error, Diagnostic, Debug)]
-#[error("TOML parse error: {cause}")]
+#[error("{format} parse error: {cause}")]
 struct ParseError {
+  format: QuizFormat,
   cause: String,
 
   #[label]
   span: Option<SourceSpan>,
 }
 
-/// Runs validation on a quiz with TOML-format `contents` at `path` under the ID set `ids`.
-pub fn validate(
+/// Runs validation on a quiz with `contents` at `path` under the ID set `ids`,
+/// returning all diagnostics without printing them.
+///
+/// The format of `contents` is determined by the extension of `path`, see [`QuizFormat`].
+///
+/// Returns `None` if the quiz at `path` has already been validated.
+pub fn validate_report(
   path: &Path,
   contents: &str,
   validated: &Validated,
-  spellcheck: bool,
-) -> anyhow::Result<()> {
+  options: &ValidationOptions,
+) -> anyhow::Result<Option<ValidationReport>> {
   let not_checked = validated.0.lock().unwrap().paths.insert(path.to_path_buf());
   if !not_checked {
-    return Ok(());
+    return Ok(None);
   }
 
-  let mut cx = ValidationContext::new(path, contents, validated.clone(), spellcheck);
+  validate_contents(path, contents, contents, validated, options).map(Some)
+}
+
+/// Like [`validate_report`], but for a quiz embedded at `range` within a larger file `source`,
+/// such as a fenced code block in a Markdown chapter. Diagnostics point into `source`.
+///
+/// Embedded quizzes are always validated, even if `path` has already been validated.
+pub fn validate_embedded_report(
+  path: &Path,
+  source: &str,
+  range: Range<usize>,
+  validated: &Validated,
+  options: &ValidationOptions,
+) -> anyhow::Result<ValidationReport> {
+  // Blank out everything outside the quiz, so that offsets into the TOML are offsets into `source`.
+  let contents = source
+    .char_indices()
+    .map(|(i, c)| {
+      if range.contains(&i) || c == '\n' {
+        c.to_string()
+      } else {
+        " ".repeat(c.len_utf8())
+      }
+    })
+    .collect::<String>();
+  validate_contents(path, &contents, source, validated, options)
+}
+
+fn validate_contents(
+  path: &Path,
+  contents: &str,
+  source: &str,
+  validated: &Validated,
+  options: &ValidationOptions,
+) -> anyhow::Result<ValidationReport> {
+  let mut cx = ValidationContext::new(path, contents, validated.clone(), options.clone())?;
+  cx.source = source.to_owned();
+  if let Some(registry) = &options.id_registry {
+    registry.visit_file(path);
+  }
 
-  let parse_result = toml::from_str::<Quiz>(contents);
-  match parse_result {
+  let format = cx.format;
+  match format.parse_spanned::<Quiz>(contents) {
     Ok(quiz) => {
-      let value: SpannedValue = toml::from_str(contents)?;
+      let value: Rc<SpannedValue> = Rc::new(format.parse_value(contents)?);
+      cx.root = Some(Rc::clone(&value));
+      let quiz = cx.expand_snippets(quiz, &value)?;
       quiz.validate(&mut cx, &value)
     }
-    Err(parse_err) => {
+    Err(err) => {
       let error = ParseError {
-        cause: format!("{parse_err}"),
-        span: None,
+        format,
+        cause: err.message,
+        span: err.span.map(SourceSpan::from),
       };
       cx.error(error);
     }
   }
 
-  let has_diagnostic = !cx.diagnostics.borrow().is_empty();
-  let is_fatal = cx.diagnostics.borrow().iter().any(|d| d.fatal);
+  Ok(ValidationReport {
+    path: path.to_path_buf(),
+    diagnostics: cx.diagnostics,
+  })
+}
 
-  if has_diagnostic {
-    eprintln!("{cx:?}");
+/// Runs validation on a quiz with `contents` at `path` under the ID set `ids`.
+///
+/// Prints all diagnostics to stderr, and returns an error if any diagnostic is fatal.
+pub fn validate(
+  path: &Path,
+  contents: &str,
+  validated: &Validated,
+  options: &ValidationOptions,
+) -> anyhow::Result<()> {
+  match validate_report(path, contents, validated, options)? {
+    Some(report) => print_report(&report),
+    None => Ok(()),
   }
+}
 
-  anyhow::ensure!(!is_fatal, "Quiz failed to validate: {}", path.display());
+/// Prints all diagnostics in `report` to stderr, and returns an error if any diagnostic is fatal.
+pub fn print_report(report: &ValidationReport) -> anyhow::Result<()> {
+  let path = &report.path;
+  if !report.diagnostics.is_empty() {
+    eprintln!("{report:?}");
+  }
+
+  anyhow::ensure!(
+    !report.is_fatal(),
+    "Quiz failed to validate: {}",
+    path.display()
+  );
 
   Ok(())
 }
@@ -200,8 +496,45 @@ pub fn validate(
 pub(crate) mod test {
   use super::*;
 
+  pub(crate) fn options() -> ValidationOptions {
+    ValidationOptions {
+      spellcheck: true,
+      ..Default::default()
+    }
+  }
+
+  /// Validates `contents` as a TOML quiz, returning every diagnostic.
+  pub(crate) fn report(contents: &str) -> ValidationReport {
+    report_at(Path::new("dummy.toml"), contents, &options())
+  }
+
+  /// Like [`report`], but for a quiz at `path` that is validated with `options`.
+  pub(crate) fn report_at(
+    path: &Path,
+    contents: &str,
+    options: &ValidationOptions,
+  ) -> ValidationReport {
+    validate_report(path, contents, &Validated::default(), options)
+      .unwrap()
+      .unwrap()
+  }
+
+  /// Returns the message of each diagnostic in `report`.
+  pub(crate) fn messages(report: &ValidationReport) -> Vec<String> {
+    report
+      .diagnostics
+      .iter()
+      .map(|d| d.error.to_string())
+      .collect()
+  }
+
   pub(crate) fn harness(contents: &str) -> anyhow::Result<()> {
-    validate(Path::new("dummy.rs"), contents, &Validated::default(), true)
+    validate(
+      Path::new("dummy.rs"),
+      contents,
+      &Validated::default(),
+      &options(),
+    )
   }
 
   #[test]
@@ -215,8 +548,8 @@ answer.answer = ""
 prompt.distractors = [""]
 "#;
     let validated = Validated::default();
-    validate(Path::new("dummy.rs"), contents, &validated, true)?;
-    validate(Path::new("dummy.rs"), contents, &validated, true)?;
+    validate(Path::new("dummy.rs"), contents, &validated, &options())?;
+    validate(Path::new("dummy.rs"), contents, &validated, &options())?;
     Ok(())
   }
 
@@ -231,4 +564,79 @@ prompt.distractors = [""]
     "#;
     assert!(harness(contents).is_err());
   }
+
+  #[test]
+  fn validate_embedded() -> anyhow::Result<()> {
+    let source = r#"# Chapter with ünïcode
+
+```quiz
+[[questions]]
+id = "embedded"
+type = "MultipleChoice"
+prompt.prompt = ""
+prompt.distractors = [""]
+answer.answer = ""
+prompt.answerIndex = 5
+```
+"#;
+    let start = source.find("[[questions]]").unwrap();
+    let end = source.rfind("```").unwrap();
+    let report = validate_embedded_report(
+      Path::new("chapter.md"),
+      source,
+      start..end,
+      &Validated::default(),
+      &ValidationOptions::default(),
+    )?;
+    assert!(report.is_fatal());
+    let label = report.diagnostics[0]
+      .error
+      .labels()
+      .unwrap()
+      .next()
+      .unwrap();
+    assert_eq!(&source[label.offset()..label.offset() + label.len()], "5");
+    Ok(())
+  }
+
+  #[test]
+  fn validate_formats() {
+    let json = r#"{
+  "questions": [{
+    "id": "json",
+    "type": "MultipleChoice",
+    "prompt": { "prompt": "", "distractors": [""], "answerIndex": 5 },
+    "answer": { "answer": "" }
+  }]
+}"#;
+    let yaml = r#"
+questions:
+  - id: yaml
+    type: MultipleChoice
+    prompt:
+      prompt: ""
+      distractors: [""]
+      answerIndex: 5
+    answer:
+      answer: ""
+"#;
+    for (path, contents) in [("quiz.json", json), ("quiz.yml", yaml)] {
+      let report = report_at(Path::new(path), contents, &ValidationOptions::default());
+      assert!(report.is_fatal());
+      let label = report.diagnostics[0]
+        .error
+        .labels()
+        .unwrap()
+        .next()
+        .unwrap();
+      assert_eq!(&contents[label.offset()..label.offset() + label.len()], "5");
+    }
+
+    let report = report_at(
+      Path::new("bad.json"),
+      r#"{"questions": [}"#,
+      &ValidationOptions::default(),
+    );
+    assert!(messages(&report)[0].starts_with("JSON parse error"));
+  }
 }
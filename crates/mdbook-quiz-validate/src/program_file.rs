@@ -0,0 +1,183 @@
+//! Reads the programs of Tracing questions from Rust files with
+//! [`TracingPrompt::program_file`](mdbook_quiz_schema::TracingPrompt::program_file).
+//!
+//! Like mdBook's `{{#include}}`, the file path can be followed by an anchor name (`file.rs:name`)
+//! or a line range (`file.rs:2`, `file.rs:2:10`, `file.rs:2:`, or `file.rs::10`).
+
+use std::{fs, ops::RangeInclusive, path::Path};
+
+use anyhow::{Context, bail, ensure};
+
+/// The lines of a Rust file selected by a `programFile`.
+pub(crate) struct ProgramFile {
+  /// The selected lines, without any `ANCHOR` comments.
+  pub program: String,
+
+  /// The program with blank lines in place of the lines that were left out,
+  /// so that its line numbers match the file's.
+  pub aligned: String,
+}
+
+fn is_anchor(line: &str) -> bool {
+  line.contains("ANCHOR:") || line.contains("ANCHOR_END:")
+}
+
+fn anchor_name<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+  let (_, rest) = line.split_once(marker)?;
+  rest.split_whitespace().next()
+}
+
+/// Returns the 1-based line numbers selected by `selector` in a file with `lines`.
+fn selected_lines(lines: &[&str], selector: Option<&str>) -> anyhow::Result<RangeInclusive<usize>> {
+  let Some(selector) = selector else {
+    return Ok(1..=lines.len());
+  };
+
+  let parse = |n: &str| -> anyhow::Result<Option<usize>> {
+    if n.is_empty() {
+      return Ok(None);
+    }
+    let n = n
+      .parse::<usize>()
+      .with_context(|| format!("Invalid line number: {n}"))?;
+    ensure!(n > 0, "Line numbers start at 1");
+    Ok(Some(n))
+  };
+
+  let is_range = selector.chars().all(|c| c.is_ascii_digit() || c == ':');
+  if is_range {
+    let (start, end) = match selector.split_once(':') {
+      Some((start, end)) => (parse(start)?, parse(end)?),
+      None => {
+        let line = parse(selector)?;
+        (line, line)
+      }
+    };
+    let start = start.unwrap_or(1);
+    let end = end.unwrap_or(lines.len()).min(lines.len());
+    ensure!(
+      start <= end,
+      "Line range {selector} selects no lines from a file with {} lines",
+      lines.len()
+    );
+    return Ok(start..=end);
+  }
+
+  let Some(start) = lines
+    .iter()
+    .position(|line| anchor_name(line, "ANCHOR:") == Some(selector))
+  else {
+    bail!("Anchor `{selector}` was not found");
+  };
+  let end = lines[start..]
+    .iter()
+    .position(|line| anchor_name(line, "ANCHOR_END:") == Some(selector))
+    .map_or(lines.len(), |n| start + n);
+  Ok(start + 2..=end)
+}
+
+/// Reads the program selected by `program_file`, relative to `quiz_dir`.
+pub(crate) fn read_program_file(
+  quiz_dir: &Path,
+  program_file: &str,
+) -> anyhow::Result<ProgramFile> {
+  let (path, selector) = match program_file.split_once(':') {
+    Some((path, selector)) => (path, Some(selector)),
+    None => (program_file, None),
+  };
+  let path = quiz_dir.join(path);
+  let contents = fs::read_to_string(&path)
+    .with_context(|| format!("Failed to read program file: {}", path.display()))?;
+
+  let lines = contents.lines().collect::<Vec<_>>();
+  let selected = selected_lines(&lines, selector)?;
+  let mut program = Vec::new();
+  let mut aligned = Vec::new();
+  for (i, line) in lines.iter().enumerate() {
+    if selected.contains(&(i + 1)) && !is_anchor(line) {
+      program.push(*line);
+      aligned.push(*line);
+    } else if i + 1 < *selected.end() {
+      aligned.push("");
+    }
+  }
+
+  Ok(ProgramFile {
+    program: program.join("\n"),
+    aligned: aligned.join("\n"),
+  })
+}
+
+/// Replaces the `programFile` of every Tracing question in `quiz` with the `program` it selects.
+///
+/// Paths are relative to `quiz_dir`, the directory of the quiz file.
+pub fn inline_program_files(quiz: &mut toml::Value, quiz_dir: &Path) -> anyhow::Result<()> {
+  let Some(questions) = quiz
+    .get_mut("questions")
+    .and_then(toml::Value::as_array_mut)
+  else {
+    return Ok(());
+  };
+  for question in questions {
+    let Some(prompt) = question
+      .get_mut("prompt")
+      .and_then(toml::Value::as_table_mut)
+    else {
+      continue;
+    };
+    if let Some(program_file) = prompt.remove("programFile") {
+      let program_file = program_file
+        .as_str()
+        .context("programFile must be a string")?;
+      let file = read_program_file(quiz_dir, program_file)?;
+      prompt.insert("program".into(), toml::Value::String(file.program));
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const FILE: &str = "\
+// ANCHOR: all
+struct Point(i32);
+// ANCHOR: main
+fn main() {
+  println!(\"{}\", Point(1).0);
+}
+// ANCHOR_END: main
+// ANCHOR_END: all
+";
+
+  #[test]
+  fn read_program_files() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("point.rs"), FILE)?;
+    let read = |spec: &str| read_program_file(dir.path(), spec);
+
+    let main = read("point.rs:main")?;
+    assert_eq!(
+      main.program,
+      "fn main() {\n  println!(\"{}\", Point(1).0);\n}"
+    );
+    assert_eq!(main.aligned.lines().count(), 6);
+    assert_eq!(main.aligned.lines().nth(3), Some("fn main() {"));
+
+    let all = read("point.rs")?;
+    assert_eq!(all.program.lines().count(), 4);
+    assert!(!all.program.contains("ANCHOR"));
+
+    assert_eq!(read("point.rs:2")?.program, "struct Point(i32);");
+    assert_eq!(read("point.rs:4:")?.program.lines().count(), 3);
+    assert_eq!(read("point.rs::2")?.program, "struct Point(i32);");
+    assert_eq!(read("point.rs:4:5")?.program.lines().count(), 2);
+
+    assert!(read("point.rs:missing").is_err());
+    assert!(read("point.rs:20").is_err());
+    assert!(read("missing.rs").is_err());
+
+    Ok(())
+  }
+}
@@ -0,0 +1,536 @@
+//! A persistent registry of question IDs, stored in a lockfile that is shared across builds.
+
+use std::{
+  collections::{BTreeMap, HashSet},
+  fmt, fs,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use mdbook_quiz_schema::Question;
+use miette::{Diagnostic, SourceSpan, miette};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+  QuizFormat, SpannedValue, SpannedValueExt, ValidationContext, program_file::read_program_file,
+  tomlcast,
+};
+
+const LOCKFILE_HEADER: &str = "\
+# This file is generated by mdbook-quiz. It records every question ID
+# so that IDs are never reused by a different question.
+# It should be checked into version control.
+";
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Question ID is already used by a question in {file}")]
+#[diagnostic(help(
+  "IDs must be unique across every book that shares {lockfile}. Give this question a new ID."
+))]
+struct ReusedId {
+  file: String,
+  lockfile: String,
+
+  #[label]
+  span: SourceSpan,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Question ID belonged to a question that was removed from {file}")]
+#[diagnostic(help(
+  "give this question a new ID, or delete the ID from {lockfile} if the original question is being restored"
+))]
+struct RemovedId {
+  file: String,
+  lockfile: String,
+
+  #[label]
+  span: SourceSpan,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Question ID changed type from {old} to {new}")]
+#[diagnostic(help("a question with a different type should have a new ID"))]
+struct ChangedType {
+  old: String,
+  new: String,
+
+  #[label]
+  span: SourceSpan,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Question was edited but kept the same ID")]
+#[diagnostic(
+  severity(Warning),
+  help(
+    "the prompt or answer changed since the ID was recorded in {lockfile}. If this is a different question, give it a new ID. If not, delete the `hash` of this ID from {lockfile}."
+  )
+)]
+struct EditedQuestion {
+  lockfile: String,
+
+  #[label]
+  span: SourceSpan,
+}
+
+#[derive(Error, Diagnostic, Debug)]
+#[error("Question ID {id} was removed from {file}")]
+#[diagnostic(
+  severity(Warning),
+  help("the ID is kept in {lockfile} so that it cannot be reused")
+)]
+struct VanishedId {
+  id: String,
+  file: String,
+  lockfile: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IdEntry {
+  /// The quiz file containing the question, relative to the lockfile.
+  file: String,
+
+  /// The type of the question, e.g. `Tracing`.
+  #[serde(rename = "type")]
+  question_type: String,
+
+  /// A hash of the normalized prompt and answer of the question.
+  ///
+  /// If missing, then the hash of the current question is recorded on the next build.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  hash: Option<String>,
+
+  /// If true, then the question was removed and its ID must not be reused.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  removed: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Lockfile {
+  #[serde(default)]
+  ids: BTreeMap<String, IdEntry>,
+}
+
+struct RegistryInner {
+  lockfile: PathBuf,
+  entries: BTreeMap<String, IdEntry>,
+
+  /// Quiz files validated in this build, relative to the lockfile.
+  files: HashSet<String>,
+
+  /// IDs seen in this build.
+  seen: HashSet<String>,
+}
+
+/// A registry of every question ID, persisted in a lockfile like `quiz-ids.lock`.
+///
+/// Detects IDs that are reused by another quiz file (e.g. in another book that shares
+/// the lockfile), IDs whose question changed type or was substantively edited,
+/// and IDs whose question was removed.
+#[derive(Clone)]
+pub struct IdRegistry(Arc<Mutex<RegistryInner>>);
+
+impl fmt::Debug for IdRegistry {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let inner = self.0.lock().unwrap();
+    f.debug_tuple("IdRegistry").field(&inner.lockfile).finish()
+  }
+}
+
+/// Writes `value` with table keys in sorted order and whitespace collapsed in strings,
+/// so its hash doesn't change with formatting.
+fn write_normalized(value: &toml::Value, out: &mut String) {
+  match value {
+    toml::Value::Table(table) => {
+      let sorted = table.iter().collect::<BTreeMap<_, _>>();
+      out.push('{');
+      for (key, value) in sorted {
+        out.push_str(&format!("{key:?}="));
+        write_normalized(value, out);
+        out.push(',');
+      }
+      out.push('}');
+    }
+    toml::Value::Array(values) => {
+      out.push('[');
+      for value in values {
+        write_normalized(value, out);
+        out.push(',');
+      }
+      out.push(']');
+    }
+    toml::Value::String(s) => {
+      let words = s.split_whitespace().collect::<Vec<_>>();
+      out.push_str(&format!("{:?}", words.join(" ")));
+    }
+    value => out.push_str(&value.to_string()),
+  }
+}
+
+/// Returns the type of `question` and a fingerprint of its content.
+///
+/// The fingerprint only covers the prompt and the answer, so that edits to
+/// other fields like `context` are not considered substantive. A `programFile`
+/// is fingerprinted by the program it selects, relative to `quiz_dir`.
+pub(crate) fn fingerprint(
+  question: &Question,
+  quiz_dir: &Path,
+) -> anyhow::Result<(String, String)> {
+  let mut value = toml::Value::try_from(question)?;
+  let question_type = value["type"].as_str().unwrap().to_string();
+  if let Some(prompt) = value.get_mut("prompt").and_then(toml::Value::as_table_mut)
+    && let Some(toml::Value::String(program_file)) = prompt.remove("programFile")
+  {
+    let file = read_program_file(quiz_dir, &program_file)?;
+    prompt.insert("program".into(), toml::Value::String(file.program));
+  }
+
+  let mut normalized = String::new();
+  for key in ["prompt", "answer"] {
+    if let Some(field) = value.get(key) {
+      write_normalized(field, &mut normalized);
+    }
+  }
+  let hash = Sha256::digest(normalized.as_bytes())
+    .iter()
+    .map(|byte| format!("{byte:02x}"))
+    .collect();
+  Ok((question_type, hash))
+}
+
+impl IdRegistry {
+  /// Loads the registry from `lockfile`, or creates an empty registry if it doesn't exist.
+  pub fn load(lockfile: &Path) -> anyhow::Result<Self> {
+    let contents = if lockfile.exists() {
+      fs::read_to_string(lockfile)
+        .with_context(|| format!("Failed to read ID lockfile: {}", lockfile.display()))?
+    } else {
+      String::new()
+    };
+    let parsed: Lockfile = toml::from_str(&contents)
+      .with_context(|| format!("Failed to parse ID lockfile: {}", lockfile.display()))?;
+    Ok(IdRegistry(Arc::new(Mutex::new(RegistryInner {
+      lockfile: lockfile.to_path_buf(),
+      entries: parsed.ids,
+      files: HashSet::new(),
+      seen: HashSet::new(),
+    }))))
+  }
+
+  /// Returns the path of `quiz_path` relative to the directory of the lockfile.
+  fn relative_path(inner: &RegistryInner, quiz_path: &Path) -> String {
+    let canonicalize = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let quiz_path = canonicalize(quiz_path);
+    let root = canonicalize(inner.lockfile.parent().unwrap_or(Path::new(".")));
+    let relative = quiz_path.strip_prefix(&root).unwrap_or(&quiz_path);
+    relative
+      .components()
+      .map(|c| c.as_os_str().to_string_lossy())
+      .collect::<Vec<_>>()
+      .join("/")
+  }
+
+  /// Returns true if the quiz `file` (relative to the lockfile) still contains a question with `id`.
+  fn file_contains_id(inner: &RegistryInner, file: &str, id: &str) -> bool {
+    let root = inner.lockfile.parent().unwrap_or(Path::new("."));
+    let path = root.join(file);
+    let Ok(contents) = fs::read_to_string(&path) else {
+      return false;
+    };
+    let Ok(value) = QuizFormat::from_path(&path).parse::<toml::Value>(&contents) else {
+      return false;
+    };
+    value
+      .get("questions")
+      .and_then(|qs| qs.as_array())
+      .into_iter()
+      .flatten()
+      .any(|q| q.get("id").and_then(|v| v.as_str()) == Some(id))
+  }
+
+  pub(crate) fn visit_file(&self, quiz_path: &Path) {
+    let mut inner = self.0.lock().unwrap();
+    let file = Self::relative_path(&inner, quiz_path);
+    inner.files.insert(file);
+  }
+
+  /// Checks `question` against the registry, and records it.
+  pub(crate) fn check(
+    &self,
+    cx: &mut ValidationContext,
+    question: &Question,
+    id: &str,
+    value: &SpannedValue,
+  ) {
+    let id_value = tomlcast!(value.table["id"]);
+    let span = SourceSpan::from(id_value.start()..id_value.end());
+    let (question_type, hash) = match fingerprint(question, cx.path.parent().unwrap()) {
+      Ok(fingerprint) => fingerprint,
+      Err(err) => {
+        cx.error(miette!(labels = vec![id_value.labeled_span()], "{err:#}"));
+        return;
+      }
+    };
+    let mut inner = self.0.lock().unwrap();
+    let file = Self::relative_path(&inner, &cx.path);
+    let lockfile = inner.lockfile.display().to_string();
+    inner.seen.insert(id.to_string());
+
+    let new_entry = IdEntry {
+      file: file.clone(),
+      question_type: question_type.clone(),
+      hash: Some(hash.clone()),
+      removed: false,
+    };
+    let Some(entry) = inner.entries.get(id) else {
+      inner.entries.insert(id.to_string(), new_entry);
+      return;
+    };
+
+    if entry.removed {
+      let file = entry.file.clone();
+      drop(inner);
+      cx.error(RemovedId {
+        file,
+        lockfile,
+        span,
+      });
+    } else if entry.file != file && Self::file_contains_id(&inner, &entry.file, id) {
+      let file = entry.file.clone();
+      drop(inner);
+      cx.error(ReusedId {
+        file,
+        lockfile,
+        span,
+      });
+    } else if entry.question_type != question_type {
+      let old = entry.question_type.clone();
+      drop(inner);
+      cx.error(ChangedType {
+        old,
+        new: question_type,
+        span,
+      });
+    } else if entry
+      .hash
+      .as_ref()
+      .is_some_and(|old_hash| *old_hash != hash)
+    {
+      drop(inner);
+      let new_id = Uuid::new_v4().to_string();
+      let fix = cx.fix(
+        value,
+        "id",
+        Some(toml::Value::String(new_id)),
+        "give the question a new ID",
+      );
+      cx.add_diagnostic(EditedQuestion { lockfile, span }, false, Some(fix));
+    } else {
+      // The question is unchanged, or was moved to another file.
+      inner.entries.insert(id.to_string(), new_entry);
+    }
+  }
+
+  /// Marks IDs that disappeared from their quiz file as removed, and writes the lockfile.
+  ///
+  /// An ID has disappeared if its quiz file was validated in this build but no longer
+  /// contains the ID, or if its quiz file no longer exists. Prints a warning for each one.
+  pub fn finish(&self) -> anyhow::Result<()> {
+    let mut inner = self.0.lock().unwrap();
+    let root = inner
+      .lockfile
+      .parent()
+      .unwrap_or(Path::new("."))
+      .to_path_buf();
+    let lockfile = inner.lockfile.display().to_string();
+
+    let RegistryInner {
+      entries,
+      files,
+      seen,
+      ..
+    } = &mut *inner;
+    for (id, entry) in entries.iter_mut() {
+      let vanished = !entry.removed
+        && !seen.contains(id)
+        && (files.contains(&entry.file) || !root.join(&entry.file).exists());
+      if vanished {
+        entry.removed = true;
+        let warning = miette::Report::new(VanishedId {
+          id: id.clone(),
+          file: entry.file.clone(),
+          lockfile: lockfile.clone(),
+        });
+        eprintln!("{warning:?}");
+      }
+    }
+
+    let lockfile = Lockfile {
+      ids: inner.entries.clone(),
+    };
+    let contents = format!("{LOCKFILE_HEADER}\n{}", toml::to_string(&lockfile)?);
+    fs::write(&inner.lockfile, contents)
+      .with_context(|| format!("Failed to write ID lockfile: {}", inner.lockfile.display()))?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::{
+    PathSegment, Validated, ValidationOptions, ValidationReport, test::messages, validate_report,
+  };
+
+  const QUIZ: &str = r#"
+[[questions]]
+id = "q1"
+type = "ShortAnswer"
+prompt.prompt = "What is the keyword for a mutable variable?"
+answer.answer = "mut"
+"#;
+
+  fn build(lockfile: &Path, quizzes: &[(&Path, &str)]) -> anyhow::Result<Vec<bool>> {
+    Ok(
+      build_reports(lockfile, quizzes)?
+        .iter()
+        .map(ValidationReport::is_fatal)
+        .collect(),
+    )
+  }
+
+  fn build_reports(
+    lockfile: &Path,
+    quizzes: &[(&Path, &str)],
+  ) -> anyhow::Result<Vec<ValidationReport>> {
+    let registry = IdRegistry::load(lockfile)?;
+    let options = ValidationOptions {
+      id_registry: Some(registry.clone()),
+      ..Default::default()
+    };
+    let validated = Validated::default();
+    let mut reports = Vec::new();
+    for (path, contents) in quizzes {
+      fs::write(path, contents)?;
+      reports.push(validate_report(path, contents, &validated, &options)?.unwrap());
+    }
+    registry.finish()?;
+    Ok(reports)
+  }
+
+  #[test]
+  fn id_registry() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let lockfile = dir.path().join("quiz-ids.lock");
+    let a = dir.path().join("a.toml");
+    let b = dir.path().join("b.toml");
+
+    assert_eq!(build(&lockfile, &[(&a, QUIZ)])?, vec![false]);
+    let contents = fs::read_to_string(&lockfile)?;
+    assert!(contents.contains("[ids.q1]\nfile = \"a.toml\"\ntype = \"ShortAnswer\""));
+
+    // Reusing an ID in another file is an error, as is changing its type.
+    assert_eq!(build(&lockfile, &[(&b, QUIZ)])?, vec![true]);
+    let changed = QUIZ.replace("ShortAnswer", "Tracing").replace(
+      "prompt.prompt = \"What is the keyword for a mutable variable?\"\nanswer.answer = \"mut\"",
+      "prompt.program = \"fn main() {}\"\nanswer.doesCompile = true\nanswer.stdout = \"\"",
+    );
+    assert_eq!(build(&lockfile, &[(&a, &changed)])?, vec![true]);
+
+    // Moving a question to another file is fine.
+    fs::remove_file(&a)?;
+    assert_eq!(build(&lockfile, &[(&b, QUIZ)])?, vec![false]);
+
+    // Removing a question marks its ID as removed, so it can't be reused.
+    let other = QUIZ.replace("q1", "q2");
+    assert_eq!(build(&lockfile, &[(&b, &other)])?, vec![false]);
+    assert!(fs::read_to_string(&lockfile)?.contains("removed = true"));
+    assert_eq!(build(&lockfile, &[(&a, QUIZ)])?, vec![true]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn id_registry_edits() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let lockfile = dir.path().join("quiz-ids.lock");
+    let a = dir.path().join("a.toml");
+    build(&lockfile, &[(&a, QUIZ)])?;
+
+    // Reformatting or adding context is not a substantive edit.
+    let reformatted = QUIZ.replace("a mutable", "a\\n  mutable") + "context = \"See chapter 3.\"\n";
+    let reports = build_reports(&lockfile, &[(&a, &reformatted)])?;
+    assert!(reports[0].diagnostics.is_empty());
+
+    // Changing the answer is, so the validator suggests a new ID.
+    let edited = QUIZ.replace("\"mut\"", "\"let mut\"");
+    let reports = build_reports(&lockfile, &[(&a, &edited)])?;
+    assert!(!reports[0].is_fatal());
+    let fixes = reports[0].fixes().collect::<Vec<_>>();
+    assert_eq!(fixes.len(), 1);
+    assert_eq!(fixes[0].path.last(), Some(&PathSegment::Key("id".into())));
+    assert_ne!(fixes[0].value.as_ref().unwrap().as_str(), Some("q1"));
+
+    // The warning persists until the hash is removed from the lockfile.
+    assert_eq!(
+      build_reports(&lockfile, &[(&a, &edited)])?[0]
+        .fixes()
+        .count(),
+      1
+    );
+    let contents = fs::read_to_string(&lockfile)?;
+    let without_hash = contents
+      .lines()
+      .filter(|line| !line.starts_with("hash"))
+      .collect::<Vec<_>>()
+      .join("\n");
+    fs::write(&lockfile, without_hash)?;
+    assert_eq!(
+      build_reports(&lockfile, &[(&a, &edited)])?[0]
+        .fixes()
+        .count(),
+      0
+    );
+    assert_eq!(
+      build_reports(&lockfile, &[(&a, &edited)])?[0]
+        .fixes()
+        .count(),
+      0
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn id_registry_program_file() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let lockfile = dir.path().join("quiz-ids.lock");
+    let a = dir.path().join("a.toml");
+    let program = dir.path().join("program.rs");
+    let quiz = r#"
+[[questions]]
+id = "q1"
+type = "Tracing"
+prompt.programFile = "program.rs"
+answer.doesCompile = true
+answer.stdout = "1"
+"#;
+    fs::write(&program, "fn main() {\n  println!(\"1\");\n}\n")?;
+    let reports = build_reports(&lockfile, &[(&a, quiz)])?;
+    assert_eq!(messages(&reports[0]), Vec::<String>::new());
+
+    // Editing the program file is a substantive edit, even though the quiz is unchanged.
+    fs::write(&program, "fn main() {\n  println!(\"{}\", 1);\n}\n")?;
+    assert_eq!(
+      build_reports(&lockfile, &[(&a, quiz)])?[0].fixes().count(),
+      1
+    );
+
+    Ok(())
+  }
+}
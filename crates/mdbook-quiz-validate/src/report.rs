@@ -0,0 +1,61 @@
+use std::{fmt, path::PathBuf};
+
+use mdbook_quiz_schema::PathSegment;
+use miette::{EyreContext, MietteHandler};
+
+/// A mechanical fix for a diagnostic, which sets or removes a single key in a quiz file.
+#[derive(Debug, Clone)]
+pub struct Fix {
+  /// A short description of the fix, e.g. "set doesCompile to true".
+  pub title: String,
+
+  /// The path from the root of the quiz file to the key being fixed.
+  /// The last segment is always a [`PathSegment::Key`].
+  pub path: Vec<PathSegment>,
+
+  /// The new value of the key, or `None` if the key should be removed.
+  pub value: Option<toml::Value>,
+}
+
+/// A single error or warning produced by validating a quiz.
+pub struct QuizDiagnostic {
+  /// The diagnostic, with the quiz file attached as its source code.
+  pub error: miette::Error,
+
+  /// If true, then the quiz should not be built.
+  pub fatal: bool,
+
+  /// A mechanical fix for the diagnostic, if one exists.
+  pub fix: Option<Fix>,
+}
+
+/// The diagnostics produced by validating a single quiz.
+pub struct ValidationReport {
+  /// The path to the quiz file.
+  pub path: PathBuf,
+
+  /// The diagnostics, in the order they were found.
+  pub diagnostics: Vec<QuizDiagnostic>,
+}
+
+impl ValidationReport {
+  /// Returns true if any diagnostic is fatal.
+  pub fn is_fatal(&self) -> bool {
+    self.diagnostics.iter().any(|d| d.fatal)
+  }
+
+  /// Returns all the fixes suggested by the diagnostics.
+  pub fn fixes(&self) -> impl Iterator<Item = &Fix> {
+    self.diagnostics.iter().filter_map(|d| d.fix.as_ref())
+  }
+}
+
+impl fmt::Debug for ValidationReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let handler = MietteHandler::default();
+    for diagnostic in &self.diagnostics {
+      handler.debug(diagnostic.error.as_ref(), f)?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,306 @@
+//! Expands `{{snippet name}}` placeholders with the [`Quiz::snippets`] of a quiz.
+//!
+//! Placeholders are expanded in every string of a quiz's `questions` and `multipart` contexts.
+//! Snippets can't use other snippets.
+
+use std::{collections::HashMap, ops::Range};
+
+use anyhow::bail;
+use mdbook_quiz_schema::Quiz;
+use miette::{LabeledSpan, Severity, miette};
+
+use crate::{SpannedValue, ValidationContext, ValueKind};
+
+const OPEN: &str = "{{snippet ";
+const CLOSE: &str = "}}";
+
+/// The keys of a quiz whose strings can contain placeholders.
+const EXPANDED_KEYS: [&str; 2] = ["questions", "multipart"];
+
+/// Returns the byte range and snippet name of each placeholder in `s`.
+fn placeholders(s: &str) -> Vec<(Range<usize>, &str)> {
+  let mut found = Vec::new();
+  let mut i = 0;
+  while let Some(start) = s[i..].find(OPEN).map(|n| i + n) {
+    let Some(end) = s[start..].find(CLOSE).map(|n| start + n + CLOSE.len()) else {
+      break;
+    };
+    let name = s[start + OPEN.len()..end - CLOSE.len()].trim();
+    found.push((start..end, name));
+    i = end;
+  }
+  found
+}
+
+/// Where a part of an expanded string came from.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SegmentSource {
+  /// The original string, starting at the given offset.
+  Original(usize),
+
+  /// The snippet with the given name, starting at its beginning.
+  Snippet(String),
+}
+
+/// A contiguous part of an expanded string.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Segment {
+  /// The range of the part within the expanded string.
+  pub range: Range<usize>,
+  pub source: SegmentSource,
+}
+
+/// Expands the placeholders in `s` whose snippet is defined. Other placeholders are left as-is.
+///
+/// Returns the expanded string along with the segments it was built from.
+fn expand(s: &str, snippets: &HashMap<String, String>) -> (String, Vec<Segment>) {
+  let mut expanded = String::new();
+  let mut segments = Vec::new();
+  let mut push = |expanded: &mut String, text: &str, source: SegmentSource| {
+    if !text.is_empty() {
+      let start = expanded.len();
+      expanded.push_str(text);
+      segments.push(Segment {
+        range: start..expanded.len(),
+        source,
+      });
+    }
+  };
+
+  let mut last = 0;
+  for (range, name) in placeholders(s) {
+    if let Some(snippet) = snippets.get(name) {
+      push(
+        &mut expanded,
+        &s[last..range.start],
+        SegmentSource::Original(last),
+      );
+      push(
+        &mut expanded,
+        snippet,
+        SegmentSource::Snippet(name.to_string()),
+      );
+      last = range.end;
+    }
+  }
+  push(&mut expanded, &s[last..], SegmentSource::Original(last));
+
+  (expanded, segments)
+}
+
+fn for_each_string(
+  value: &mut toml::Value,
+  f: &mut impl FnMut(&mut String) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+  match value {
+    toml::Value::String(s) => f(s)?,
+    toml::Value::Array(array) => {
+      for value in array {
+        for_each_string(value, f)?;
+      }
+    }
+    toml::Value::Table(table) => {
+      for (_, value) in table.iter_mut() {
+        for_each_string(value, f)?;
+      }
+    }
+    _ => {}
+  }
+  Ok(())
+}
+
+/// Replaces every `{{snippet name}}` placeholder in `quiz` with its snippet, and removes the
+/// `snippets` table. Fails if a placeholder uses a snippet that isn't defined.
+pub fn expand_snippets(quiz: &mut toml::Value) -> anyhow::Result<()> {
+  let Some(table) = quiz.as_table_mut() else {
+    return Ok(());
+  };
+  let Some(snippets) = table.remove("snippets") else {
+    return Ok(());
+  };
+  let snippets: HashMap<String, String> = snippets.try_into()?;
+
+  for key in EXPANDED_KEYS {
+    if let Some(value) = table.get_mut(key) {
+      for_each_string(value, &mut |s| {
+        if let Some((_, name)) = placeholders(s)
+          .into_iter()
+          .find(|(_, name)| !snippets.contains_key(*name))
+        {
+          bail!("Quiz has no snippet named `{name}`");
+        }
+        *s = expand(s, &snippets).0;
+        Ok(())
+      })?;
+    }
+  }
+
+  Ok(())
+}
+
+impl ValidationContext {
+  /// Expands the snippets of `quiz`, whose source is `value`, and returns the expanded quiz.
+  ///
+  /// The segments of each expanded string are recorded so that [`ValidationContext::string_span`]
+  /// can map offsets in the expanded string back to the placeholder's snippet.
+  pub(crate) fn expand_snippets(
+    &mut self,
+    quiz: Quiz,
+    value: &SpannedValue,
+  ) -> anyhow::Result<Quiz> {
+    let Some(snippets) = &quiz.snippets else {
+      return Ok(quiz);
+    };
+
+    let mut expanded = value.clone();
+    let mut used = Vec::new();
+    for key in EXPANDED_KEYS {
+      if let Some(value) = expanded.get_mut().as_table_mut().unwrap().get_mut(key) {
+        self.expand_value(value, snippets, &mut used);
+      }
+    }
+
+    let snippets_val = value.get_ref().get("snippets").unwrap();
+    for (name, snippet_val) in snippets_val.get_ref().as_table().unwrap() {
+      if !used.contains(name.get_ref()) {
+        self.warning(miette!(
+          severity = Severity::Warning,
+          labels = vec![LabeledSpan::new_with_span(
+            None,
+            name.start()..snippet_val.end()
+          )],
+          "Snippet `{}` is never used",
+          name.get_ref()
+        ));
+      }
+    }
+
+    Ok(expanded.get_ref().to_toml().try_into()?)
+  }
+
+  fn expand_value(
+    &mut self,
+    value: &mut SpannedValue,
+    snippets: &HashMap<String, String>,
+    used: &mut Vec<String>,
+  ) {
+    let span = (value.start(), value.end());
+    match value.get_mut() {
+      ValueKind::String(s) => {
+        let found = placeholders(s);
+        if found.is_empty() {
+          return;
+        }
+        for (range, name) in found {
+          if snippets.contains_key(name) {
+            used.push(name.to_string());
+          } else {
+            let span = self.source_string_span(span, range);
+            self.error(miette!(
+              labels = vec![LabeledSpan::new_with_span(None, span)],
+              "Quiz has no snippet named `{name}`"
+            ));
+          }
+        }
+        let (expanded, segments) = expand(s, snippets);
+        *s = expanded;
+        self.expansions.insert(span, segments);
+      }
+      ValueKind::Array(array) => {
+        for value in array {
+          self.expand_value(value, snippets, used);
+        }
+      }
+      ValueKind::Table(table) => {
+        for (_, value) in table.iter_mut() {
+          self.expand_value(value, snippets, used);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+#[test]
+fn expand_placeholders() {
+  let snippets = HashMap::from([("s".to_string(), "struct S;".to_string())]);
+  let (expanded, segments) = expand("a {{snippet s}} b {{snippet t}}", &snippets);
+  assert_eq!(expanded, "a struct S; b {{snippet t}}");
+  assert_eq!(
+    segments,
+    [
+      Segment {
+        range: 0..2,
+        source: SegmentSource::Original(0)
+      },
+      Segment {
+        range: 2..11,
+        source: SegmentSource::Snippet("s".into())
+      },
+      Segment {
+        range: 11..27,
+        source: SegmentSource::Original(15)
+      },
+    ]
+  );
+
+  let mut quiz: toml::Value = toml::toml! {
+    [snippets]
+    s = "struct S;"
+
+    [[questions]]
+    prompt = "{{snippet s}}"
+  };
+  expand_snippets(&mut quiz).unwrap();
+  assert_eq!(quiz.get("snippets"), None);
+  assert_eq!(quiz["questions"][0]["prompt"].as_str(), Some("struct S;"));
+}
+
+#[test]
+fn validate_snippets() {
+  use crate::test::messages;
+
+  let label_text = |report: &crate::ValidationReport, contents: &str, i: usize| {
+    let label = report.diagnostics[i]
+      .error
+      .labels()
+      .unwrap()
+      .next()
+      .unwrap();
+    contents[label.offset()..label.offset() + label.len()].to_string()
+  };
+
+  // Diagnostics within a snippet point at its definition.
+  let contents = r#"
+[snippets]
+note = "A mispeled word"
+
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Question {{snippet note}} and a tpyo"
+answer.answer = "1"
+"#;
+  let report = crate::test::report(contents);
+  assert_eq!(report.diagnostics.len(), 2);
+  assert_eq!(label_text(&report, contents, 0), "mispeled");
+  assert_eq!(label_text(&report, contents, 1), "tpyo");
+
+  let contents = r#"
+[snippets]
+unused = "Unused"
+
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Question {{snippet missing}}"
+answer.answer = "1"
+"#;
+  let report = crate::test::report(contents);
+  assert_eq!(
+    messages(&report),
+    [
+      "Quiz has no snippet named `missing`",
+      "Snippet `unused` is never used"
+    ]
+  );
+  assert_eq!(label_text(&report, contents, 0), "{{snippet missing}}");
+}
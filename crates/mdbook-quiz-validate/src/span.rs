@@ -0,0 +1,94 @@
+//! Mapping between offsets in a decoded TOML string and offsets in the TOML source.
+
+/// Computes, for each byte offset in the decoded contents of the string literal `raw`,
+/// the corresponding byte offset in `raw`.
+///
+/// The returned vector has one more entry than the decoded string, so the end of a range
+/// can be mapped as well. Handles all four kinds of TOML strings, including escape sequences
+/// and line-ending backslashes in basic strings.
+pub(crate) fn string_offsets(raw: &str) -> Vec<usize> {
+  let (delim, multiline) = if raw.starts_with(r#"""""#) {
+    ('"', true)
+  } else if raw.starts_with("'''") {
+    ('\'', true)
+  } else if raw.starts_with('\'') {
+    ('\'', false)
+  } else {
+    ('"', false)
+  };
+
+  let mut idx = if multiline { 3 } else { 1 };
+  if multiline {
+    // A newline immediately following the opening delimiter is trimmed.
+    if raw[idx..].starts_with("\r\n") {
+      idx += 2;
+    } else if raw[idx..].starts_with('\n') {
+      idx += 1;
+    }
+  }
+
+  let close_len = if multiline { 3 } else { 1 };
+  let body_end = raw.len().saturating_sub(close_len).max(idx);
+  let body = &raw[..body_end];
+
+  let mut offsets = Vec::with_capacity(body_end - idx + 1);
+
+  while idx < body.len() {
+    let c = body[idx..].chars().next().unwrap();
+    if delim == '"' && c == '\\' {
+      let rest = &body[idx + 1..];
+      let next = rest.chars().next();
+      match next {
+        Some('u') | Some('U') => {
+          let n = if next == Some('u') { 4 } else { 8 };
+          let decoded = rest
+            .get(1..1 + n)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32)
+            .map_or(1, char::len_utf8);
+          offsets.extend(std::iter::repeat_n(idx, decoded));
+          idx += 2 + n;
+        }
+        Some(w) if multiline && w.is_whitespace() => {
+          // Line-ending backslash: skip all following whitespace, including newlines.
+          idx += 1;
+          while let Some(w) = body[idx..].chars().next() {
+            if !w.is_whitespace() {
+              break;
+            }
+            idx += w.len_utf8();
+          }
+        }
+        Some(_) => {
+          offsets.push(idx);
+          idx += 2;
+        }
+        None => idx += 1,
+      }
+    } else {
+      for i in 0..c.len_utf8() {
+        offsets.push(idx + i);
+      }
+      idx += c.len_utf8();
+    }
+  }
+
+  offsets.push(idx.min(body_end));
+  offsets
+}
+
+#[test]
+fn string_offsets_basic() {
+  let offsets = string_offsets(r#""a\nb""#);
+  // decoded: "a" "\n" "b"
+  assert_eq!(offsets, vec![1, 2, 4, 5]);
+}
+
+#[test]
+fn string_offsets_multiline() {
+  let offsets = string_offsets("\"\"\"\nab\n\"\"\"");
+  assert_eq!(offsets, vec![4, 5, 6, 7]);
+
+  let offsets = string_offsets("'''x\\y'''");
+  assert_eq!(offsets, vec![3, 4, 5, 6]);
+}
@@ -1,33 +1,320 @@
-use anyhow::{Context, anyhow};
-use std::{borrow::Cow, fs, path::Path, sync::OnceLock};
+use anyhow::{Context, bail};
+use miette::{LabeledSpan, MietteDiagnostic, SourceSpan};
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+  sync::{Arc, LazyLock, Mutex},
+};
 use zspell::{DictBuilder, Dictionary};
 
-static DICTIONARY: OnceLock<Dictionary> = OnceLock::new();
-static MORE_WORDS: OnceLock<String> = OnceLock::new();
+use crate::ValidationContext;
+
+/// The language of the dictionary that is embedded in this crate.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+#[derive(Default)]
+struct Registry {
+  dictionary_dirs: Vec<PathBuf>,
+  more_words: String,
+  spellcheckers: HashMap<String, Arc<Spellchecker>>,
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Default::default);
 
 /// Register a `.dic` file into the dictionary for spellchecking.
 ///
-/// Will return an error if called more than once.
+/// The words are added to the dictionary of every language. Can be called multiple times.
 pub fn register_more_words(path: &Path) -> anyhow::Result<()> {
   let contents =
     fs::read_to_string(path).with_context(|| format!("Failed to read path: {}", path.display()))?;
-  MORE_WORDS
-    .set(contents)
-    .map_err(|_| anyhow!("Attempted to register words twice"))
+  let mut registry = REGISTRY.lock().unwrap();
+  registry.more_words.push('\n');
+  registry.more_words.push_str(&contents);
+  registry.spellcheckers.clear();
+  Ok(())
+}
+
+/// Register a directory of Hunspell dictionaries for spellchecking.
+///
+/// A dictionary for a language `<lang>` is either a pair of files `<lang>/index.aff` and
+/// `<lang>/index.dic` (the layout of [wooorm/dictionaries](https://github.com/wooorm/dictionaries)),
+/// or a pair of files `<lang>.aff` and `<lang>.dic`. Directories registered later take precedence.
+pub fn register_dictionary_dir(path: &Path) -> anyhow::Result<()> {
+  REGISTRY.lock().unwrap().register_dictionary_dir(path)
+}
+
+pub(crate) struct Spellchecker {
+  dict: Dictionary,
+
+  /// Lowercased stems of every word in the dictionary, used for suggestions.
+  stems: Vec<String>,
+}
+
+/// Finds the `.aff` and `.dic` contents for `language` in `dirs`, falling back to the embedded
+/// English dictionary. A regional language like `en-US` also matches a dictionary for `en`.
+fn find_dictionary(dirs: &[PathBuf], language: &str) -> anyhow::Result<(String, String)> {
+  let mut candidates = vec![language];
+  if let Some((base, _)) = language.split_once(['-', '_']) {
+    candidates.push(base);
+  }
+
+  for candidate in &candidates {
+    for dir in dirs.iter().rev() {
+      let layouts = [
+        (
+          dir.join(candidate).join("index.aff"),
+          dir.join(candidate).join("index.dic"),
+        ),
+        (
+          dir.join(format!("{candidate}.aff")),
+          dir.join(format!("{candidate}.dic")),
+        ),
+      ];
+      for (aff, dic) in layouts {
+        if aff.exists() && dic.exists() {
+          let read = |path: &Path| {
+            fs::read_to_string(path)
+              .with_context(|| format!("Failed to read path: {}", path.display()))
+          };
+          return Ok((read(&aff)?, read(&dic)?));
+        }
+      }
+    }
+
+    if *candidate == DEFAULT_LANGUAGE {
+      return Ok((
+        include_str!("../dictionaries/en/index.aff").to_string(),
+        include_str!("../dictionaries/en/index.dic").to_string(),
+      ));
+    }
+  }
+
+  bail!(
+    "No spellcheck dictionary found for language `{language}`. \
+     Add a directory containing `{language}.aff` and `{language}.dic` via `spellcheck-dictionaries`."
+  )
 }
 
-pub(crate) fn dictionary() -> &'static Dictionary {
-  DICTIONARY.get_or_init(|| {
-    let mut base_dict = Cow::Borrowed(include_str!("../dictionaries/en/index.dic"));
+impl Registry {
+  fn register_dictionary_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+    anyhow::ensure!(
+      path.is_dir(),
+      "Dictionary directory does not exist: {}",
+      path.display()
+    );
+    self.dictionary_dirs.push(path.to_path_buf());
+    self.spellcheckers.clear();
+    Ok(())
+  }
 
-    if let Some(more_words) = MORE_WORDS.get() {
-      base_dict.to_mut().push_str(more_words);
+  /// Returns the spellchecker for `language`, loading and caching its dictionary if necessary.
+  fn spellchecker(&mut self, language: &str) -> anyhow::Result<Arc<Spellchecker>> {
+    if let Some(spellchecker) = self.spellcheckers.get(language) {
+      return Ok(Arc::clone(spellchecker));
     }
 
-    DictBuilder::new()
-      .config_str(include_str!("../dictionaries/en/index.aff"))
-      .dict_str(&base_dict)
+    let (aff, mut dic) = find_dictionary(&self.dictionary_dirs, language)?;
+    if !dic.ends_with('\n') {
+      dic.push('\n');
+    }
+    dic.push_str(&self.more_words);
+
+    let dict = DictBuilder::new()
+      .config_str(&aff)
+      .dict_str(&dic)
       .build()
-      .expect("failed to build dictionary!")
+      .map_err(|e| anyhow::anyhow!("Failed to build dictionary for `{language}`: {e}"))?;
+
+    // The first line of a .dic file is the number of entries.
+    let mut stems = dic
+      .lines()
+      .skip(1)
+      .filter_map(|line| line.split('/').next())
+      .map(|stem| stem.trim().to_lowercase())
+      .filter(|stem| !stem.is_empty())
+      .collect::<Vec<_>>();
+    stems.sort();
+    stems.dedup();
+
+    let spellchecker = Arc::new(Spellchecker { dict, stems });
+    self
+      .spellcheckers
+      .insert(language.to_string(), Arc::clone(&spellchecker));
+    Ok(spellchecker)
+  }
+}
+
+/// Returns the spellchecker for `language` from the global registry.
+pub(crate) fn spellchecker(language: &str) -> anyhow::Result<Arc<Spellchecker>> {
+  REGISTRY.lock().unwrap().spellchecker(language)
+}
+
+/// Loads the spellcheck dictionary for `language`, returning an error if none is available.
+///
+/// Useful for reporting a missing dictionary once before validating any quizzes.
+pub fn load_dictionary(language: &str) -> anyhow::Result<()> {
+  spellchecker(language).map(|_| ())
+}
+
+/// Returns true if a whitespace-delimited chunk of prose looks like a URL.
+fn is_url(chunk: &str) -> bool {
+  chunk.contains("://") || chunk.starts_with("www.")
+}
+
+/// Returns true if a whitespace-delimited chunk of prose looks like code,
+/// e.g. a path like `std::vec`, a call like `len()`, or a file name like `main.rs`.
+fn is_code_like(chunk: &str) -> bool {
+  const CODE_PATTERNS: [&str; 5] = ["::", "()", "->", "=>", "!("];
+  if CODE_PATTERNS.iter().any(|pat| chunk.contains(pat)) {
+    return true;
+  }
+  if chunk.contains(['_', '/', '<', '>', '=', '{', '}', '[', ']', '&', '$']) {
+    return true;
+  }
+  let chars = chunk.chars().collect::<Vec<_>>();
+  chars.windows(3).any(|w| {
+    w[1] == '.' && w[0].is_alphanumeric() && w[2].is_alphanumeric() && !w[2].is_uppercase()
   })
 }
+
+/// Returns true if a word looks like an identifier rather than prose,
+/// e.g. `camelCase`, `HashMap`, or `u32`.
+fn is_identifier_like(word: &str) -> bool {
+  word.chars().any(|c| c.is_ascii_digit()) || word.chars().skip(1).any(char::is_uppercase)
+}
+
+/// Edit distance between two strings, where transposing adjacent characters counts as one edit.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+  let mut prev2: Vec<usize> = vec![0; b.len() + 1];
+  let mut prev: Vec<usize> = (0..=b.len()).collect();
+  for i in 1..=a.len() {
+    let mut cur = vec![i; b.len() + 1];
+    for j in 1..=b.len() {
+      let cost = usize::from(a[i - 1] != b[j - 1]);
+      cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+      if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+        cur[j] = cur[j].min(prev2[j - 2] + 1);
+      }
+    }
+    prev2 = std::mem::replace(&mut prev, cur);
+  }
+  prev[b.len()]
+}
+
+impl Spellchecker {
+  /// Returns the byte offset and contents of each misspelled word in `text`.
+  ///
+  /// URLs, code-like chunks and identifier-like words are never reported.
+  pub fn check<'a>(&self, text: &'a str) -> Vec<(usize, &'a str)> {
+    let mut errors = Vec::new();
+    let mut offset = 0;
+    for chunk in text.split_inclusive(char::is_whitespace) {
+      let start = offset;
+      offset += chunk.len();
+      let chunk = chunk.trim_end();
+      if is_url(chunk) || is_code_like(chunk) {
+        continue;
+      }
+      let words = self
+        .dict
+        .check_indices(chunk)
+        .filter(|(_, s)| *s != "-")
+        .filter(|(_, s)| s.parse::<isize>().is_err())
+        .filter(|(_, s)| s.parse::<f32>().is_err())
+        .filter(|(_, s)| !is_identifier_like(s));
+      errors.extend(words.map(|(idx, word)| (start + idx, word)));
+    }
+    errors
+  }
+
+  /// Returns the closest dictionary word to `word`, if one is close enough to be a likely typo.
+  pub fn suggest(&self, word: &str) -> Option<&str> {
+    let lower = word.to_lowercase().chars().collect::<Vec<_>>();
+    let max_distance = if lower.len() <= 4 { 1 } else { 2 };
+    // Ties are broken in favor of words with the same length, which catches transpositions.
+    self
+      .stems
+      .iter()
+      .map(|stem| (stem, stem.chars().collect::<Vec<_>>()))
+      .filter(|(_, chars)| chars.len().abs_diff(lower.len()) <= max_distance)
+      .map(|(stem, chars)| {
+        let dist = edit_distance(&lower, &chars);
+        (dist, chars.len().abs_diff(lower.len()), stem)
+      })
+      .filter(|(dist, _, _)| *dist <= max_distance)
+      .min_by_key(|(dist, len_diff, _)| (*dist, *len_diff))
+      .map(|(_, _, stem)| stem.as_str())
+  }
+}
+
+/// Reports every misspelling recorded in `cx` as one diagnostic per distinct word,
+/// skipping words in `ignore` (compared case-insensitively).
+pub(crate) fn report_misspellings(
+  cx: &mut ValidationContext,
+  spellchecker: &Spellchecker,
+  ignore: &[String],
+) {
+  let misspellings = std::mem::take(&mut cx.misspellings);
+  for (word, spans) in misspellings {
+    if ignore.iter().any(|w| w.eq_ignore_ascii_case(&word)) {
+      continue;
+    }
+    let labels = spans
+      .into_iter()
+      .map(|span: SourceSpan| LabeledSpan::new_with_span(None, span));
+    let mut diagnostic =
+      MietteDiagnostic::new(format!("Spelling error: `{word}`")).with_labels(labels);
+    if let Some(suggestion) = spellchecker.suggest(&word) {
+      diagnostic = diagnostic.with_help(format!("did you mean `{suggestion}`?"));
+    }
+    cx.warning(diagnostic);
+  }
+}
+
+#[test]
+fn spellcheck_skips_code_and_urls() {
+  let spellchecker = spellchecker(DEFAULT_LANGUAGE).unwrap();
+  let text =
+    "Call HashMap::new or see https://doc.rust-lang.org/std and the wrold of serde_json in main.rs";
+  let errors = spellchecker.check(text);
+  assert_eq!(errors, vec![(text.find("wrold").unwrap(), "wrold")]);
+
+  assert!(
+    spellchecker
+      .check("A camelCase identifier and a u32")
+      .is_empty()
+  );
+}
+
+#[test]
+fn spellcheck_suggests_corrections() {
+  let spellchecker = spellchecker(DEFAULT_LANGUAGE).unwrap();
+  assert_eq!(spellchecker.suggest("wrold"), Some("world"));
+  assert_eq!(spellchecker.suggest("recieve"), Some("receive"));
+  assert_eq!(spellchecker.suggest("xqzvwk"), None);
+}
+
+#[test]
+fn spellcheck_languages() {
+  let dir = tempfile::tempdir().unwrap();
+  fs::write(dir.path().join("xx.aff"), "SET UTF-8\n").unwrap();
+  fs::write(dir.path().join("xx.dic"), "2\nhola\nmundo\n").unwrap();
+
+  // A local registry, so the dictionary is not visible to other tests.
+  let mut registry = Registry::default();
+  assert!(registry.spellchecker("xx").is_err());
+  registry.register_dictionary_dir(dir.path()).unwrap();
+  let xx = registry.spellchecker("xx-YY").unwrap();
+  assert!(xx.check("hola mundo").is_empty());
+  assert_eq!(xx.check("hello mundo"), vec![(0, "hello")]);
+
+  // Regional variants fall back to the embedded English dictionary.
+  assert!(
+    registry
+      .spellchecker("en-US")
+      .unwrap()
+      .check("hello world")
+      .is_empty()
+  );
+}
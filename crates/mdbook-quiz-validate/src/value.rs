@@ -0,0 +1,189 @@
+//! Quiz values that remember the span of their source, in any [`QuizFormat`](crate::QuizFormat).
+//!
+//! TOML is parsed into a [`toml_spanned_value::SpannedValue`], and JSON and YAML by the parsers in
+//! the `format` module. Both are converted into a [`SpannedValue`], so that validation doesn't
+//! depend on the format of the quiz.
+
+use std::{borrow::Borrow, collections::BTreeMap, ops::Range};
+
+use toml::value::Datetime;
+use toml_spanned_value::spanned_value as toml_spanned;
+
+/// A value along with the byte range of its source.
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+  span: Range<usize>,
+  value: T,
+}
+
+impl<T> Spanned<T> {
+  /// Creates a value whose source is at `span`.
+  pub fn new(span: Range<usize>, value: T) -> Self {
+    Spanned { span, value }
+  }
+
+  /// The byte offset where the source of the value starts.
+  pub fn start(&self) -> usize {
+    self.span.start
+  }
+
+  /// The byte offset where the source of the value ends.
+  pub fn end(&self) -> usize {
+    self.span.end
+  }
+
+  /// The value.
+  pub fn get_ref(&self) -> &T {
+    &self.value
+  }
+
+  /// The value, mutably.
+  pub fn get_mut(&mut self) -> &mut T {
+    &mut self.value
+  }
+
+  /// Discards the span and returns the value.
+  pub fn into_inner(self) -> T {
+    self.value
+  }
+}
+
+// Like `toml::Spanned`, spanned values are compared by their value, so that table keys can be
+// looked up by name.
+impl<T: PartialEq> PartialEq for Spanned<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.value == other.value
+  }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T: PartialOrd> PartialOrd for Spanned<T> {
+  fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    self.value.partial_cmp(&other.value)
+  }
+}
+
+impl<T: Ord> Ord for Spanned<T> {
+  fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    self.value.cmp(&other.value)
+  }
+}
+
+impl Borrow<str> for Spanned<String> {
+  fn borrow(&self) -> &str {
+    &self.value
+  }
+}
+
+/// A quiz value with the span of its source.
+pub type SpannedValue = Spanned<ValueKind>;
+
+/// The keys and values of a table.
+pub type Table = BTreeMap<Spanned<String>, SpannedValue>;
+
+/// A value that can be written in TOML.
+///
+/// JSON and YAML values without a TOML equivalent, like `null`, are not represented.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueKind {
+  /// A string, after any escapes are decoded.
+  String(String),
+  /// An integer.
+  Integer(i64),
+  /// A float.
+  Float(f64),
+  /// A boolean.
+  Boolean(bool),
+  /// A TOML date or time.
+  Datetime(Datetime),
+  /// An array, or a JSON or YAML sequence.
+  Array(Vec<SpannedValue>),
+  /// A table, or a JSON object or YAML mapping.
+  Table(Table),
+}
+
+impl ValueKind {
+  /// Returns the value of `key` if this is a table.
+  pub fn get(&self, key: &str) -> Option<&SpannedValue> {
+    self.as_table()?.get(key)
+  }
+
+  /// Returns the string if this is a string.
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      ValueKind::String(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  /// Returns the elements if this is an array.
+  pub fn as_array(&self) -> Option<&Vec<SpannedValue>> {
+    match self {
+      ValueKind::Array(array) => Some(array),
+      _ => None,
+    }
+  }
+
+  /// Returns the entries if this is a table.
+  pub fn as_table(&self) -> Option<&Table> {
+    match self {
+      ValueKind::Table(table) => Some(table),
+      _ => None,
+    }
+  }
+
+  /// Returns the entries mutably if this is a table.
+  pub fn as_table_mut(&mut self) -> Option<&mut Table> {
+    match self {
+      ValueKind::Table(table) => Some(table),
+      _ => None,
+    }
+  }
+
+  /// Converts the value into a [`toml::Value`], without its spans.
+  pub fn to_toml(&self) -> toml::Value {
+    match self {
+      ValueKind::String(s) => toml::Value::String(s.clone()),
+      ValueKind::Integer(n) => toml::Value::Integer(*n),
+      ValueKind::Float(n) => toml::Value::Float(*n),
+      ValueKind::Boolean(b) => toml::Value::Boolean(*b),
+      ValueKind::Datetime(dt) => toml::Value::Datetime(dt.clone()),
+      ValueKind::Array(array) => {
+        toml::Value::Array(array.iter().map(|v| v.get_ref().to_toml()).collect())
+      }
+      ValueKind::Table(table) => toml::Value::Table(
+        table
+          .iter()
+          .map(|(k, v)| (k.get_ref().clone(), v.get_ref().to_toml()))
+          .collect(),
+      ),
+    }
+  }
+}
+
+impl From<toml_spanned::SpannedValue> for SpannedValue {
+  fn from(value: toml_spanned::SpannedValue) -> Self {
+    let (start, end) = value.span();
+    let kind = match value.into_inner() {
+      toml_spanned::ValueKind::String(s) => ValueKind::String(s),
+      toml_spanned::ValueKind::Integer(n) => ValueKind::Integer(n),
+      toml_spanned::ValueKind::Float(n) => ValueKind::Float(n),
+      toml_spanned::ValueKind::Boolean(b) => ValueKind::Boolean(b),
+      toml_spanned::ValueKind::Datetime(dt) => ValueKind::Datetime(dt),
+      toml_spanned::ValueKind::Array(array) => {
+        ValueKind::Array(array.into_iter().map(SpannedValue::from).collect())
+      }
+      toml_spanned::ValueKind::Table(table) => ValueKind::Table(
+        table
+          .into_iter()
+          .map(|(key, value)| {
+            let (start, end) = key.span();
+            (Spanned::new(start..end, key.into_inner()), value.into())
+          })
+          .collect(),
+      ),
+    };
+    Spanned::new(start..end, kind)
+  }
+}
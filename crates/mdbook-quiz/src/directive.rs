@@ -0,0 +1,146 @@
+//! Parses the arguments of a `{{#quiz <path> <options>}}` directive,
+//! and the options of an inline ```` ```quiz <options> ```` block.
+
+use anyhow::{Context, Result, bail};
+
+/// Settings that can be overridden for a single embedded quiz,
+/// e.g. `{{#quiz ../q.toml fullscreen=false initial-text="Checkpoint"}}`.
+///
+/// Unset options fall back to the `[preprocessor.quiz]` settings.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct EmbedOptions {
+  pub fullscreen: Option<bool>,
+  pub cache_answers: Option<bool>,
+  pub default_language: Option<String>,
+  pub show_bug_reporter: Option<bool>,
+  pub initial_text: Option<String>,
+}
+
+const OPTIONS: [&str; 5] = [
+  "fullscreen",
+  "cache-answers",
+  "default-language",
+  "show-bug-reporter",
+  "initial-text",
+];
+
+/// The arguments of a quiz directive.
+#[derive(Debug, PartialEq, Eq)]
+pub struct QuizDirective {
+  /// The path to the quiz file, relative to the chapter.
+  pub path: String,
+  pub options: EmbedOptions,
+}
+
+/// Splits `args` on whitespace, except within double quotes.
+/// Quotes are removed, and `\"` and `\\` are unescaped within them.
+fn tokenize(args: &str) -> Result<Vec<String>> {
+  let mut tokens = Vec::new();
+  let mut token: Option<String> = None;
+  let mut chars = args.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      c if c.is_whitespace() => tokens.extend(token.take()),
+      '"' => {
+        let token = token.get_or_insert_with(String::new);
+        loop {
+          match chars.next() {
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+              Some(c @ ('"' | '\\')) => token.push(c),
+              Some(c) => {
+                token.push('\\');
+                token.push(c);
+              }
+              None => bail!("Unterminated string"),
+            },
+            Some(c) => token.push(c),
+            None => bail!("Unterminated string"),
+          }
+        }
+      }
+      c => token.get_or_insert_with(String::new).push(c),
+    }
+  }
+  tokens.extend(token);
+  Ok(tokens)
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+  match value {
+    "true" => Ok(true),
+    "false" => Ok(false),
+    _ => bail!("Option `{key}` must be `true` or `false`, not `{value}`"),
+  }
+}
+
+impl EmbedOptions {
+  /// Parses a whitespace-separated list of options like `key=value`.
+  pub fn parse(args: &str) -> Result<Self> {
+    Self::from_tokens(tokenize(args)?)
+  }
+
+  fn from_tokens(tokens: impl IntoIterator<Item = String>) -> Result<Self> {
+    let mut options = EmbedOptions::default();
+    for token in tokens {
+      let Some((key, value)) = token.split_once('=') else {
+        bail!("Expected an option like `key=value`, found `{token}`");
+      };
+      match key {
+        "fullscreen" => options.fullscreen = Some(parse_bool(key, value)?),
+        "cache-answers" => options.cache_answers = Some(parse_bool(key, value)?),
+        "default-language" => options.default_language = Some(value.to_string()),
+        "show-bug-reporter" => options.show_bug_reporter = Some(parse_bool(key, value)?),
+        "initial-text" => options.initial_text = Some(value.to_string()),
+        _ => bail!(
+          "Unknown option `{key}`. The valid options are: {}",
+          OPTIONS.join(", ")
+        ),
+      }
+    }
+    Ok(options)
+  }
+}
+
+impl QuizDirective {
+  /// Parses the arguments of a directive, i.e. everything after `{{#quiz`.
+  pub fn parse(args: &str) -> Result<Self> {
+    let mut tokens = tokenize(args)?.into_iter();
+    let path = tokens.next().context("Missing the path to a quiz file")?;
+    let options = EmbedOptions::from_tokens(tokens)?;
+    Ok(QuizDirective { path, options })
+  }
+}
+
+#[test]
+fn parse_directive() -> Result<()> {
+  assert_eq!(
+    QuizDirective::parse("../quiz.toml")?,
+    QuizDirective {
+      path: "../quiz.toml".into(),
+      options: EmbedOptions::default()
+    }
+  );
+
+  let directive = QuizDirective::parse(
+    r#"../q.toml  fullscreen=false initial-text="Checkpoint \"1\"" cache-answers=true"#,
+  )?;
+  assert_eq!(directive.path, "../q.toml");
+  assert_eq!(
+    directive.options,
+    EmbedOptions {
+      fullscreen: Some(false),
+      cache_answers: Some(true),
+      initial_text: Some("Checkpoint \"1\"".into()),
+      ..Default::default()
+    }
+  );
+
+  let err = |args: &str| QuizDirective::parse(args).unwrap_err().to_string();
+  assert!(err("q.toml fullscren=true").contains("Unknown option `fullscren`"));
+  assert!(err("q.toml fullscreen=yes").contains("must be `true` or `false`"));
+  assert!(err("q.toml fullscreen").contains("key=value"));
+  assert!(err(r#"q.toml initial-text="Quiz"#).contains("Unterminated"));
+
+  Ok(())
+}
@@ -0,0 +1,336 @@
+//! Runs the quiz preprocessor on a book.
+//!
+//! The driver of mdbook-preprocessor-utils is private, always writes the assets into the book's
+//! source directory, and only passes a chapter's directory to the preprocessor. So this driver
+//! runs the same [`SimplePreprocessor`] hooks, and only differs in that:
+//!
+//! * Read-only builds never write to the book's source directory. The assets are instead written
+//!   into the HTML output by the `mdbook-quiz assets` renderer.
+//! * The preprocessor gets the path of each chapter, so inline quizzes can be named after it.
+
+use std::{
+  env, fs, io,
+  path::{Path, PathBuf},
+  process,
+};
+
+use anyhow::{Context, Result};
+use mdbook_preprocessor_utils::{
+  SimplePreprocessor,
+  mdbook::{
+    BookItem,
+    book::Book,
+    preprocess::{CmdPreprocessor, Preprocessor, PreprocessorContext},
+    renderer::RenderContext,
+  },
+  rayon::prelude::*,
+};
+
+use crate::{QuizPreprocessor, all_assets};
+
+/// The name of the renderer that copies assets for read-only builds.
+pub const ASSETS_RENDERER: &str = "quiz";
+
+pub struct QuizDriver;
+
+impl QuizDriver {
+  fn process_chapter(
+    sp: &QuizPreprocessor,
+    src_dir: &Path,
+    chapter_path: &Path,
+    content: &mut String,
+  ) -> Result<()> {
+    let chapter_dir = chapter_path.parent().unwrap();
+    let mut replacements = sp.chapter_replacements(chapter_dir, Some(chapter_path), content)?;
+    if replacements.is_empty() {
+      return Ok(());
+    }
+
+    replacements.sort_by_key(|(range, _)| range.start);
+    for (range, html) in replacements.into_iter().rev() {
+      content.replace_range(range, &html);
+    }
+
+    // If a chapter is located at foo/bar/the_chapter.md, then the generated source files
+    // will be at foo/bar/the_chapter.html. So they need to reference preprocessor files
+    // at ../../<preprocessor>/embed.js, i.e. we generate the right number of "..".
+    let chapter_rel_path = chapter_dir.strip_prefix(src_dir).unwrap();
+    let depth = chapter_rel_path.components().count();
+    let prefix = vec![".."; depth].into_iter().collect::<PathBuf>();
+
+    // Ensure there's space between existing markdown and inserted HTML
+    content.push_str("\n\n");
+
+    for asset in sp.linked_assets() {
+      let asset_rel = prefix.join(QuizPreprocessor::name()).join(asset.name);
+      let asset_str = asset_rel.display().to_string();
+      let link = match &*asset_rel.extension().unwrap().to_string_lossy() {
+        "js" => format!(r#"<script type="text/javascript" src="{asset_str}"></script>"#),
+        "mjs" => format!(r#"<script type="module" src="{asset_str}"></script>"#),
+        "css" => format!(r#"<link rel="stylesheet" type="text/css" href="{asset_str}">"#),
+        _ => continue,
+      };
+      content.push_str(&link);
+    }
+
+    Ok(())
+  }
+}
+
+impl Preprocessor for QuizDriver {
+  fn name(&self) -> &str {
+    QuizPreprocessor::name()
+  }
+
+  fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+    let src_dir = ctx.root.join(&ctx.config.book.src);
+    let sp = QuizPreprocessor::build(ctx)?;
+
+    // Rather than copying directly to the build directory, we instead copy to the book source
+    // since mdBook will clean the build-dir after preprocessing. See mdBook#1087 for more.
+    if !sp.config.read_only {
+      write_assets(&src_dir)?;
+    }
+
+    fn collect_chapters<'a>(
+      src_dir: &Path,
+      chapters: &mut Vec<(PathBuf, &'a mut String)>,
+      items: impl IntoIterator<Item = &'a mut BookItem>,
+    ) {
+      for item in items {
+        if let BookItem::Chapter(chapter) = item
+          && let Some(path) = &chapter.path
+        {
+          chapters.push((src_dir.join(path), &mut chapter.content));
+          collect_chapters(src_dir, chapters, &mut chapter.sub_items);
+        }
+      }
+    }
+
+    let mut chapters = Vec::new();
+    collect_chapters(&src_dir, &mut chapters, &mut book.sections);
+
+    chapters
+      .into_par_iter()
+      .map(|(chapter_path, content)| Self::process_chapter(&sp, &src_dir, &chapter_path, content))
+      .collect::<Result<Vec<_>>>()?;
+
+    // Unlike `SimplePreprocessor::finish`, a lockfile that fails to be written fails the build.
+    sp.write_lockfile()?;
+
+    Ok(book)
+  }
+
+  fn supports_renderer(&self, renderer: &str) -> bool {
+    renderer != ASSETS_RENDERER
+  }
+}
+
+/// Writes the frontend assets into `<dir>/quiz`.
+fn write_assets(dir: &Path) -> Result<()> {
+  let dst_dir = dir.join(QuizPreprocessor::name());
+  fs::create_dir_all(&dst_dir)?;
+  for asset in all_assets() {
+    fs::write(dst_dir.join(asset.name), asset.contents)?;
+  }
+  Ok(())
+}
+
+/// Runs the preprocessor as mdBook invokes it, like `mdbook_preprocessor_utils::main`.
+///
+/// `supports <renderer>` exits with whether the renderer is supported. Any other arguments
+/// are ignored, and the book is read from stdin.
+pub fn main() {
+  let args = env::args().skip(1).collect::<Vec<_>>();
+  if let [command, renderer, ..] = args.as_slice()
+    && command == "supports"
+  {
+    process::exit(if QuizDriver.supports_renderer(renderer) {
+      0
+    } else {
+      1
+    });
+  }
+
+  if let Err(e) = preprocess() {
+    eprintln!("{e:#}");
+    process::exit(1);
+  }
+}
+
+/// Runs the preprocessor on a book read from stdin, and writes the processed book to stdout.
+fn preprocess() -> Result<()> {
+  env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+  let (ctx, book) = CmdPreprocessor::parse_input(io::stdin())?;
+
+  let book_version = semver::Version::parse(&ctx.mdbook_version)?;
+  let version_req = semver::VersionReq::parse(mdbook_preprocessor_utils::mdbook::MDBOOK_VERSION)?;
+  if !version_req.matches(&book_version) {
+    log::warn!(
+      "The {} plugin was built against version {} of mdbook, but we're being called from version {}",
+      QuizPreprocessor::name(),
+      mdbook_preprocessor_utils::mdbook::MDBOOK_VERSION,
+      ctx.mdbook_version
+    );
+  }
+
+  let processed_book = QuizDriver.run(&ctx, book)?;
+  serde_json::to_writer(io::stdout(), &processed_book)?;
+  Ok(())
+}
+
+/// Runs as a renderer, copying the assets into the output of the HTML renderer.
+pub fn render_assets() -> Result<()> {
+  let ctx = RenderContext::from_json(io::stdin())?;
+
+  // With more than one renderer, mdBook gives each one a subdirectory of the build dir.
+  let html_dir = ctx.root.join(&ctx.config.build.build_dir).join("html");
+  anyhow::ensure!(
+    html_dir.is_dir(),
+    "HTML output not found at {}. The [output.html] renderer must run before [output.{ASSETS_RENDERER}].",
+    html_dir.display()
+  );
+  write_assets(&html_dir)
+    .with_context(|| format!("Failed to write quiz assets to {}", html_dir.display()))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use mdbook_preprocessor_utils::testing::MdbookTestHarness;
+  use std::collections::HashSet;
+
+  const QUIZ: &str = r#"
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Hello world"
+answer.answer = "No"
+"#;
+
+  /// Runs the driver on the book at `root`, returning the content of each chapter.
+  fn run_all(root: &Path, config: serde_json::Value) -> Result<Vec<String>> {
+    let input = serde_json::json!([
+      {
+        "root": root.display().to_string(),
+        "config": { "preprocessor": { "quiz": config } },
+        "renderer": "html",
+        "mdbook_version": mdbook_preprocessor_utils::mdbook::MDBOOK_VERSION,
+      },
+      mdbook_preprocessor_utils::mdbook::book::load_book(root.join("src"), &Default::default())?,
+    ]);
+    let (ctx, book) = CmdPreprocessor::parse_input(serde_json::to_string(&input)?.as_bytes())?;
+    let book = QuizDriver.run(&ctx, book)?;
+    let contents = book
+      .sections
+      .into_iter()
+      .filter_map(|item| match item {
+        BookItem::Chapter(chapter) => Some(chapter.content),
+        _ => None,
+      })
+      .collect();
+    Ok(contents)
+  }
+
+  /// Runs the driver on the book at `root`, returning the content of the first chapter.
+  fn run(root: &Path, config: serde_json::Value) -> Result<String> {
+    Ok(run_all(root, config)?.remove(0))
+  }
+
+  #[test]
+  fn read_only_build() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    let quiz_path = root.join("quiz.toml");
+    fs::write(&quiz_path, QUIZ)?;
+    fs::write(
+      root.join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml}}",
+    )?;
+
+    let content = run(root, serde_json::json!({ "read-only": true }))?;
+    assert!(content.contains("quiz/quiz-embed.iife.js"));
+    assert!(!root.join("src").join("quiz").exists());
+    assert_eq!(fs::read_to_string(&quiz_path)?, QUIZ);
+
+    Ok(())
+  }
+
+  #[test]
+  fn lockfile_write_error() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    fs::write(root.join("quiz.toml"), QUIZ)?;
+    fs::write(
+      root.join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml}}",
+    )?;
+
+    let config = serde_json::json!({ "id-lockfile": "missing/quiz-ids.lock" });
+    let err = run(root, config).unwrap_err();
+    assert!(format!("{err:#}").contains("quiz-ids.lock"), "{err:#}");
+
+    Ok(())
+  }
+
+  #[test]
+  fn inline_quiz() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    let chapter_path = root.join("src").join("chapter_1.md");
+    let chapter = format!("# Chapter 1\n\n```quiz initial-text=\"Check\"{QUIZ}```\n\nThe end.\n");
+    fs::write(&chapter_path, &chapter)?;
+
+    let content = run(root, serde_json::json!({ "id-strategy": "path" }))?;
+    assert!(content.contains("quiz-placeholder"));
+    assert!(content.contains("src/chapter_1-inline-1"));
+    assert!(!content.contains("```quiz"));
+    assert!(content.contains("The end."));
+
+    // The ID is written back into the chapter, and nothing else changes.
+    let new_chapter = fs::read_to_string(&chapter_path)?;
+    assert!(new_chapter.contains("[[questions]]\nid = "));
+    let id_line = new_chapter
+      .lines()
+      .find(|l| l.starts_with("id = "))
+      .unwrap();
+    assert_eq!(new_chapter.replace(&format!("{id_line}\n"), ""), chapter);
+
+    Ok(())
+  }
+
+  #[test]
+  fn shared_quiz_ids() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    let quiz_path = root.join("quiz.toml");
+    fs::write(&quiz_path, QUIZ.repeat(3))?;
+    // Chapters are processed in parallel, so they all add IDs to the quiz at once.
+    let mut summary = String::from("# Summary\n\n");
+    for i in 1..=8 {
+      let chapter = format!("chapter_{i}.md");
+      fs::write(root.join("src").join(&chapter), "{{#quiz ../quiz.toml}}")?;
+      summary.push_str(&format!("- [Chapter {i}](./{chapter})\n"));
+    }
+    fs::write(root.join("src").join("SUMMARY.md"), summary)?;
+
+    let pool = mdbook_preprocessor_utils::rayon::ThreadPoolBuilder::new()
+      .num_threads(8)
+      .build()?;
+    let contents = pool.install(|| run_all(root, serde_json::json!({})))?;
+    let quiz = toml::from_str::<toml::Value>(&fs::read_to_string(&quiz_path)?)?;
+    let ids = quiz["questions"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|q| q["id"].as_str().unwrap())
+      .collect::<HashSet<_>>();
+    assert_eq!(ids.len(), 3);
+    assert_eq!(contents.len(), 8);
+    for content in &contents {
+      assert!(ids.iter().all(|id| content.contains(id)));
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,116 @@
+//! Applies fixes suggested by the validator to quiz files, preserving formatting and comments.
+
+use anyhow::{Context, Result};
+use mdbook_quiz_validate::{Fix, PathSegment};
+use toml_edit::{Array, Document, InlineTable, Item, Value};
+
+fn to_edit_value(value: &toml::Value) -> Value {
+  match value {
+    toml::Value::String(s) => Value::from(s.as_str()),
+    toml::Value::Integer(n) => Value::from(*n),
+    toml::Value::Float(n) => Value::from(*n),
+    toml::Value::Boolean(b) => Value::from(*b),
+    toml::Value::Datetime(dt) => dt
+      .to_string()
+      .parse::<Value>()
+      .unwrap_or_else(|_| Value::from(dt.to_string())),
+    toml::Value::Array(values) => Value::Array(values.iter().map(to_edit_value).collect::<Array>()),
+    toml::Value::Table(table) => Value::InlineTable(
+      table
+        .iter()
+        .map(|(k, v)| (k.clone(), to_edit_value(v)))
+        .collect::<InlineTable>(),
+    ),
+  }
+}
+
+/// Applies a single fix to `doc`.
+pub fn apply_fix(doc: &mut Document, fix: &Fix) -> Result<()> {
+  let (key, parents) = match fix.path.split_last() {
+    Some((PathSegment::Key(key), parents)) => (key, parents),
+    _ => anyhow::bail!("Invalid fix path: {:?}", fix.path),
+  };
+
+  let path_str = || fix.path.iter().map(|s| s.to_string()).collect::<String>();
+  let mut item = doc.as_item_mut();
+  for segment in parents {
+    let next = match segment {
+      PathSegment::Key(k) => item.get_mut(k.as_str()),
+      PathSegment::Index(i) => item.get_mut(*i),
+    };
+    item = next.with_context(|| format!("Fix path does not exist: {}", path_str()))?;
+  }
+
+  let table = item
+    .as_table_like_mut()
+    .with_context(|| format!("Fix path is not in a table: {}", path_str()))?;
+  match &fix.value {
+    Some(value) => {
+      let mut new_value = to_edit_value(value);
+      if let Some(old_value) = table.get(key).and_then(Item::as_value) {
+        *new_value.decor_mut() = old_value.decor().clone();
+      }
+      table.insert(key, Item::Value(new_value));
+    }
+    None => {
+      table.remove(key);
+    }
+  }
+
+  Ok(())
+}
+
+/// Applies each fix to the TOML `contents`, returning the new contents.
+pub fn apply_fixes<'a>(contents: &str, fixes: impl IntoIterator<Item = &'a Fix>) -> Result<String> {
+  let mut doc = contents.parse::<Document>()?;
+  for fix in fixes {
+    apply_fix(&mut doc, fix)?;
+  }
+  Ok(doc.to_string())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn apply_fixes_preserves_formatting() -> Result<()> {
+    let contents = r#"
+[[questions]]
+type = "Tracing" # a comment
+answer.doesCompile = false # another comment
+answer.stdout = "Hello"
+"#;
+    let key = |s: &str| PathSegment::Key(s.into());
+    let path = |k: &str| {
+      vec![
+        key("questions"),
+        PathSegment::Index(0),
+        key("answer"),
+        key(k),
+      ]
+    };
+    let fixes = [
+      Fix {
+        title: "set doesCompile to true".into(),
+        path: path("doesCompile"),
+        value: Some(toml::Value::Boolean(true)),
+      },
+      Fix {
+        title: "remove stdout".into(),
+        path: path("stdout"),
+        value: None,
+      },
+    ];
+    let fixed = apply_fixes(contents, &fixes)?;
+    assert_eq!(
+      fixed,
+      r#"
+[[questions]]
+type = "Tracing" # a comment
+answer.doesCompile = true # another comment
+"#
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,19 @@
+//! A canonical formatter for quiz files, see [`mdbook_quiz_schema::format_document`].
+
+use anyhow::{Context, Result};
+
+/// Formats the contents of a quiz file.
+pub fn format(contents: &str) -> Result<String> {
+  let output = mdbook_quiz_schema::format_document(contents)?;
+
+  // Reordering keys must never change what the quiz means.
+  let before = toml::from_str::<toml::Value>(contents)?;
+  let after = toml::from_str::<toml::Value>(&output)
+    .with_context(|| format!("Formatter produced invalid TOML:\n{output}"))?;
+  anyhow::ensure!(
+    before == after,
+    "Formatter changed the contents of the quiz:\n{output}"
+  );
+
+  Ok(output)
+}
@@ -0,0 +1,114 @@
+//! Finds quizzes written inline in a chapter as fenced code blocks, e.g.
+//!
+//! ````markdown
+//! ```quiz fullscreen=false
+//! [[questions]]
+//! type = "ShortAnswer"
+//! ...
+//! ```
+//! ````
+
+use std::ops::Range;
+
+use anyhow::{Context, Result, bail};
+
+use crate::directive::EmbedOptions;
+
+/// A quiz in a fenced code block with the info string `quiz`.
+#[derive(Debug)]
+pub struct InlineQuiz {
+  /// The range of the whole block, including its fences.
+  pub range: Range<usize>,
+
+  /// The range of the quiz's TOML, between the fences.
+  pub body: Range<usize>,
+
+  /// Options that follow `quiz` in the info string.
+  pub options: EmbedOptions,
+}
+
+/// If `line` opens or closes a fenced code block, returns the fence character,
+/// the length of the fence, and the rest of the line.
+fn parse_fence(line: &str) -> Option<(char, usize, &str)> {
+  let trimmed = line.trim_start_matches(' ');
+  if line.len() - trimmed.len() > 3 {
+    return None;
+  }
+  let fence_char = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+  let len = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+  (len >= 3).then(|| (fence_char, len, &trimmed[len..]))
+}
+
+/// Returns all the inline quizzes in `content`, in order.
+pub fn find_inline_quizzes(content: &str) -> Result<Vec<InlineQuiz>> {
+  let mut quizzes = Vec::new();
+  let mut lines = content.split_inclusive('\n').scan(0, |offset, line| {
+    let start = *offset;
+    *offset += line.len();
+    Some((start, line))
+  });
+
+  while let Some((start, line)) = lines.next() {
+    let Some((fence_char, fence_len, info)) = parse_fence(line) else {
+      continue;
+    };
+    let info = info.trim();
+    let is_quiz = info.split_whitespace().next() == Some("quiz");
+
+    // Skip to the end of the block, which is the end of the content if it's unclosed.
+    let body_start = start + line.len();
+    let close = lines.find(|(_, line)| {
+      parse_fence(line)
+        .is_some_and(|(c, len, rest)| c == fence_char && len >= fence_len && rest.trim().is_empty())
+    });
+
+    if !is_quiz {
+      continue;
+    }
+    let Some((close_start, close_line)) = close else {
+      let line_number = content[..start].lines().count() + 1;
+      bail!("Unclosed quiz block starting on line {line_number}");
+    };
+
+    let options = EmbedOptions::parse(&info["quiz".len()..])
+      .with_context(|| format!("Invalid quiz block options: {info}"))?;
+    quizzes.push(InlineQuiz {
+      range: start..close_start + close_line.len(),
+      body: body_start..close_start,
+      options,
+    });
+  }
+
+  Ok(quizzes)
+}
+
+#[test]
+fn find_inline() -> Result<()> {
+  let content = r#"# Chapter
+
+```rust
+// not a quiz
+```quiz
+```
+
+````quiz initial-text="Check"
+[[questions]]
+```
+````
+
+~~~quiz
+a = 1
+~~~
+"#;
+  let quizzes = find_inline_quizzes(content)?;
+  assert_eq!(quizzes.len(), 2);
+  assert_eq!(&content[quizzes[0].body.clone()], "[[questions]]\n```\n");
+  assert!(content[quizzes[0].range.clone()].starts_with("````quiz"));
+  assert!(content[quizzes[0].range.clone()].ends_with("````\n"));
+  assert_eq!(quizzes[0].options.initial_text.as_deref(), Some("Check"));
+  assert_eq!(&content[quizzes[1].body.clone()], "a = 1\n");
+
+  assert!(find_inline_quizzes("```quiz\na = 1\n").is_err());
+  assert!(find_inline_quizzes("```quiz nope=1\n```").is_err());
+  Ok(())
+}
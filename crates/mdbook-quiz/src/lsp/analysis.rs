@@ -0,0 +1,404 @@
+//! Editor features computed from the text of a quiz file.
+//!
+//! Completions and hovers work line-by-line on the raw text, since a file being edited
+//! often does not parse as TOML.
+
+use std::ops::Range;
+
+use lsp_types::{
+  CompletionItem, CompletionItemKind, Documentation, MarkupContent, MarkupKind, Position,
+};
+use mdbook_quiz_validate::QuizFormat;
+
+use super::schema::Schema;
+
+/// Converts between byte offsets and LSP positions, which count UTF-16 code units.
+pub struct LineIndex<'a> {
+  text: &'a str,
+  line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+  pub fn new(text: &'a str) -> Self {
+    let line_starts = std::iter::once(0)
+      .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    LineIndex { text, line_starts }
+  }
+
+  pub fn position(&self, offset: usize) -> Position {
+    let offset = offset.min(self.text.len());
+    let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+    let line_start = self.line_starts[line];
+    let character = self.text[line_start..offset]
+      .chars()
+      .map(char::len_utf16)
+      .sum::<usize>();
+    Position::new(line as u32, character as u32)
+  }
+
+  pub fn offset(&self, position: Position) -> usize {
+    let Some(line_start) = self.line_starts.get(position.line as usize) else {
+      return self.text.len();
+    };
+    let line = &self.text[*line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    let mut units = 0;
+    for (idx, c) in line.char_indices() {
+      if units >= position.character as usize {
+        return line_start + idx;
+      }
+      units += c.len_utf16();
+    }
+    line_start + line.len()
+  }
+
+  pub fn range(&self, range: Range<usize>) -> lsp_types::Range {
+    lsp_types::Range::new(self.position(range.start), self.position(range.end))
+  }
+}
+
+/// Parses a table header like `[[questions]]` or `[questions.prompt]` into its keys.
+fn parse_header(line: &str) -> Option<Vec<&str>> {
+  let line = line.trim();
+  let inner = line
+    .strip_prefix("[[")
+    .and_then(|l| l.strip_suffix("]]"))
+    .or_else(|| line.strip_prefix('[').and_then(|l| l.strip_suffix(']')))?;
+  Some(split_key(inner))
+}
+
+/// Splits a dotted key like `prompt.distractors` into its segments.
+fn split_key(key: &str) -> Vec<&str> {
+  key
+    .split('.')
+    .map(|segment| segment.trim().trim_matches('"'))
+    .collect()
+}
+
+/// Returns true if `line` begins a new table.
+fn is_header(line: &str) -> bool {
+  line.trim_start().starts_with('[')
+}
+
+/// The table and question that a position in a quiz file belongs to.
+struct Context<'a> {
+  /// The keys of the enclosing table, e.g. `["questions", "prompt"]`.
+  header: Vec<&'a str>,
+
+  /// The `type` of the enclosing question, if known.
+  question_type: Option<&'a str>,
+}
+
+fn context(text: &str, line: usize) -> Context<'_> {
+  let lines = text.lines().collect::<Vec<_>>();
+  let before = &lines[..line.min(lines.len())];
+
+  let header = before
+    .iter()
+    .rev()
+    .find_map(|line| parse_header(line))
+    .unwrap_or_default();
+
+  let question_type = question_start(&lines, line).and_then(|start| {
+    lines[start + 1..]
+      .iter()
+      .take_while(|line| !is_header(line))
+      .find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "type").then(|| value.trim().trim_matches(['"', '\'']))
+      })
+  });
+
+  Context {
+    header,
+    question_type,
+  }
+}
+
+/// Returns the line of the `[[questions]]` header of the question containing `line`.
+fn question_start(lines: &[&str], line: usize) -> Option<usize> {
+  let end = (line + 1).min(lines.len());
+  for idx in (0..end).rev() {
+    // Subtables like `[questions.prompt]` belong to the preceding question.
+    match parse_header(lines[idx]) {
+      Some(keys) if keys == ["questions"] && lines[idx].trim_start().starts_with("[[") => {
+        return Some(idx);
+      }
+      Some(keys) if keys[0] != "questions" => return None,
+      _ => {}
+    }
+  }
+  None
+}
+
+fn documentation(schema: &Schema, schemas: &[&serde_json::Value]) -> Option<Documentation> {
+  schema.description(schemas).map(|description| {
+    Documentation::MarkupContent(MarkupContent {
+      kind: MarkupKind::Markdown,
+      value: description.to_string(),
+    })
+  })
+}
+
+/// Returns completions of keys and values at `offset` in `text`.
+pub fn completions(schema: &Schema, text: &str, offset: usize) -> Vec<CompletionItem> {
+  let index = LineIndex::new(text);
+  let line = index.position(offset).line as usize;
+  let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+  let prefix = &text[line_start..offset];
+  let cx = context(text, line);
+
+  if let Some((key, value)) = prefix.split_once('=') {
+    // Completing a value, e.g. `type = "`.
+    let value = value.trim_start();
+    let quoted = value.starts_with(['"', '\'']);
+    if !value
+      .trim_start_matches(['"', '\''])
+      .chars()
+      .all(char::is_alphanumeric)
+    {
+      return Vec::new();
+    }
+
+    let mut path = cx.header.clone();
+    path.extend(split_key(key.trim()));
+    // When editing the `type` of a question, offer every type rather than only the current one.
+    let question_type = cx.question_type.filter(|_| path != ["questions", "type"]);
+    let schemas = schema.lookup(&path, question_type);
+
+    let mut items = schema
+      .enum_values(&schemas)
+      .into_iter()
+      .map(|value| CompletionItem {
+        label: value.to_string(),
+        kind: Some(CompletionItemKind::ENUM_MEMBER),
+        insert_text: (!quoted).then(|| format!("\"{value}\"")),
+        ..Default::default()
+      })
+      .collect::<Vec<_>>();
+    if !quoted && schema.is_boolean(&schemas) {
+      items.extend(["true", "false"].map(|value| CompletionItem {
+        label: value.to_string(),
+        kind: Some(CompletionItemKind::VALUE),
+        ..Default::default()
+      }));
+    }
+    return items;
+  }
+
+  // Completing a key, e.g. `prompt.`, or a table header, e.g. `[[`.
+  let trimmed = prefix.trim_start();
+  let (base, key) = match trimmed.strip_prefix('[') {
+    Some(header) => (Vec::new(), header.trim_start_matches('[')),
+    None => (cx.header.clone(), trimmed),
+  };
+  if !key
+    .chars()
+    .all(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | '"'))
+  {
+    return Vec::new();
+  }
+
+  let mut path = base;
+  let segments = split_key(key);
+  path.extend(&segments[..segments.len() - 1]);
+  let schemas = schema.lookup(&path, cx.question_type);
+  schema
+    .properties(&schemas, cx.question_type)
+    .into_iter()
+    .map(|property| CompletionItem {
+      label: property.name.to_string(),
+      kind: Some(CompletionItemKind::FIELD),
+      detail: Some(schema.type_name(property.schema)),
+      documentation: documentation(schema, &[property.schema]),
+      ..Default::default()
+    })
+    .collect()
+}
+
+/// Returns the hover docs for the key at `offset` in `text`, and the byte range of that key.
+pub fn hover(schema: &Schema, text: &str, offset: usize) -> Option<(String, Range<usize>)> {
+  let index = LineIndex::new(text);
+  let line_idx = index.position(offset).line as usize;
+  let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+  let line = text[line_start..].lines().next().unwrap_or("");
+  let column = offset - line_start;
+
+  // Find the dotted key on this line, and the offset where it starts.
+  let (keys_start, keys, mut path) = if is_header(line) {
+    // The key is between the opening brackets and the first closing bracket after them,
+    // so brackets in a trailing comment are ignored.
+    let start = line.len() - line.trim_start().trim_start_matches('[').len();
+    let end = start + line[start..].find(']')?;
+    (start, &line[start..end], Vec::new())
+  } else {
+    let end = line.find('=')?;
+    let cx = context(text, line_idx);
+    (0, &line[..end], cx.header)
+  };
+  if column < keys_start || column > keys_start + keys.len() {
+    return None;
+  }
+
+  let mut segment_start = keys_start;
+  for segment in keys.split('.') {
+    let segment_end = segment_start + segment.len();
+    path.push(segment.trim().trim_matches('"'));
+    if column <= segment_end {
+      let question_type = context(text, line_idx).question_type;
+      let schemas = schema.lookup(&path, question_type);
+      let first = schemas.first()?;
+      let name = path.last().unwrap();
+      let mut contents = format!("**{name}**: `{}`", schema.type_name(first));
+      if let Some(description) = schema.description(&schemas) {
+        contents.push_str("\n\n");
+        contents.push_str(description);
+      }
+      let trimmed_start = segment_start + (segment.len() - segment.trim_start().len());
+      let range = line_start + trimmed_start..line_start + segment_start + segment.trim_end().len();
+      return Some((contents, range));
+    }
+    segment_start = segment_end + 1;
+  }
+  None
+}
+
+/// If `offset` is on the value of a question's `multipart` key, returns the byte range
+/// of the corresponding entry in the top-level `multipart` table.
+pub fn definition(text: &str, offset: usize) -> Option<Range<usize>> {
+  let root = QuizFormat::Toml.parse_value(text).ok()?;
+  let questions = root.get_ref().get("questions")?.get_ref().as_array()?;
+  let part = questions.iter().find_map(|question| {
+    let part = question.get_ref().get("multipart")?;
+    (part.start() <= offset && offset <= part.end()).then(|| part.get_ref().as_str())?
+  })?;
+
+  let multipart = root.get_ref().get("multipart")?.get_ref().as_table()?;
+  multipart
+    .keys()
+    .find(|key| key.get_ref() == part)
+    .map(|key| key.start()..key.end())
+}
+
+/// If `offset` is inside a question without an `id`, returns the offset where the `id`
+/// key should be inserted.
+pub fn missing_id(text: &str, offset: usize) -> Option<usize> {
+  let lines = text.lines().collect::<Vec<_>>();
+  let line = LineIndex::new(text).position(offset).line as usize;
+  let start = question_start(&lines, line)?;
+  let has_id = lines[start + 1..]
+    .iter()
+    .take_while(|line| !is_header(line))
+    .any(|line| {
+      line
+        .split_once('=')
+        .is_some_and(|(key, _)| key.trim() == "id")
+    });
+  if has_id {
+    return None;
+  }
+
+  let header_end = text
+    .split_inclusive('\n')
+    .take(start + 1)
+    .map(str::len)
+    .sum::<usize>();
+  Some(header_end)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const QUIZ: &str = r#"[[questions]]
+type = "Tracing"
+prompt.program = "fn main() {}"
+answer.doesCompile = true
+multipart = "a"
+
+[multipart]
+a = "Some context"
+"#;
+
+  fn labels(items: Vec<CompletionItem>) -> Vec<String> {
+    items.into_iter().map(|item| item.label).collect()
+  }
+
+  #[test]
+  fn line_index() {
+    let text = "a = \"é\"\nb = 1";
+    let index = LineIndex::new(text);
+    assert_eq!(index.position(0), Position::new(0, 0));
+    assert_eq!(index.position(text.find('b').unwrap()), Position::new(1, 0));
+    let end = text.find("\"\n").unwrap();
+    assert_eq!(index.position(end), Position::new(0, 6));
+    assert_eq!(index.offset(Position::new(0, 6)), end);
+    assert_eq!(index.offset(Position::new(1, 100)), text.len());
+  }
+
+  #[test]
+  fn complete_keys_and_values() {
+    let schema = Schema::new();
+
+    let text = "[[questions]]\ntype = \"Tracing\"\nanswer.";
+    let items = labels(completions(&schema, text, text.len()));
+    assert_eq!(items, vec!["doesCompile", "lineNumber", "stdout"]);
+
+    let text = "[[questions]]\ntype = \"";
+    let items = labels(completions(&schema, text, text.len()));
+    assert_eq!(items, vec!["ShortAnswer", "Tracing", "MultipleChoice"]);
+
+    let text = "[[questions]]\ntype = \"Tracing\"";
+    let items = labels(completions(&schema, text, text.len() - "Tracing\"".len()));
+    assert_eq!(items.len(), 3);
+
+    let text = "[[questions]]\n";
+    let items = labels(completions(&schema, text, text.len()));
+    assert!(items.contains(&"prompt".to_string()));
+
+    let text = "[[";
+    let items = labels(completions(&schema, text, text.len()));
+    assert!(items.contains(&"questions".to_string()));
+  }
+
+  #[test]
+  fn hover_keys() {
+    let schema = Schema::new();
+    let offset = QUIZ.find("doesCompile").unwrap() + 2;
+    let (contents, range) = hover(&schema, QUIZ, offset).unwrap();
+    assert!(contents.starts_with("**doesCompile**: `boolean`"));
+    assert_eq!(&QUIZ[range], "doesCompile");
+
+    let offset = QUIZ.find("questions").unwrap();
+    let (contents, _) = hover(&schema, QUIZ, offset).unwrap();
+    assert!(contents.contains("The questions of the quiz."));
+
+    // Brackets after the header, or in a line of Markdown that looks like a header.
+    let text = "[[questions]] # see [docs]\n";
+    let (contents, range) = hover(&schema, text, 4).unwrap();
+    assert!(contents.contains("The questions of the quiz."));
+    assert_eq!(&text[range], "questions");
+    assert!(hover(&schema, text, text.find("docs").unwrap()).is_none());
+    assert!(hover(&schema, "[a](b) and [c]\n", 12).is_none());
+  }
+
+  #[test]
+  fn multipart_definition() {
+    let offset = QUIZ.find("\"a\"").unwrap() + 1;
+    let range = definition(QUIZ, offset).unwrap();
+    assert_eq!(range.start, QUIZ.find("a = ").unwrap());
+  }
+
+  #[test]
+  fn missing_id_insertion() {
+    let offset = QUIZ.find("prompt").unwrap();
+    assert_eq!(missing_id(QUIZ, offset), Some("[[questions]]\n".len()));
+
+    let text = "[[questions]]\nid = \"foo\"\ntype = \"Tracing\"\n";
+    assert_eq!(missing_id(text, text.len() - 1), None);
+
+    let offset = QUIZ.find("Some context").unwrap();
+    assert_eq!(missing_id(QUIZ, offset), None);
+  }
+}
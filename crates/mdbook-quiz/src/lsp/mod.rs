@@ -0,0 +1,408 @@
+//! A language server for quiz files, run via `mdbook-quiz lsp`.
+//!
+//! Publishes the validator's diagnostics, and provides completions and hover docs
+//! derived from the schema in [`mdbook_quiz_schema`].
+
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+  thread,
+  time::Duration,
+};
+
+use anyhow::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+  CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability,
+  CompletionOptions, CompletionParams, CompletionResponse, DiagnosticRelatedInformation,
+  DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+  HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf,
+  PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+  TextEdit, Url, WorkspaceEdit,
+  notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+    Notification as _, PublishDiagnostics,
+  },
+  request::{CodeActionRequest, Completion, GotoDefinition, HoverRequest, Request as _},
+};
+use mdbook_preprocessor_utils::mdbook::Config;
+use mdbook_quiz_validate::{Fix, QuizDiagnostic, Validated, ValidationOptions};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::QuizConfig;
+use analysis::LineIndex;
+use schema::Schema;
+
+mod analysis;
+mod schema;
+
+/// How long to wait after an edit before validating, since validating compiles programs.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+type Shared<T> = Arc<Mutex<HashMap<Url, T>>>;
+
+struct Server {
+  sender: crossbeam_channel::Sender<Message>,
+  schema: Schema,
+  documents: HashMap<Url, String>,
+
+  /// The number of edits to each document, used to discard outdated validations.
+  versions: Shared<u64>,
+
+  /// The fixes for the most recent diagnostics of each document.
+  fixes: Shared<Vec<(lsp_types::Range, Fix)>>,
+
+  /// The validation options for each book, keyed by the book's root directory.
+  options: HashMap<PathBuf, ValidationOptions>,
+}
+
+/// Runs the language server over stdin and stdout until the client shuts it down.
+pub fn run() -> Result<()> {
+  let (connection, io_threads) = Connection::stdio();
+
+  let capabilities = ServerCapabilities {
+    text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+    completion_provider: Some(CompletionOptions {
+      trigger_characters: Some(vec![".".into(), "\"".into(), "[".into()]),
+      ..Default::default()
+    }),
+    hover_provider: Some(HoverProviderCapability::Simple(true)),
+    definition_provider: Some(OneOf::Left(true)),
+    code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+    ..Default::default()
+  };
+  connection.initialize(serde_json::to_value(capabilities)?)?;
+
+  let mut server = Server {
+    sender: connection.sender.clone(),
+    schema: Schema::new(),
+    documents: HashMap::new(),
+    versions: Default::default(),
+    fixes: Default::default(),
+    options: HashMap::new(),
+  };
+  server.main_loop(&connection)?;
+
+  // The writer thread exits once every sender has been dropped.
+  drop(server);
+  drop(connection);
+  io_threads.join()?;
+  Ok(())
+}
+
+/// Converts a validator diagnostic into an LSP diagnostic, placed at its first label.
+fn to_lsp_diagnostic(
+  diagnostic: &QuizDiagnostic,
+  uri: &Url,
+  index: &LineIndex,
+) -> lsp_types::Diagnostic {
+  let error = &diagnostic.error;
+  let ranges = error
+    .labels()
+    .into_iter()
+    .flatten()
+    .map(|label| index.range(label.offset()..label.offset() + label.len()))
+    .collect::<Vec<_>>();
+
+  let mut message = error.to_string();
+  if let Some(help) = error.help() {
+    message.push_str(&format!("\nhelp: {help}"));
+  }
+
+  let related_information = (ranges.len() > 1).then(|| {
+    ranges[1..]
+      .iter()
+      .map(|range| DiagnosticRelatedInformation {
+        location: Location::new(uri.clone(), *range),
+        message: "also here".into(),
+      })
+      .collect()
+  });
+
+  lsp_types::Diagnostic {
+    range: ranges.first().copied().unwrap_or_default(),
+    severity: Some(if diagnostic.fatal {
+      DiagnosticSeverity::ERROR
+    } else {
+      DiagnosticSeverity::WARNING
+    }),
+    source: Some("mdbook-quiz".into()),
+    message,
+    related_information,
+    ..Default::default()
+  }
+}
+
+/// Reads the validation options from the `book.toml` in `root`.
+fn load_options(root: &Path) -> Result<ValidationOptions> {
+  let config = Config::from_disk(root.join("book.toml"))?;
+  let mut quiz_config = QuizConfig::new(&config, root)?;
+  quiz_config.load_spellcheck()?;
+  Ok(quiz_config.validation_options(None))
+}
+
+fn file_path(uri: &Url) -> PathBuf {
+  uri
+    .to_file_path()
+    .unwrap_or_else(|_| PathBuf::from(uri.path()))
+}
+
+impl Server {
+  fn main_loop(&mut self, connection: &Connection) -> Result<()> {
+    for message in &connection.receiver {
+      match message {
+        Message::Request(request) => {
+          if connection.handle_shutdown(&request)? {
+            return Ok(());
+          }
+          let response = self.handle_request(request);
+          connection.sender.send(Message::Response(response))?;
+        }
+        Message::Notification(notification) => self.handle_notification(notification)?,
+        Message::Response(_) => {}
+      }
+    }
+    Ok(())
+  }
+
+  fn handle_request(&self, request: Request) -> Response {
+    let result = match request.method.as_str() {
+      Completion::METHOD => self.dispatch::<Completion>(request.params, Self::completion),
+      HoverRequest::METHOD => self.dispatch::<HoverRequest>(request.params, Self::hover),
+      GotoDefinition::METHOD => self.dispatch::<GotoDefinition>(request.params, Self::definition),
+      CodeActionRequest::METHOD => {
+        self.dispatch::<CodeActionRequest>(request.params, Self::code_action)
+      }
+      method => {
+        return Response::new_err(
+          request.id,
+          ErrorCode::MethodNotFound as i32,
+          format!("Unknown method: {method}"),
+        );
+      }
+    };
+    match result {
+      Ok(value) => Response::new_ok(request.id, value),
+      Err(e) => Response::new_err(request.id, ErrorCode::InternalError as i32, e.to_string()),
+    }
+  }
+
+  fn dispatch<R: lsp_types::request::Request>(
+    &self,
+    params: Value,
+    handler: impl FnOnce(&Self, R::Params) -> R::Result,
+  ) -> Result<Value> {
+    let params = serde_json::from_value(params)?;
+    Ok(serde_json::to_value(handler(self, params))?)
+  }
+
+  fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+    match notification.method.as_str() {
+      DidOpenTextDocument::METHOD => {
+        let params = notification
+          .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)?;
+        let uri = params.text_document.uri;
+        self
+          .documents
+          .insert(uri.clone(), params.text_document.text);
+        self.validate(uri);
+      }
+      DidChangeTextDocument::METHOD => {
+        let params = notification
+          .extract::<lsp_types::DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)?;
+        let uri = params.text_document.uri;
+        if let Some(change) = params.content_changes.into_iter().last() {
+          self.documents.insert(uri.clone(), change.text);
+          self.validate(uri);
+        }
+      }
+      DidSaveTextDocument::METHOD => {
+        let params = notification
+          .extract::<lsp_types::DidSaveTextDocumentParams>(DidSaveTextDocument::METHOD)?;
+        self.validate(params.text_document.uri);
+      }
+      DidCloseTextDocument::METHOD => {
+        let params = notification
+          .extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)?;
+        let uri = params.text_document.uri;
+        self.documents.remove(&uri);
+        self.versions.lock().unwrap().remove(&uri);
+        self.fixes.lock().unwrap().remove(&uri);
+        self.publish(PublishDiagnosticsParams::new(uri, Vec::new(), None))?;
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+
+  fn publish(&self, params: PublishDiagnosticsParams) -> Result<()> {
+    let notification = Notification::new(PublishDiagnostics::METHOD.into(), params);
+    self.sender.send(Message::Notification(notification))?;
+    Ok(())
+  }
+
+  /// Returns the validation options for a document, read from the `book.toml` of its book.
+  fn options(&mut self, uri: &Url) -> ValidationOptions {
+    let path = file_path(uri);
+    let Some(root) = path.ancestors().find(|dir| dir.join("book.toml").exists()) else {
+      return ValidationOptions::default();
+    };
+    self
+      .options
+      .entry(root.to_path_buf())
+      .or_insert_with(|| {
+        load_options(root).unwrap_or_else(|e| {
+          eprintln!("Failed to load book config from {}: {e:?}", root.display());
+          ValidationOptions::default()
+        })
+      })
+      .clone()
+  }
+
+  /// Validates a document in the background, and publishes its diagnostics
+  /// unless the document is edited in the meantime.
+  fn validate(&mut self, uri: Url) {
+    let Some(text) = self.documents.get(&uri).cloned() else {
+      return;
+    };
+    let options = self.options(&uri);
+    let version = {
+      let mut versions = self.versions.lock().unwrap();
+      let version = versions.entry(uri.clone()).or_default();
+      *version += 1;
+      *version
+    };
+
+    let sender = self.sender.clone();
+    let versions = Arc::clone(&self.versions);
+    let fixes = Arc::clone(&self.fixes);
+    thread::spawn(move || {
+      let is_current = || versions.lock().unwrap().get(&uri) == Some(&version);
+      thread::sleep(DEBOUNCE);
+      if !is_current() {
+        return;
+      }
+
+      let path = file_path(&uri);
+      let report = match mdbook_quiz_validate::validate_report(
+        &path,
+        &text,
+        &Validated::default(),
+        &options,
+      ) {
+        Ok(Some(report)) => report,
+        Ok(None) => return,
+        Err(e) => {
+          eprintln!("Failed to validate {}: {e:?}", path.display());
+          return;
+        }
+      };
+      if !is_current() {
+        return;
+      }
+
+      let index = LineIndex::new(&text);
+      let mut document_fixes = Vec::new();
+      let diagnostics = report
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+          let lsp_diagnostic = to_lsp_diagnostic(diagnostic, &uri, &index);
+          if let Some(fix) = &diagnostic.fix {
+            document_fixes.push((lsp_diagnostic.range, fix.clone()));
+          }
+          lsp_diagnostic
+        })
+        .collect();
+      fixes.lock().unwrap().insert(uri.clone(), document_fixes);
+
+      let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+      let notification = Notification::new(PublishDiagnostics::METHOD.into(), params);
+      let _ = sender.send(Message::Notification(notification));
+    });
+  }
+
+  fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+    let position = params.text_document_position;
+    let text = self.documents.get(&position.text_document.uri)?;
+    let offset = LineIndex::new(text).offset(position.position);
+    let items = analysis::completions(&self.schema, text, offset);
+    Some(CompletionResponse::Array(items))
+  }
+
+  fn hover(&self, params: HoverParams) -> Option<Hover> {
+    let position = params.text_document_position_params;
+    let text = self.documents.get(&position.text_document.uri)?;
+    let index = LineIndex::new(text);
+    let offset = index.offset(position.position);
+    let (contents, range) = analysis::hover(&self.schema, text, offset)?;
+    Some(Hover {
+      contents: HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: contents,
+      }),
+      range: Some(index.range(range)),
+    })
+  }
+
+  fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+    let position = params.text_document_position_params;
+    let uri = position.text_document.uri;
+    let text = self.documents.get(&uri)?;
+    let index = LineIndex::new(text);
+    let range = analysis::definition(text, index.offset(position.position))?;
+    Some(GotoDefinitionResponse::Scalar(Location::new(
+      uri,
+      index.range(range),
+    )))
+  }
+
+  fn code_action(&self, params: CodeActionParams) -> Option<Vec<CodeActionOrCommand>> {
+    let uri = params.text_document.uri;
+    let text = self.documents.get(&uri)?;
+    let index = LineIndex::new(text);
+    let edit = |edits: Vec<TextEdit>| WorkspaceEdit {
+      changes: Some(HashMap::from([(uri.clone(), edits)])),
+      ..Default::default()
+    };
+
+    let mut actions = Vec::new();
+
+    let overlaps =
+      |range: &lsp_types::Range| range.start <= params.range.end && params.range.start <= range.end;
+    let fixes = self.fixes.lock().unwrap();
+    for (range, fix) in fixes.get(&uri).into_iter().flatten() {
+      if !overlaps(range) {
+        continue;
+      }
+      let Ok(new_text) = crate::fix::apply_fixes(text, [fix]) else {
+        continue;
+      };
+      let whole_document = index.range(0..text.len());
+      actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Fix: {}", fix.title),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(edit(vec![TextEdit::new(whole_document, new_text)])),
+        ..Default::default()
+      }));
+    }
+
+    if let Some(insert_at) = analysis::missing_id(text, index.offset(params.range.start)) {
+      let position = index.position(insert_at);
+      let id = Uuid::new_v4();
+      actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+        title: "Insert question id".into(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(edit(vec![TextEdit::new(
+          lsp_types::Range::new(position, position),
+          format!("id = \"{id}\"\n"),
+        )])),
+        ..Default::default()
+      }));
+    }
+
+    Some(actions)
+  }
+}
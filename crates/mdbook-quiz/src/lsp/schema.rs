@@ -0,0 +1,232 @@
+//! Queries over the JSON schema of [`Quiz`], used for completions and hover docs.
+
+use mdbook_quiz_schema::Quiz;
+use serde_json::Value;
+
+pub struct Schema {
+  root: Value,
+}
+
+/// A property of an object in the schema.
+pub struct Property<'a> {
+  pub name: &'a str,
+  pub schema: &'a Value,
+}
+
+impl Schema {
+  pub fn new() -> Self {
+    let root = serde_json::to_value(schemars::schema_for!(Quiz)).unwrap();
+    Schema { root }
+  }
+
+  /// Follows a `$ref` to its definition.
+  fn resolve<'a>(&'a self, schema: &'a Value) -> &'a Value {
+    match schema.get("$ref").and_then(Value::as_str) {
+      Some(reference) => {
+        let name = reference.trim_start_matches("#/definitions/");
+        self
+          .root
+          .get("definitions")
+          .and_then(|defs| defs.get(name))
+          .map_or(schema, |def| self.resolve(def))
+      }
+      None => schema,
+    }
+  }
+
+  /// Returns the schemas that make up `schema`, following references and `allOf`/`oneOf`/`anyOf`.
+  ///
+  /// If `question_type` names one of the variants of a `oneOf`, then the other variants are skipped.
+  fn parts<'a>(&'a self, schema: &'a Value, question_type: Option<&str>) -> Vec<&'a Value> {
+    let has_tag = |subschema: &Value, question_type: &str| {
+      subschema
+        .pointer("/properties/type/enum")
+        .and_then(Value::as_array)
+        .is_some_and(|tags| tags.iter().any(|tag| tag == question_type))
+    };
+
+    let schema = self.resolve(schema);
+    let mut parts = vec![schema];
+    for key in ["allOf", "oneOf", "anyOf"] {
+      let Some(subschemas) = schema.get(key).and_then(Value::as_array) else {
+        continue;
+      };
+      let variant =
+        question_type.filter(|ty| key == "oneOf" && subschemas.iter().any(|s| has_tag(s, ty)));
+      for subschema in subschemas {
+        if let Some(variant) = variant
+          && !has_tag(subschema, variant)
+        {
+          continue;
+        }
+        parts.extend(self.parts(subschema, question_type));
+      }
+    }
+    parts
+  }
+
+  /// Returns the schemas of the elements of `schema` if it is an array, or else `schema` itself.
+  fn elements<'a>(&'a self, schema: &'a Value) -> &'a Value {
+    let resolved = self.resolve(schema);
+    resolved.get("items").unwrap_or(schema)
+  }
+
+  /// Returns all the properties of the objects (or arrays of objects) described by `schemas`.
+  pub fn properties<'a>(
+    &'a self,
+    schemas: &[&'a Value],
+    question_type: Option<&str>,
+  ) -> Vec<Property<'a>> {
+    let mut properties: Vec<Property<'a>> = Vec::new();
+    for schema in schemas {
+      for part in self.parts(self.elements(schema), question_type) {
+        let Some(props) = part.get("properties").and_then(Value::as_object) else {
+          continue;
+        };
+        for (name, schema) in props {
+          if !properties.iter().any(|p| p.name == name) {
+            properties.push(Property { name, schema });
+          }
+        }
+      }
+    }
+    properties
+  }
+
+  /// Returns the schemas of the value at `path`, where each segment is a key.
+  /// Arrays are traversed implicitly, so `["questions", "prompt"]` is the prompt of a question.
+  ///
+  /// There can be multiple schemas if the question type is not known.
+  pub fn lookup<'a>(&'a self, path: &[&str], question_type: Option<&str>) -> Vec<&'a Value> {
+    let mut schemas = vec![&self.root];
+    for key in path {
+      let mut next = Vec::new();
+      for schema in schemas {
+        let element = self.elements(schema);
+        for part in self.parts(element, question_type) {
+          let child = part
+            .get("properties")
+            .and_then(|props| props.get(*key))
+            .or_else(|| part.get("additionalProperties").filter(|s| s.is_object()));
+          if let Some(child) = child {
+            next.push(child);
+          }
+        }
+      }
+      schemas = next;
+    }
+    schemas
+  }
+
+  /// Returns the possible values of an enum in `schemas`, such as the question types.
+  pub fn enum_values<'a>(&'a self, schemas: &[&'a Value]) -> Vec<&'a str> {
+    let mut values = Vec::new();
+    for schema in schemas {
+      for part in self.parts(schema, None) {
+        for value in part
+          .get("enum")
+          .and_then(Value::as_array)
+          .into_iter()
+          .flatten()
+        {
+          if let Some(value) = value.as_str()
+            && !values.contains(&value)
+          {
+            values.push(value);
+          }
+        }
+      }
+    }
+    values
+  }
+
+  /// Returns true if any of `schemas` is a boolean.
+  pub fn is_boolean(&self, schemas: &[&Value]) -> bool {
+    schemas
+      .iter()
+      .any(|schema| self.type_name(schema) == "boolean")
+  }
+
+  /// Returns the first doc comment found in `schemas`.
+  pub fn description<'a>(&'a self, schemas: &[&'a Value]) -> Option<&'a str> {
+    schemas.iter().find_map(|schema| {
+      schema
+        .get("description")
+        .or_else(|| self.resolve(schema).get("description"))
+        .or_else(|| {
+          self
+            .parts(schema, None)
+            .into_iter()
+            .find_map(|part| part.get("description"))
+        })
+        .and_then(Value::as_str)
+    })
+  }
+
+  /// Returns a short human-readable name for the type of `schema`, e.g. `Markdown` or `string[]`.
+  pub fn type_name(&self, schema: &Value) -> String {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+      return reference.trim_start_matches("#/definitions/").to_string();
+    }
+    if let Some([inner]) = schema
+      .get("allOf")
+      .and_then(Value::as_array)
+      .map(Vec::as_slice)
+    {
+      return self.type_name(inner);
+    }
+
+    let types = match schema.get("type") {
+      Some(Value::String(ty)) => vec![ty.as_str()],
+      Some(Value::Array(tys)) => tys
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|ty| *ty != "null")
+        .collect(),
+      _ => vec![],
+    };
+    match types.as_slice() {
+      ["array"] => match schema.get("items") {
+        Some(items) => format!("{}[]", self.type_name(items)),
+        None => "array".to_string(),
+      },
+      ["object"] => match schema.get("additionalProperties") {
+        Some(values) if values.is_object() => format!("Record<string, {}>", self.type_name(values)),
+        _ => "object".to_string(),
+      },
+      [] => "any".to_string(),
+      types => types.join(" | "),
+    }
+  }
+}
+
+#[test]
+fn schema_lookup() {
+  let schema = Schema::new();
+
+  let questions = schema.lookup(&["questions"], None);
+  let keys = schema
+    .properties(&questions, None)
+    .into_iter()
+    .map(|p| p.name)
+    .collect::<Vec<_>>();
+  for key in ["id", "type", "prompt", "answer", "context", "multipart"] {
+    assert!(keys.contains(&key), "missing {key} in {keys:?}");
+  }
+
+  let types = schema.enum_values(&schema.lookup(&["questions", "type"], None));
+  assert_eq!(types.len(), 3);
+  assert!(types.contains(&"MultipleChoice"));
+
+  let prompt = schema.lookup(&["questions", "prompt"], Some("Tracing"));
+  let keys = schema
+    .properties(&prompt, Some("Tracing"))
+    .into_iter()
+    .map(|p| p.name)
+    .collect::<Vec<_>>();
+  assert_eq!(keys, vec!["program", "programFile"]);
+
+  let does_compile = schema.lookup(&["questions", "answer", "doesCompile"], Some("Tracing"));
+  assert!(schema.is_boolean(&does_compile));
+  assert!(schema.description(&does_compile).is_some());
+}
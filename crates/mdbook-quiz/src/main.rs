@@ -1,17 +1,34 @@
 use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, error::ErrorKind};
 use mdbook_preprocessor_utils::{
-  Asset, HtmlElementBuilder, SimplePreprocessor, mdbook::preprocess::PreprocessorContext,
+  Asset, HtmlElementBuilder, SimplePreprocessor,
+  mdbook::{Config, preprocess::PreprocessorContext},
 };
 
-use mdbook_quiz_validate::Validated;
+use directive::{EmbedOptions, QuizDirective};
+use inline::InlineQuiz;
+use mdbook_quiz_schema::{PoolSampling, QuestionPool, QuizInclude, QuizSettings};
+use mdbook_quiz_validate::{IdRegistry, QuizFormat, Validated, ValidationOptions};
+use pools::LearnerPool;
 use regex::Regex;
 use std::{
+  collections::HashSet,
   env, fs,
+  ops::Range,
   path::{Path, PathBuf},
-  sync::OnceLock,
+  sync::{Mutex, OnceLock},
 };
 use uuid::Uuid;
 
+mod directive;
+mod driver;
+mod fix;
+mod fmt;
+mod inline;
+mod lsp;
+mod migrate;
+mod pools;
+
 mdbook_preprocessor_utils::asset_generator!("../js/");
 
 const FRONTEND_ASSETS: [Asset; 2] = [make_asset!("quiz-embed.iife.js"), make_asset!("style.css")];
@@ -32,6 +49,75 @@ const SOURCE_MAP_ASSETS: [Asset; 1] = [
 #[cfg(not(feature = "source-map"))]
 const SOURCE_MAP_ASSETS: [Asset; 0] = [];
 
+/// How question IDs are generated for questions that don't have one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum IdStrategy {
+  /// A random UUIDv4, written back to the quiz file.
+  #[default]
+  Random,
+
+  /// A UUIDv5 derived from the path of the quiz file and the index of the question.
+  Path,
+
+  /// A UUIDv5 derived from the path of the quiz file and the content of the question.
+  Content,
+
+  /// Never generate IDs, and fail the build if a question is missing one.
+  Never,
+}
+
+impl IdStrategy {
+  fn parse(s: &str) -> Result<Self> {
+    Ok(match s {
+      "random" => IdStrategy::Random,
+      "path" => IdStrategy::Path,
+      "content" => IdStrategy::Content,
+      "never" => IdStrategy::Never,
+      _ => anyhow::bail!(
+        "Invalid id-strategy: {s:?}, expected one of \"random\", \"path\", \"content\", or \"never\""
+      ),
+    })
+  }
+
+  /// Returns a new ID for the question at `index` in the quiz at `quiz_path` (relative to the book root).
+  ///
+  /// If a deterministic ID is already taken, then it is derived again with an `attempt` above 0.
+  fn generate(
+    self,
+    quiz_path: &str,
+    index: usize,
+    attempt: usize,
+    question: &toml::Value,
+  ) -> Result<Option<Uuid>> {
+    let namespace = || {
+      Uuid::new_v5(
+        &Uuid::NAMESPACE_URL,
+        b"https://github.com/cognitive-engineering-lab/mdbook-quiz",
+      )
+    };
+    let suffix = match attempt {
+      0 => String::new(),
+      n => format!("#{n}"),
+    };
+    Ok(match self {
+      IdStrategy::Random => Some(Uuid::new_v4()),
+      IdStrategy::Path => Some(Uuid::new_v5(
+        &namespace(),
+        format!("{quiz_path}#{index}{suffix}").as_bytes(),
+      )),
+      IdStrategy::Content => {
+        let content = toml::to_string(question)
+          .with_context(|| format!("Failed to serialize question {} in {quiz_path}", index + 1))?;
+        Some(Uuid::new_v5(
+          &namespace(),
+          format!("{quiz_path}\n{content}{suffix}").as_bytes(),
+        ))
+      }
+      IdStrategy::Never => None,
+    })
+  }
+}
+
 struct QuizConfig {
   /// If true, then a quiz will take up the web page's full screen during use.
   fullscreen: Option<bool>,
@@ -40,10 +126,20 @@ struct QuizConfig {
   /// and displayed to them upon revisiting a completed quiz.
   cache_answers: Option<bool>,
 
+  /// If false, then users can't retry the questions they answered incorrectly.
+  allow_retry: Option<bool>,
+
+  /// If true, then the questions of a quiz are shown in a random order for each user.
+  shuffle: Option<bool>,
+
+  /// The percentage of points that a user needs to pass a quiz.
+  passing_score: Option<u32>,
+
   /// Sets the default language for syntax highlighting.
   default_language: Option<String>,
 
-  /// If true, then run a spellchecker on all Markdown strings.
+  /// If true, then run a spellchecker on all Markdown strings,
+  /// and on comments and string literals in Tracing programs.
   ///
   /// You can add a custom dictionary via the `more-words` key.
   spellcheck: Option<bool>,
@@ -51,18 +147,161 @@ struct QuizConfig {
   /// Path to a .dic file containing words to include in the spellcheck dictionary.
   more_words: Option<PathBuf>,
 
+  /// The language of the spellcheck dictionary, set by the `spellcheck-language` key.
+  ///
+  /// Otherwise, [`QuizConfig::load_spellcheck`] sets it to the book's `language`
+  /// if a dictionary exists for it.
+  spellcheck_language: Option<String>,
+
+  /// The book's `language`.
+  book_language: Option<String>,
+
+  /// Path to a directory of Hunspell dictionaries (`.aff`/`.dic` pairs) for other languages.
+  spellcheck_dictionaries: Option<PathBuf>,
+
   /// If true (and telemetry is enabled) then allow users to report bugs in the frontend.
   show_bug_reporter: Option<bool>,
 
   /// The text to initially show before a user starts a quiz. "Quiz" by default.
   initial_text: Option<String>,
 
+  /// Path to a lockfile, e.g. `quiz-ids.lock`, that records every question ID across builds.
+  ///
+  /// Can be shared between books to catch IDs that are copied from one book to another.
+  id_lockfile: Option<PathBuf>,
+
+  /// How IDs are generated for questions without one. Set by the `id-strategy` key.
+  id_strategy: IdStrategy,
+
+  /// If true, then never write to the book's source directory.
+  /// Missing IDs are reported as warnings, and assets are written by the `quiz` renderer.
+  read_only: bool,
+
+  /// If defined, the only tags that questions may use.
+  tags: Option<Vec<String>>,
+
+  /// If defined, the only learning objectives that questions may use.
+  objectives: Option<Vec<String>>,
+
   dev_mode: bool,
 }
 
+impl QuizConfig {
+  /// Reads the `[preprocessor.quiz]` section of a book's config.
+  /// Paths are resolved relative to the book's `root`.
+  fn new(book_config: &Config, root: &Path) -> Result<Self> {
+    let empty = toml::value::Table::new();
+    let config_toml = book_config
+      .get_preprocessor(QuizPreprocessor::name())
+      .unwrap_or(&empty);
+    let parse_bool = |key: &str| config_toml.get(key).map(|value| value.as_bool().unwrap());
+    let get_str = |key: &str| {
+      config_toml
+        .get(key)
+        .map(|value| value.as_str().unwrap().to_string())
+    };
+    let get_strs = |key: &str| -> Result<Option<Vec<String>>> {
+      config_toml
+        .get(key)
+        .map(|value| {
+          value
+            .clone()
+            .try_into()
+            .with_context(|| format!("`{key}` must be an array of strings"))
+        })
+        .transpose()
+    };
+
+    let dev_mode = env::var("QUIZ_DEV_MODE").is_ok();
+    Ok(QuizConfig {
+      fullscreen: parse_bool("fullscreen"),
+      cache_answers: parse_bool("cache-answers"),
+      allow_retry: parse_bool("allow-retry"),
+      shuffle: parse_bool("shuffle"),
+      passing_score: config_toml
+        .get("passing-score")
+        .map(|value| {
+          value
+            .as_integer()
+            .and_then(|n| u32::try_from(n).ok())
+            .filter(|n| *n <= 100)
+            .context("`passing-score` must be a percentage from 0 to 100")
+        })
+        .transpose()?,
+      default_language: get_str("default-language"),
+      more_words: get_str("more-words").map(|path| root.join(path)),
+      spellcheck: parse_bool("spellcheck"),
+      spellcheck_language: get_str("spellcheck-language"),
+      book_language: book_config.book.language.clone(),
+      spellcheck_dictionaries: get_str("spellcheck-dictionaries").map(|path| root.join(path)),
+      show_bug_reporter: parse_bool("show-bug-reporter"),
+      initial_text: get_str("initial-text"),
+      id_lockfile: get_str("id-lockfile").map(|path| root.join(path)),
+      id_strategy: get_str("id-strategy")
+        .map(|s| IdStrategy::parse(&s))
+        .transpose()?
+        .unwrap_or_default(),
+      read_only: parse_bool("read-only").unwrap_or(false),
+      tags: get_strs("tags")?,
+      objectives: get_strs("objectives")?,
+      dev_mode,
+    })
+  }
+
+  /// Registers the configured dictionaries, and loads the spellcheck dictionary if enabled.
+  ///
+  /// A missing dictionary is an error for an explicit `spellcheck-language`. A book whose
+  /// `language` has no dictionary is spellchecked in English, as it was before languages
+  /// could be selected.
+  fn load_spellcheck(&mut self) -> Result<()> {
+    if let Some(more_words) = &self.more_words {
+      mdbook_quiz_validate::register_more_words(more_words)?;
+    }
+
+    if let Some(dir) = &self.spellcheck_dictionaries {
+      mdbook_quiz_validate::register_dictionary_dir(dir)?;
+    }
+
+    if let Some(true) = self.spellcheck {
+      match (&self.spellcheck_language, &self.book_language) {
+        (Some(language), _) => mdbook_quiz_validate::load_dictionary(language)?,
+        (None, Some(language)) => match mdbook_quiz_validate::load_dictionary(language) {
+          Ok(()) => self.spellcheck_language = Some(language.clone()),
+          Err(e) => log::warn!(
+            "{e:#} Spellchecking in `{}` instead.",
+            mdbook_quiz_validate::DEFAULT_LANGUAGE
+          ),
+        },
+        (None, None) => {
+          mdbook_quiz_validate::load_dictionary(mdbook_quiz_validate::DEFAULT_LANGUAGE)?
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn validation_options(&self, chapter_dir: Option<PathBuf>) -> ValidationOptions {
+    ValidationOptions {
+      spellcheck: self.spellcheck.unwrap_or(false),
+      language: self.spellcheck_language.clone(),
+      chapter_dir,
+      id_registry: None,
+      require_ids: false,
+      allowed_tags: self.tags.clone(),
+      allowed_objectives: self.objectives.clone(),
+    }
+  }
+}
+
 struct QuizPreprocessor {
   config: QuizConfig,
   validated: Validated,
+  id_registry: Option<IdRegistry>,
+  root: PathBuf,
+  /// Held while adding IDs to a file, since chapters are processed in parallel
+  /// and a file may be used by many of them.
+  id_lock: Mutex<()>,
   #[cfg(feature = "aquascope")]
   aquascope: mdbook_aquascope::AquascopePreprocessor,
 }
@@ -72,106 +311,439 @@ impl QuizPreprocessor {
   // Need to figure out an extension mechanism to add custom blocks w/ pre-rendering.
   #[cfg(feature = "aquascope")]
   fn add_aquascope_blocks(&self, config: &mut toml::Value) -> Result<()> {
-    let config = config.as_table_mut().unwrap();
-    let questions = config
+    let Some(questions) = config
       .get_mut("questions")
-      .context("Must contain questions")?
-      .as_array_mut()
-      .unwrap();
+      .and_then(toml::Value::as_array_mut)
+    else {
+      return Ok(());
+    };
     for question in questions.iter_mut() {
-      let question = question.as_table_mut().unwrap();
-      let prompt = question.get_mut("prompt").unwrap().as_table_mut().unwrap();
+      let Some(question) = question.as_table_mut() else {
+        continue;
+      };
 
       let update_slot = |slot_opt: Option<&mut toml::Value>| -> Result<()> {
-        if let Some(slot) = slot_opt {
-          let text = slot.as_str().unwrap();
+        if let Some(toml::Value::String(text)) = slot_opt {
           let replacements = self.aquascope.replacements(text)?;
-          let mut new_text = String::from(text);
           for (range, html) in replacements.into_iter().rev() {
-            new_text.replace_range(range, &html);
+            text.replace_range(range, &html);
           }
-          *slot = toml::Value::String(new_text);
         }
         Ok(())
       };
 
-      update_slot(prompt.get_mut("prompt"))?;
+      update_slot(
+        question
+          .get_mut("prompt")
+          .and_then(|prompt| prompt.get_mut("prompt")),
+      )?;
       update_slot(question.get_mut("context"))?;
     }
     Ok(())
   }
 
-  fn auto_id(&self, path: &Path, contents: &str) -> Result<bool> {
-    use toml_edit::{Document, Formatted, Item, Value};
-    let mut doc = contents.parse::<Document>()?;
-    let qs = doc
-      .get_mut("questions")
-      .unwrap()
-      .as_array_of_tables_mut()
-      .unwrap();
-    let mut changed = false;
-    for q in qs.iter_mut() {
-      if !q.contains_key("id") {
-        changed = true;
-        let id = Uuid::new_v4().to_string();
-        q.insert("id", Item::Value(Value::String(Formatted::new(id))));
+  /// Writes the ID lockfile, if there is one, after every chapter has been processed.
+  fn write_lockfile(&self) -> Result<()> {
+    match &self.id_registry {
+      Some(registry) => registry.finish(),
+      None => Ok(()),
+    }
+  }
+
+  /// Returns `path` relative to the book root, with `/` separators.
+  fn book_relative_path(&self, path: &Path) -> String {
+    let path = path.strip_prefix(&self.root).unwrap_or(path);
+    path
+      .components()
+      .map(|c| c.as_os_str().to_string_lossy())
+      .collect::<Vec<_>>()
+      .join("/")
+  }
+
+  /// Adds an ID to each question in `contents` without one, returning the new contents
+  /// if any were added. `quiz_path` identifies the quiz for deterministic IDs.
+  fn add_ids(&self, quiz_path: &str, format: QuizFormat, contents: &str) -> Result<Option<String>> {
+    let quiz = format.parse::<toml::Value>(contents)?;
+    // A quiz with only includes or pools has no questions of its own.
+    let questions = quiz
+      .get("questions")
+      .and_then(toml::Value::as_array)
+      .map_or(&[][..], Vec::as_slice);
+    // A deterministic ID can collide with an existing one, e.g. when a question is inserted
+    // above a question whose ID was derived from the same position.
+    let mut taken = questions
+      .iter()
+      .filter_map(|q| q.get("id")?.as_str())
+      .map(str::to_string)
+      .collect::<HashSet<_>>();
+    let mut ids = Vec::new();
+    for (i, question) in questions.iter().enumerate() {
+      if question.get("id").is_some() {
+        continue;
       }
+      let mut attempt = 0;
+      let id = loop {
+        let Some(id) = self
+          .config
+          .id_strategy
+          .generate(quiz_path, i, attempt, question)?
+        else {
+          anyhow::bail!(
+            "Question {} in {quiz_path} is missing an `id`, and `id-strategy = \"never\"` prevents generating one",
+            i + 1,
+          );
+        };
+        let id = id.to_string();
+        if !taken.contains(&id) && self.validated.insert_id(&id) {
+          break id;
+        }
+        attempt += 1;
+      };
+      taken.insert(id.clone());
+      ids.push((i, id));
     }
-    if changed {
-      fs::write(path, doc.to_string())?;
+    if ids.is_empty() {
+      return Ok(None);
     }
-    Ok(changed)
+
+    let new_contents = match format {
+      QuizFormat::Toml => {
+        use toml_edit::{Document, Formatted, Item, Value};
+        let mut doc = contents.parse::<Document>()?;
+        let qs = doc
+          .get_mut("questions")
+          .unwrap()
+          .as_array_of_tables_mut()
+          .unwrap();
+        for (i, id) in ids {
+          let q = qs.get_mut(i).unwrap();
+          q.insert("id", Item::Value(Value::String(Formatted::new(id))));
+          // Put the ID first, where `mdbook-quiz fmt` would.
+          q.sort_values_by(|k1, _, k2, _| (k2.get() == "id").cmp(&(k1.get() == "id")));
+        }
+        doc.to_string()
+      }
+      QuizFormat::Json | QuizFormat::Yaml => insert_ids(format, contents, &ids)?,
+    };
+    Ok(Some(new_contents))
   }
 
-  fn process_quiz(&self, chapter_dir: &Path, quiz_path: &str) -> Result<String> {
-    let quiz_path_rel = Path::new(quiz_path);
+  /// Returns true if IDs may be written to quiz files.
+  /// In read-only builds, IDs are only checked, except that the "never" strategy still fails.
+  fn write_ids(&self) -> bool {
+    !self.config.read_only || self.config.id_strategy == IdStrategy::Never
+  }
+
+  fn validation_options(&self, chapter_dir: &Path, write_ids: bool) -> ValidationOptions {
+    ValidationOptions {
+      id_registry: self.id_registry.clone(),
+      require_ids: !write_ids,
+      ..self
+        .config
+        .validation_options(Some(chapter_dir.to_path_buf()))
+    }
+  }
+
+  fn process_quiz(&self, chapter_dir: &Path, directive: &QuizDirective) -> Result<String> {
+    let quiz_path_rel = Path::new(&directive.path);
     let quiz_path_abs = chapter_dir.join(quiz_path_rel);
 
-    let mut content_toml = fs::read_to_string(&quiz_path_abs)
+    let format = QuizFormat::from_path(&quiz_path_abs);
+    let mut contents = fs::read_to_string(&quiz_path_abs)
       .with_context(|| format!("Failed to read quiz file: {}", quiz_path_abs.display()))?;
 
-    mdbook_quiz_validate::validate(
-      &quiz_path_abs,
-      &content_toml,
+    let write_ids = self.write_ids();
+    let options = self.validation_options(chapter_dir, write_ids);
+    mdbook_quiz_validate::validate(&quiz_path_abs, &contents, &self.validated, &options)?;
+
+    if write_ids {
+      contents = self.read_quiz_file(&quiz_path_abs)?;
+    }
+
+    let mut quiz = format.parse::<toml::Value>(&contents)?;
+    let quiz_dir = quiz_path_abs.parent().unwrap();
+    mdbook_quiz_validate::expand_snippets(&mut quiz)?;
+    mdbook_quiz_validate::inline_program_files(&mut quiz, quiz_dir)?;
+    self.resolve_includes(&mut quiz, &quiz_path_abs)?;
+    let learner_pools = self.resolve_pools(
+      &mut quiz,
+      quiz_dir,
+      &self.book_relative_path(&quiz_path_abs),
+    )?;
+
+    let quiz_name = quiz_path_rel.file_stem().unwrap().to_string_lossy();
+    self.render_quiz(&quiz_name, quiz, learner_pools, &directive.options)
+  }
+
+  /// Processes the `index`-th inline quiz in a chapter with the given `content`.
+  ///
+  /// If the chapter's file is known, then generated IDs are written back into it.
+  fn process_inline_quiz(
+    &self,
+    chapter_dir: &Path,
+    chapter_path: Option<&Path>,
+    content: &str,
+    index: usize,
+    quiz: &InlineQuiz,
+  ) -> Result<String> {
+    let mut content_toml = content[quiz.body.clone()].to_string();
+    let write_ids = self.write_ids() && chapter_path.is_some();
+    let options = self.validation_options(chapter_dir, write_ids);
+    let path = chapter_path.map_or_else(|| chapter_dir.join("<inline>"), Path::to_path_buf);
+    let report = mdbook_quiz_validate::validate_embedded_report(
+      &path,
+      content,
+      quiz.body.clone(),
       &self.validated,
-      self.config.spellcheck.unwrap_or(false),
+      &options,
     )?;
+    mdbook_quiz_validate::print_report(&report)?;
+
+    let quiz_path = format!("{}#{index}", self.book_relative_path(&path));
+    if write_ids {
+      // The chapter's content may have been changed by other preprocessors (e.g. `{{#include}}`),
+      // so find the quiz in the file on disk rather than using its range in `content`.
+      let located = self.add_ids_to_file(&path, &quiz_path, QuizFormat::Toml, |file_contents| {
+        let mut matches = file_contents.match_indices(&content_toml);
+        match (matches.next(), matches.next()) {
+          (Some((start, _)), None) => Some(start..start + content_toml.len()),
+          _ => None,
+        }
+      })?;
+      match located {
+        Some(new_contents) => content_toml = new_contents,
+        None => log::warn!(
+          "Could not find inline quiz {quiz_path} in its chapter file, so IDs were not written to it"
+        ),
+      }
+    }
+
+    let mut quiz_value = content_toml.parse::<toml::Value>()?;
+    mdbook_quiz_validate::expand_snippets(&mut quiz_value)?;
+    mdbook_quiz_validate::inline_program_files(&mut quiz_value, chapter_dir)?;
+    self.resolve_includes(&mut quiz_value, &path)?;
+    let learner_pools = self.resolve_pools(&mut quiz_value, chapter_dir, &quiz_path)?;
+
+    // Chapters in different directories can have the same file name, so the name includes the path.
+    let chapter = self.book_relative_path(&path.with_extension(""));
+    let quiz_name = format!("{chapter}-inline-{index}");
+    self.render_quiz(&quiz_name, quiz_value, learner_pools, &quiz.options)
+  }
+
+  /// Reads a quiz file, after adding IDs to it.
+  fn read_quiz_file(&self, path: &Path) -> Result<String> {
+    if !self.write_ids() {
+      return fs::read_to_string(path)
+        .with_context(|| format!("Failed to read quiz file: {}", path.display()));
+    }
+    let quiz_path = self.book_relative_path(path);
+    let format = QuizFormat::from_path(path);
+    let contents =
+      self.add_ids_to_file(path, &quiz_path, format, |contents| Some(0..contents.len()))?;
+    Ok(contents.unwrap())
+  }
+
+  /// Adds IDs to the quiz in the file at `path`, within the range of the file's contents
+  /// returned by `locate`, and returns the quiz with its IDs.
+  ///
+  /// The file is read and written while holding `id_lock`, so that IDs added by another
+  /// chapter are not overwritten. Returns `None` if `locate` can't find the quiz.
+  fn add_ids_to_file(
+    &self,
+    path: &Path,
+    quiz_path: &str,
+    format: QuizFormat,
+    locate: impl FnOnce(&str) -> Option<Range<usize>>,
+  ) -> Result<Option<String>> {
+    let _guard = self.id_lock.lock().unwrap();
+    let mut contents = fs::read_to_string(path)
+      .with_context(|| format!("Failed to read quiz file: {}", path.display()))?;
+    let Some(range) = locate(&contents) else {
+      return Ok(None);
+    };
+    let quiz = match self.add_ids(quiz_path, format, &contents[range.clone()])? {
+      Some(new_quiz) => {
+        contents.replace_range(range, &new_quiz);
+        fs::write(path, &contents)?;
+        new_quiz
+      }
+      None => contents[range].to_string(),
+    };
+    Ok(Some(quiz))
+  }
+
+  /// Reads the questions of the pool file at `path`, after adding IDs to it.
+  fn load_pool_file(&self, path: &Path) -> Result<Vec<toml::Value>> {
+    let contents = self.read_quiz_file(path)?;
+    let mut pool_quiz = QuizFormat::from_path(path).parse::<toml::Value>(&contents)?;
+    mdbook_quiz_validate::expand_snippets(&mut pool_quiz)?;
+    mdbook_quiz_validate::inline_program_files(&mut pool_quiz, path.parent().unwrap())?;
+    let questions = pool_quiz.as_table_mut().unwrap().remove("questions");
+    Ok(match questions {
+      Some(toml::Value::Array(questions)) => questions,
+      _ => Vec::new(),
+    })
+  }
+
+  /// Replaces the includes of the quiz at `quiz_path` with the questions they select,
+  /// which are added to its questions.
+  fn resolve_includes(&self, quiz: &mut toml::Value, quiz_path: &Path) -> Result<()> {
+    let quiz = quiz.as_table_mut().unwrap();
+    let Some(includes) = quiz.remove("include") else {
+      return Ok(());
+    };
+    let includes: Vec<QuizInclude> = includes.try_into()?;
+
+    for include in &includes {
+      let included = mdbook_quiz_validate::resolve_include(quiz_path, include, &mut |path| {
+        self.read_quiz_file(path)
+      })?;
+      let questions = quiz
+        .entry("questions")
+        .or_insert_with(|| toml::Value::Array(Vec::new()));
+      questions.as_array_mut().unwrap().extend(included);
+    }
+
+    Ok(())
+  }
+
+  /// Replaces the pools of `quiz` with their samples, which are added to its questions.
+  /// Returns the pools that are sampled by each learner instead, with their candidates.
+  ///
+  /// `quiz_path` identifies the quiz for the default seed.
+  fn resolve_pools(
+    &self,
+    quiz: &mut toml::Value,
+    quiz_dir: &Path,
+    quiz_path: &str,
+  ) -> Result<Vec<LearnerPool>> {
+    let quiz = quiz.as_table_mut().unwrap();
+    let Some(pools) = quiz.remove("pools") else {
+      return Ok(Vec::new());
+    };
+    let pools: Vec<QuestionPool> = pools.try_into()?;
+
+    let mut learner_pools = Vec::new();
+    for (i, pool) in pools.into_iter().enumerate() {
+      let mut questions = Vec::new();
+      for file in &pool.files {
+        questions.extend(self.load_pool_file(&quiz_dir.join(file))?);
+      }
+      let candidates = pools::candidates(&pool, questions);
 
-    let changed = self.auto_id(&quiz_path_abs, &content_toml)?;
-    if changed {
-      content_toml = fs::read_to_string(&quiz_path_abs)?;
+      match pool.sample.unwrap_or(PoolSampling::Build) {
+        PoolSampling::Build => {
+          let seed = pool.seed.map_or_else(
+            || {
+              let name = format!("{quiz_path}#pools[{i}]");
+              Uuid::new_v5(&Uuid::NAMESPACE_URL, name.as_bytes())
+                .as_u64_pair()
+                .0
+            },
+            u64::from,
+          );
+          let sampled = pools::sample(&pool, &candidates, seed)
+            .with_context(|| format!("Failed to sample pool {} of {quiz_path}", i + 1))?;
+          let questions = quiz
+            .entry("questions")
+            .or_insert_with(|| toml::Value::Array(Vec::new()));
+          questions.as_array_mut().unwrap().extend(sampled);
+        }
+        PoolSampling::Learner => {
+          pools::check(&pool, &candidates)
+            .with_context(|| format!("Failed to sample pool {} of {quiz_path}", i + 1))?;
+          learner_pools.push(LearnerPool {
+            count: pool.count,
+            tags: pool.tags,
+            questions: candidates,
+          });
+        }
+      }
     }
 
-    #[allow(unused_mut)]
-    let mut content = content_toml.parse::<toml::Value>()?;
+    quiz
+      .entry("questions")
+      .or_insert_with(|| toml::Value::Array(Vec::new()));
+    Ok(learner_pools)
+  }
 
+  /// Returns the placeholder HTML for a quiz, which is replaced by the frontend.
+  fn render_quiz(
+    &self,
+    quiz_name: &str,
+    mut content: toml::Value,
+    #[allow(unused_mut)] mut learner_pools: Vec<LearnerPool>,
+    options: &EmbedOptions,
+  ) -> Result<String> {
     #[cfg(feature = "aquascope")]
-    self.add_aquascope_blocks(&mut content)?;
+    {
+      self.add_aquascope_blocks(&mut content)?;
+      for pool in &mut learner_pools {
+        let mut candidates = toml::Value::Table(toml::value::Table::from_iter([(
+          "questions".to_string(),
+          toml::Value::Array(std::mem::take(&mut pool.questions)),
+        )]));
+        self.add_aquascope_blocks(&mut candidates)?;
+        pool.questions = candidates["questions"].as_array().unwrap().clone();
+      }
+    }
 
-    let quiz_name = quiz_path_rel.file_stem().unwrap().to_string_lossy();
+    let settings: QuizSettings = match content.as_table_mut().unwrap().remove("settings") {
+      Some(settings) => settings.try_into()?,
+      None => QuizSettings::default(),
+    };
 
     let mut html = HtmlElementBuilder::new();
 
     html
       .attr("class", "quiz-placeholder")
-      .data("quiz-name", &quiz_name)?
+      .data("quiz-name", quiz_name)?
       .data("quiz-questions", &content)?;
+    if !learner_pools.is_empty() {
+      html.data("quiz-pools", &learner_pools)?;
+    }
 
-    if let Some(true) = self.config.fullscreen {
+    // Options in the directive override the quiz's settings, which override the book's config.
+    let config = &self.config;
+    if let Some(true) = options
+      .fullscreen
+      .or(settings.fullscreen)
+      .or(config.fullscreen)
+    {
       html.data("quiz-fullscreen", true)?;
     }
-    if let Some(true) = self.config.cache_answers
-      && !self.config.dev_mode
+    if let Some(true) = options
+      .cache_answers
+      .or(settings.cache_answers)
+      .or(config.cache_answers)
+      && !config.dev_mode
     {
       html.data("quiz-cache-answers", true)?;
     }
-    if let Some(lang) = &self.config.default_language {
+    if let Some(false) = settings.allow_retry.or(config.allow_retry) {
+      html.data("quiz-allow-retry", false)?;
+    }
+    if let Some(true) = settings.shuffle.or(config.shuffle) {
+      html.data("quiz-shuffle", true)?;
+    }
+    if let Some(score) = settings.passing_score.or(config.passing_score) {
+      html.data("quiz-passing-score", score)?;
+    }
+    if let Some(lang) = options
+      .default_language
+      .as_ref()
+      .or(config.default_language.as_ref())
+    {
       html.data("quiz-default-language", lang)?;
     }
-    if let Some(true) = self.config.show_bug_reporter {
+    if let Some(true) = options.show_bug_reporter.or(config.show_bug_reporter) {
       html.data("quiz-show-bug-reporter", "")?;
     }
-    if let Some(s) = &self.config.initial_text {
+    if let Some(s) = options
+      .initial_text
+      .as_ref()
+      .or(config.initial_text.as_ref())
+    {
       html.data("quiz-initial-text", s)?;
     }
 
@@ -179,9 +751,291 @@ impl QuizPreprocessor {
   }
 }
 
+impl QuizPreprocessor {
+  /// Returns the HTML that replaces each `{{#quiz}}` directive and inline quiz in a chapter.
+  ///
+  /// `chapter_path` is the chapter's file, if known, where IDs of inline quizzes are written.
+  fn chapter_replacements(
+    &self,
+    chapter_dir: &Path,
+    chapter_path: Option<&Path>,
+    content: &str,
+  ) -> Result<Vec<(std::ops::Range<usize>, String)>> {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    let regex = REGEX.get_or_init(|| Regex::new(r"\{\{#quiz ([^}]+)\}\}").unwrap());
+    let mut replacements = regex
+      .captures_iter(content)
+      .map(|captures| {
+        let range = captures.get(0).unwrap().range();
+        let directive = QuizDirective::parse(captures.get(1).unwrap().as_str())
+          .with_context(|| format!("Invalid quiz directive: {}", &captures[0]))?;
+        let html = self.process_quiz(chapter_dir, &directive)?;
+        Ok((range, html))
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    let inline_quizzes = inline::find_inline_quizzes(content)?;
+    for (i, quiz) in inline_quizzes.iter().enumerate() {
+      let html = self.process_inline_quiz(chapter_dir, chapter_path, content, i + 1, quiz)?;
+      replacements.push((quiz.range.clone(), format!("{html}\n")));
+    }
+
+    Ok(replacements)
+  }
+}
+
+/// Inserts an `id` key as the first entry of each of the given questions in a JSON or YAML quiz,
+/// matching the layout of the question's other entries.
+fn insert_ids(format: QuizFormat, contents: &str, ids: &[(usize, String)]) -> Result<String> {
+  let quiz = format.parse_value(contents)?;
+  let questions = quiz.get_ref().as_table().unwrap()["questions"]
+    .get_ref()
+    .as_array()
+    .unwrap();
+
+  let mut insertions = Vec::new();
+  for (i, id) in ids {
+    let question = &questions[*i];
+    let first_key = question
+      .get_ref()
+      .as_table()
+      .unwrap()
+      .keys()
+      .map(|key| key.start())
+      .min()
+      .context("Cannot add an ID to an empty question")?;
+    let entry = match format {
+      QuizFormat::Json => format!("\"id\": \"{id}\""),
+      _ => format!("id: {id}"),
+    };
+    let text = if contents[question.start()..].starts_with('{') {
+      // A flow mapping (or any JSON object), like `{ "type": ... }`. The new entry is separated
+      // from the next one by the same whitespace that follows the opening brace.
+      let sep = &contents[question.start() + 1..first_key];
+      format!("{entry},{}", if sep.is_empty() { " " } else { sep })
+    } else {
+      // A block mapping, where every entry starts at the column of the first key.
+      let line_start = contents[..first_key].rfind('\n').map_or(0, |i| i + 1);
+      let indent = " ".repeat(contents[line_start..first_key].chars().count());
+      format!("{entry}\n{indent}")
+    };
+    insertions.push((first_key, text));
+  }
+
+  let mut new_contents = contents.to_string();
+  insertions.sort_by_key(|(offset, _)| *offset);
+  for (offset, text) in insertions.into_iter().rev() {
+    new_contents.insert_str(offset, &text);
+  }
+  Ok(new_contents)
+}
+
+fn all_assets() -> Vec<Asset> {
+  FRONTEND_ASSETS
+    .iter()
+    .chain(&RA_ASSETS)
+    .chain(&SOURCE_MAP_ASSETS)
+    .copied()
+    .collect()
+}
+
 #[derive(clap::Parser)]
 #[clap(author, about, version)]
-struct QuizArgs;
+struct QuizArgs {
+  #[clap(subcommand)]
+  command: Option<QuizCommand>,
+}
+
+#[derive(clap::Subcommand)]
+enum QuizCommand {
+  /// Validate quiz files without building a book.
+  Validate {
+    /// Rewrite each quiz file to apply the fixes suggested by the validator.
+    /// Only TOML quizzes can be fixed.
+    #[clap(long)]
+    fix: bool,
+
+    /// Enable the spellchecker.
+    #[clap(long)]
+    spellcheck: bool,
+
+    /// Check question IDs against this lockfile, and update it.
+    #[clap(long)]
+    id_lockfile: Option<PathBuf>,
+
+    /// Quiz files to validate.
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+  },
+
+  /// Format quiz files in a canonical style.
+  Fmt {
+    /// Don't write the files, and fail if any of them are not formatted.
+    #[clap(long)]
+    check: bool,
+
+    /// Quiz files to format. JSON and YAML quizzes are skipped.
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+  },
+
+  /// Upgrade quiz files to the latest version of the quiz format.
+  ///
+  /// Version 1 is the only version so far, so this leaves every quiz unchanged for now.
+  Migrate {
+    /// Don't write the files, and fail if any of them need to be migrated.
+    #[clap(long)]
+    check: bool,
+
+    /// Quiz files to migrate. JSON and YAML quizzes are skipped.
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+  },
+
+  /// Run a language server for quiz files over stdio.
+  Lsp,
+
+  /// Copy the quiz assets into the HTML output, when used as the `[output.quiz]` renderer.
+  Assets,
+}
+
+impl QuizCommand {
+  fn run(self) -> Result<()> {
+    match self {
+      QuizCommand::Validate {
+        fix,
+        spellcheck,
+        id_lockfile,
+        paths,
+      } => {
+        let validated = Validated::default();
+        let id_registry = id_lockfile.as_deref().map(IdRegistry::load).transpose()?;
+        // Fixes are computed with a separate registry, so that IDs replaced by a fix
+        // are not considered seen in this run.
+        let fix_registry = match &id_lockfile {
+          Some(path) if fix => Some(IdRegistry::load(path)?),
+          _ => None,
+        };
+        let mut failed = false;
+        for path in paths {
+          let mut contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read quiz file: {}", path.display()))?;
+          let options = ValidationOptions {
+            spellcheck,
+            chapter_dir: path.parent().map(Path::to_path_buf),
+            id_registry: id_registry.clone(),
+            ..Default::default()
+          };
+
+          let can_fix = QuizFormat::from_path(&path) == QuizFormat::Toml;
+          if fix && !can_fix {
+            eprintln!(
+              "Skipping fixes for {}: only TOML quizzes can be fixed",
+              path.display()
+            );
+          }
+
+          if fix
+            && can_fix
+            && let Some(report) = mdbook_quiz_validate::validate_report(
+              &path,
+              &contents,
+              &Validated::default(),
+              &ValidationOptions {
+                id_registry: fix_registry.clone(),
+                ..options.clone()
+              },
+            )?
+          {
+            let fixes = report.fixes().collect::<Vec<_>>();
+            if !fixes.is_empty() {
+              contents = fix::apply_fixes(&contents, fixes.iter().copied())?;
+              fs::write(&path, &contents)?;
+              eprintln!("Fixed {} problem(s) in {}:", fixes.len(), path.display());
+              for fix in fixes {
+                eprintln!("  {}", fix.title);
+              }
+            }
+          }
+
+          if mdbook_quiz_validate::validate(&path, &contents, &validated, &options).is_err() {
+            failed = true;
+          }
+        }
+        if let Some(registry) = id_registry {
+          registry.finish()?;
+        }
+        anyhow::ensure!(!failed, "Some quizzes failed validation");
+        Ok(())
+      }
+      QuizCommand::Fmt { check, paths } => {
+        let mut unformatted = Vec::new();
+        for path in paths {
+          if QuizFormat::from_path(&path) != QuizFormat::Toml {
+            eprintln!(
+              "Skipping {}: only TOML quizzes can be formatted",
+              path.display()
+            );
+            continue;
+          }
+          let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read quiz file: {}", path.display()))?;
+          let formatted = fmt::format(&contents)
+            .with_context(|| format!("Failed to format quiz file: {}", path.display()))?;
+          if formatted == contents {
+            continue;
+          }
+          if check {
+            eprintln!("Not formatted: {}", path.display());
+          } else {
+            fs::write(&path, formatted)?;
+          }
+          unformatted.push(path);
+        }
+        anyhow::ensure!(
+          !check || unformatted.is_empty(),
+          "{} quiz file(s) are not formatted, run `mdbook-quiz fmt` to fix them",
+          unformatted.len()
+        );
+        Ok(())
+      }
+      QuizCommand::Migrate { check, paths } => {
+        let mut outdated = Vec::new();
+        for path in paths {
+          if QuizFormat::from_path(&path) != QuizFormat::Toml {
+            eprintln!(
+              "Skipping {}: only TOML quizzes can be migrated",
+              path.display()
+            );
+            continue;
+          }
+          let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read quiz file: {}", path.display()))?;
+          let migrated = migrate::migrate(&contents)
+            .with_context(|| format!("Failed to migrate quiz file: {}", path.display()))?;
+          let Some(migrated) = migrated else {
+            continue;
+          };
+          if check {
+            eprintln!("Not migrated: {}", path.display());
+          } else {
+            fs::write(&path, migrated)?;
+            eprintln!("Migrated {}", path.display());
+          }
+          outdated.push(path);
+        }
+        anyhow::ensure!(
+          !check || outdated.is_empty(),
+          "{} quiz file(s) are not migrated, run `mdbook-quiz migrate` to fix them",
+          outdated.len()
+        );
+        Ok(())
+      }
+      QuizCommand::Lsp => lsp::run(),
+      QuizCommand::Assets => driver::render_assets(),
+    }
+  }
+}
 
 impl SimplePreprocessor for QuizPreprocessor {
   type Args = QuizArgs;
@@ -193,33 +1047,20 @@ impl SimplePreprocessor for QuizPreprocessor {
   fn build(ctx: &PreprocessorContext) -> Result<Self> {
     log::info!("Running the mdbook-quiz preprocessor");
 
-    let config_toml = ctx.config.get_preprocessor(Self::name()).unwrap();
-    let parse_bool = |key: &str| config_toml.get(key).map(|value| value.as_bool().unwrap());
-    let get_str = |key: &str| {
-      config_toml
-        .get(key)
-        .map(|value| value.as_str().unwrap().to_string())
-    };
-
-    let dev_mode = env::var("QUIZ_DEV_MODE").is_ok();
-    let config = QuizConfig {
-      fullscreen: parse_bool("fullscreen"),
-      cache_answers: parse_bool("cache-answers"),
-      default_language: get_str("default-language"),
-      more_words: get_str("more-words").map(PathBuf::from),
-      spellcheck: parse_bool("spellcheck"),
-      show_bug_reporter: parse_bool("show-bug-reporter"),
-      initial_text: get_str("initial-text"),
-      dev_mode,
-    };
-
-    if let Some(more_words) = &config.more_words {
-      mdbook_quiz_validate::register_more_words(more_words)?;
-    }
+    let mut config = QuizConfig::new(&ctx.config, &ctx.root)?;
+    config.load_spellcheck()?;
+    let id_registry = config
+      .id_lockfile
+      .as_deref()
+      .map(IdRegistry::load)
+      .transpose()?;
 
     Ok(QuizPreprocessor {
       config,
       validated: Validated::default(),
+      id_registry,
+      root: ctx.root.clone(),
+      id_lock: Mutex::new(()),
       #[cfg(feature = "aquascope")]
       aquascope: mdbook_aquascope::AquascopePreprocessor::new()
         .context("Aquascope failed to initialize")?,
@@ -231,44 +1072,60 @@ impl SimplePreprocessor for QuizPreprocessor {
     chapter_dir: &Path,
     content: &str,
   ) -> Result<Vec<(std::ops::Range<usize>, String)>> {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    let regex = REGEX.get_or_init(|| Regex::new(r"\{\{#quiz ([^}]+)\}\}").unwrap());
-    regex
-      .captures_iter(content)
-      .map(|captures| {
-        let range = captures.get(0).unwrap().range();
-        let quiz_path = captures.get(1).unwrap().as_str();
-        let html = self.process_quiz(chapter_dir, quiz_path)?;
-        Ok((range, html))
-      })
-      .collect()
+    self.chapter_replacements(chapter_dir, None, content)
   }
 
   fn all_assets(&self) -> Vec<Asset> {
-    FRONTEND_ASSETS
-      .iter()
-      .chain(&RA_ASSETS)
-      .chain(&SOURCE_MAP_ASSETS)
-      .copied()
-      .collect()
+    all_assets()
   }
 
   fn linked_assets(&self) -> Vec<Asset> {
     FRONTEND_ASSETS.to_vec()
   }
+
+  fn finish(self) {
+    if let Err(e) = self.write_lockfile() {
+      log::error!("{e:#}");
+    }
+  }
 }
 
 fn main() {
-  mdbook_preprocessor_utils::main::<QuizPreprocessor>()
+  // mdbook invokes the preprocessor with no arguments or with `supports <renderer>`,
+  // which are handled by the driver.
+  match QuizArgs::try_parse() {
+    Ok(QuizArgs {
+      command: Some(command),
+    }) => {
+      if let Err(e) = command.run() {
+        eprintln!("{e:#}");
+        std::process::exit(1);
+      }
+    }
+    // Help, version, and errors in one of our own commands are reported by clap.
+    Err(e)
+      if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion)
+        || std::env::args()
+          .nth(1)
+          .is_some_and(|arg| QuizArgs::command().find_subcommand(&arg).is_some()) =>
+    {
+      e.exit()
+    }
+    _ => driver::main(),
+  }
 }
 
 #[cfg(test)]
 mod test {
-  use super::QuizPreprocessor;
+  use super::{QuizConfig, QuizPreprocessor};
   use anyhow::Result;
-  use mdbook_preprocessor_utils::{mdbook::BookItem, testing::MdbookTestHarness};
+  use mdbook_preprocessor_utils::{
+    mdbook::{BookItem, Config},
+    testing::MdbookTestHarness,
+  };
   use mdbook_quiz_schema::{Question, Quiz};
-  use std::fs;
+  use mdbook_quiz_validate::QuizFormat;
+  use std::{fs, path::Path};
 
   #[test]
   fn test_quiz_generator() -> Result<()> {
@@ -313,4 +1170,505 @@ mod test {
 
     Ok(())
   }
+
+  /// Builds a book with a single quiz, and returns the quiz file afterwards.
+  fn compile_quiz(config: serde_json::Value, quiz: &str) -> Result<String> {
+    let harness = MdbookTestHarness::new()?;
+    let quiz_path = harness.root().join("quiz.toml");
+    fs::write(&quiz_path, quiz)?;
+    let chapter_path = harness.root().join("src").join("chapter_1.md");
+    fs::write(&chapter_path, "{{#quiz ../quiz.toml}}")?;
+    harness.compile::<QuizPreprocessor>(config)?;
+    Ok(fs::read_to_string(quiz_path)?)
+  }
+
+  const QUIZ_WITHOUT_IDS: &str = r#"
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Hello world"
+answer.answer = "No"
+
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Goodbye world"
+answer.answer = "Yes"
+"#;
+
+  #[test]
+  fn test_id_strategies() -> Result<()> {
+    let ids = |strategy: &str| -> Result<Vec<String>> {
+      let config = serde_json::json!({ "id-strategy": strategy });
+      let quiz: Quiz = toml::from_str(&compile_quiz(config, QUIZ_WITHOUT_IDS)?)?;
+      Ok(
+        quiz
+          .questions
+          .iter()
+          .map(|q| match q {
+            Question::ShortAnswer(q) => q.0.id.clone().unwrap(),
+            _ => unreachable!(),
+          })
+          .collect(),
+      )
+    };
+
+    let path_ids = ids("path")?;
+    assert_eq!(path_ids, ids("path")?);
+    assert_ne!(path_ids[0], path_ids[1]);
+    let content_ids = ids("content")?;
+    assert_eq!(content_ids, ids("content")?);
+    assert_ne!(content_ids, path_ids);
+    assert_ne!(ids("random")?, ids("random")?);
+
+    let err = compile_quiz(
+      serde_json::json!({ "id-strategy": "never" }),
+      QUIZ_WITHOUT_IDS,
+    );
+    assert!(err.is_err());
+    let err = compile_quiz(
+      serde_json::json!({ "id-strategy": "nope" }),
+      QUIZ_WITHOUT_IDS,
+    );
+    assert!(err.is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_id_collisions() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let quiz_path = harness.root().join("quiz.toml");
+    fs::write(
+      harness.root().join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml}}",
+    )?;
+    let question_ids = || -> Result<Vec<String>> {
+      let quiz: Quiz = toml::from_str(&fs::read_to_string(&quiz_path)?)?;
+      Ok(
+        quiz
+          .questions
+          .iter()
+          .map(|q| match q {
+            Question::ShortAnswer(q) => q.0.id.clone().unwrap(),
+            _ => unreachable!(),
+          })
+          .collect(),
+      )
+    };
+
+    for strategy in ["path", "content"] {
+      let config = serde_json::json!({ "id-strategy": strategy });
+      let question = "[[questions]]\ntype = \"ShortAnswer\"\nprompt.prompt = \"Hello\"\nanswer.answer = \"No\"\n";
+      fs::write(&quiz_path, question)?;
+      harness.compile::<QuizPreprocessor>(config.clone())?;
+      let first = question_ids()?;
+
+      // A question inserted above the first one, which is identical to it before IDs are added.
+      let contents = fs::read_to_string(&quiz_path)?;
+      fs::write(&quiz_path, format!("{question}\n{contents}"))?;
+      harness.compile::<QuizPreprocessor>(config.clone())?;
+      let ids = question_ids()?;
+      assert_eq!(ids[1], first[0], "{strategy}: existing ID should be kept");
+      assert_ne!(ids[0], ids[1], "{strategy}: generated a duplicate ID");
+
+      // The file is valid in the next build.
+      harness.compile::<QuizPreprocessor>(config)?;
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_json_and_yaml() -> Result<()> {
+    let quizzes = [
+      (
+        "quiz.json",
+        r#"{
+  "questions": [
+    {
+      "type": "ShortAnswer",
+      "prompt": { "prompt": "Hello world" },
+      "answer": { "answer": "No" }
+    },
+    {"type": "ShortAnswer", "prompt": {"prompt": "Goodbye world"}, "answer": {"answer": "Yes"}}
+  ]
+}
+"#,
+      ),
+      (
+        "quiz.yaml",
+        r#"questions:
+  - type: ShortAnswer
+    prompt:
+      prompt: Hello world
+    answer: { answer: "No" }
+  - { type: ShortAnswer, prompt: { prompt: Goodbye world }, answer: { answer: "Yes" } }
+"#,
+      ),
+    ];
+
+    for (name, quiz) in quizzes {
+      let harness = MdbookTestHarness::new()?;
+      let quiz_path = harness.root().join(name);
+      fs::write(&quiz_path, quiz)?;
+      fs::write(
+        harness.root().join("src").join("chapter_1.md"),
+        format!("{{{{#quiz ../{name}}}}}"),
+      )?;
+      let config = serde_json::json!({ "id-strategy": "path" });
+      let mut book = harness.compile::<QuizPreprocessor>(config)?;
+      let BookItem::Chapter(chapter) = book.sections.remove(0) else {
+        unreachable!()
+      };
+      assert!(chapter.content.contains("Goodbye world"), "{name}");
+
+      // Each question gets an ID in its first entry, and the rest of the file is unchanged.
+      let new_quiz = fs::read_to_string(&quiz_path)?;
+      let quiz_value = QuizFormat::from_path(&quiz_path).parse::<Quiz>(&new_quiz)?;
+      let ids = quiz_value
+        .questions
+        .iter()
+        .map(|q| match q {
+          Question::ShortAnswer(q) => q.0.id.clone().unwrap(),
+          _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+      let entry = |id: &str| match name {
+        "quiz.json" => format!("\"id\": \"{id}\""),
+        _ => format!("id: {id}"),
+      };
+      let flow = match name {
+        "quiz.json" => format!("{{{}, \"type\"", entry(&ids[1])),
+        _ => format!("{{ {}, type", entry(&ids[1])),
+      };
+      assert!(new_quiz.contains(&flow), "{name}");
+
+      let mut stripped = new_quiz.clone();
+      for id in &ids {
+        let start = stripped.find(&entry(id)).unwrap();
+        let end = start + entry(id).len();
+        let end = end + stripped[end..].starts_with(',') as usize;
+        let ws = stripped[end..].len() - stripped[end..].trim_start().len();
+        stripped.replace_range(start..end + ws, "");
+      }
+      let normalize = |s: &str| s.replace([' ', '\n'], "");
+      assert_eq!(normalize(&stripped), normalize(quiz), "{name}");
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_pools() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    let pool = |name: &str, n: usize| {
+      (1..=n)
+        .map(|i| {
+          format!(
+            "[[questions]]\ntype = \"ShortAnswer\"\nprompt.prompt = \"{name} {i}\"\nanswer.answer = \"{i}\"\n"
+          )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+    };
+    fs::create_dir(root.join("pools"))?;
+    fs::write(root.join("pools").join("build.toml"), pool("Build", 4))?;
+    fs::write(root.join("pools").join("learner.toml"), pool("Learner", 3))?;
+    fs::write(
+      root.join("quiz.toml"),
+      r#"
+[[pools]]
+files = ["pools/build.toml"]
+count = 2
+
+[[pools]]
+files = ["pools/learner.toml"]
+count = 1
+sample = "learner"
+"#,
+    )?;
+    fs::write(
+      root.join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml}}",
+    )?;
+
+    let chapter = |config: serde_json::Value| -> Result<String> {
+      let mut book = harness.compile::<QuizPreprocessor>(config)?;
+      let BookItem::Chapter(chapter) = book.sections.remove(0) else {
+        unreachable!()
+      };
+      Ok(chapter.content)
+    };
+    let content = chapter(serde_json::json!({}))?;
+    let sampled = (1..=4)
+      .filter(|i| content.contains(&format!("Build {i}")))
+      .collect::<Vec<_>>();
+    assert_eq!(sampled.len(), 2);
+    assert!((1..=3).all(|i| content.contains(&format!("Learner {i}"))));
+    assert!(content.contains("data-quiz-pools"));
+
+    // Every pool question gets an ID, and the sample is the same in each build.
+    let pool_quiz: Quiz =
+      toml::from_str(&fs::read_to_string(root.join("pools").join("build.toml"))?)?;
+    assert!(pool_quiz.questions.iter().all(|q| match q {
+      Question::ShortAnswer(q) => q.0.id.is_some(),
+      _ => unreachable!(),
+    }));
+    let content = chapter(serde_json::json!({}))?;
+    assert!(
+      sampled
+        .iter()
+        .all(|i| content.contains(&format!("Build {i}")))
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_includes() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    fs::write(
+      root.join("bank.toml"),
+      r#"
+[[questions]]
+type = "ShortAnswer"
+tags = ["shared"]
+prompt.prompt = "Shared question"
+answer.answer = "1"
+
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Unshared question"
+answer.answer = "2"
+"#,
+    )?;
+    fs::write(
+      root.join("quiz.toml"),
+      r#"
+[[include]]
+path = "bank.toml"
+tags = ["shared"]
+"#,
+    )?;
+    fs::write(
+      root.join("src").join("chapter_1.md"),
+      "{{#quiz ../bank.toml}}\n\n{{#quiz ../quiz.toml}}",
+    )?;
+
+    let mut book = harness.compile::<QuizPreprocessor>(serde_json::json!({}))?;
+    let BookItem::Chapter(chapter) = book.sections.remove(0) else {
+      unreachable!()
+    };
+    let (bank, quiz) = chapter.content.split_once("\n\n").unwrap();
+    assert!(bank.contains("Unshared question"));
+    assert!(quiz.contains("Shared question"));
+    assert!(!quiz.contains("Unshared question"));
+
+    // The included question keeps the ID it was given in its own file.
+    let bank_quiz: Quiz = toml::from_str(&fs::read_to_string(root.join("bank.toml"))?)?;
+    let Question::ShortAnswer(shared) = &bank_quiz.questions[0] else {
+      unreachable!()
+    };
+    assert!(quiz.contains(shared.0.id.as_ref().unwrap()));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_snippets() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    fs::write(
+      root.join("quiz.toml"),
+      r#"
+[snippets]
+shape = "struct Square { side: u32 }"
+
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Given `{{snippet shape}}`, what is a square?"
+answer.answer = "A shape"
+"#,
+    )?;
+    fs::write(
+      root.join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml}}",
+    )?;
+
+    let mut book = harness.compile::<QuizPreprocessor>(serde_json::json!({}))?;
+    let BookItem::Chapter(chapter) = book.sections.remove(0) else {
+      unreachable!()
+    };
+    assert!(
+      chapter
+        .content
+        .contains("Given `struct Square { side: u32 }`")
+    );
+    assert!(!chapter.content.contains("snippet"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_metadata() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    fs::write(
+      root.join("quiz.toml"),
+      r#"
+[[questions]]
+id = "metadata"
+type = "ShortAnswer"
+tags = ["ownership"]
+difficulty = "hard"
+objectives = ["borrowck"]
+points = 3
+prompt.prompt = "Hello world"
+answer.answer = "No"
+"#,
+    )?;
+    fs::write(
+      root.join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml}}",
+    )?;
+
+    let mut book = harness.compile::<QuizPreprocessor>(serde_json::json!({}))?;
+    let BookItem::Chapter(chapter) = book.sections.remove(0) else {
+      unreachable!()
+    };
+    for key in ["difficulty", "hard", "objectives", "borrowck", "points"] {
+      assert!(chapter.content.contains(key), "payload is missing {key}");
+    }
+
+    let config = serde_json::json!({ "tags": ["ownership"], "objectives": ["borrowck"] });
+    assert!(harness.compile::<QuizPreprocessor>(config).is_ok());
+    let config = serde_json::json!({ "tags": ["ownership"], "objectives": ["traits"] });
+    assert!(harness.compile::<QuizPreprocessor>(config).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_spellcheck_language() -> Result<()> {
+    let mut config = Config::default();
+    config.book.language = Some("zz".into());
+    config.set("preprocessor.quiz.spellcheck", true)?;
+
+    // The book's language falls back to English without a dictionary.
+    let mut quiz_config = QuizConfig::new(&config, Path::new("."))?;
+    quiz_config.load_spellcheck()?;
+    assert_eq!(quiz_config.spellcheck_language, None);
+
+    // An explicit language must have a dictionary.
+    config.set("preprocessor.quiz.spellcheck-language", "zz")?;
+    let mut quiz_config = QuizConfig::new(&config, Path::new("."))?;
+    assert!(quiz_config.load_spellcheck().is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_program_files() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    let root = harness.root();
+    fs::create_dir(root.join("programs"))?;
+    fs::write(
+      root.join("programs").join("hello.rs"),
+      "// ANCHOR: main\nfn main() {\n  println!(\"Hello\");\n}\n// ANCHOR_END: main\n",
+    )?;
+    fs::write(
+      root.join("quiz.toml"),
+      r#"
+[[questions]]
+type = "Tracing"
+prompt.programFile = "programs/hello.rs:main"
+answer.doesCompile = true
+answer.stdout = "Hello"
+"#,
+    )?;
+    fs::write(
+      root.join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml}}",
+    )?;
+
+    let mut book = harness.compile::<QuizPreprocessor>(serde_json::json!({}))?;
+    let BookItem::Chapter(chapter) = book.sections.remove(0) else {
+      unreachable!()
+    };
+    assert!(chapter.content.contains("println!"));
+    assert!(!chapter.content.contains("ANCHOR"));
+    assert!(!chapter.content.contains("programFile"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_directive_options() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    fs::write(harness.root().join("quiz.toml"), QUIZ_WITHOUT_IDS)?;
+    fs::write(
+      harness.root().join("src").join("chapter_1.md"),
+      r#"{{#quiz ../quiz.toml fullscreen=false initial-text="Checkpoint"}}"#,
+    )?;
+
+    let config = serde_json::json!({ "fullscreen": true });
+    let mut book = harness.compile::<QuizPreprocessor>(config)?;
+    let BookItem::Chapter(chapter) = book.sections.remove(0) else {
+      unreachable!()
+    };
+    assert!(!chapter.content.contains("data-quiz-fullscreen"));
+    assert!(chapter.content.contains("data-quiz-initial-text"));
+    assert!(chapter.content.contains("Checkpoint"));
+
+    fs::write(
+      harness.root().join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml fullscren=false}}",
+    )?;
+    let err = harness
+      .compile::<QuizPreprocessor>(serde_json::json!({}))
+      .unwrap_err();
+    assert!(format!("{err:#}").contains("Unknown option `fullscren`"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_quiz_settings() -> Result<()> {
+    let harness = MdbookTestHarness::new()?;
+    fs::write(
+      harness.root().join("quiz.toml"),
+      format!(
+        "[settings]\nfullscreen = false\nshuffle = true\npassingScore = 80\n{QUIZ_WITHOUT_IDS}"
+      ),
+    )?;
+    fs::write(
+      harness.root().join("src").join("chapter_1.md"),
+      "{{#quiz ../quiz.toml}}",
+    )?;
+
+    let config =
+      serde_json::json!({ "fullscreen": true, "allow-retry": false, "passing-score": 50 });
+    let mut book = harness.compile::<QuizPreprocessor>(config)?;
+    let BookItem::Chapter(chapter) = book.sections.remove(0) else {
+      unreachable!()
+    };
+    assert!(!chapter.content.contains("data-quiz-fullscreen"));
+    assert!(chapter.content.contains("data-quiz-shuffle"));
+    assert!(chapter.content.contains("data-quiz-allow-retry"));
+    assert!(chapter.content.contains(r#"data-quiz-passing-score="80""#));
+    assert!(!chapter.content.contains("passingScore"));
+
+    fs::write(
+      harness.root().join("quiz.toml"),
+      format!("[settings]\nshufle = true\n{QUIZ_WITHOUT_IDS}"),
+    )?;
+    assert!(
+      harness
+        .compile::<QuizPreprocessor>(serde_json::json!({}))
+        .is_err()
+    );
+
+    Ok(())
+  }
 }
@@ -0,0 +1,143 @@
+//! Upgrades quiz files written in an older version of the quiz format.
+//!
+//! Each change to the format that would break existing quizzes adds a [`Migration`] from the
+//! previous version, and bumps [`QUIZ_VERSION`]. Migrations edit the file with `toml_edit`,
+//! so comments and formatting are kept.
+
+use anyhow::{Context, Result, ensure};
+use mdbook_quiz_schema::QUIZ_VERSION;
+use toml_edit::{Document, value};
+
+/// A change to a quiz file that upgrades it from version `from` to version `from + 1`.
+pub struct Migration {
+  /// The version that the migration upgrades from.
+  pub from: u32,
+
+  /// Upgrades a quiz in version `from` to the next version.
+  pub apply: fn(&mut Document) -> Result<()>,
+}
+
+/// The migrations between each version of the quiz format, in order.
+///
+/// Version 1, the format of mdbook-quiz 0.4 and of quizzes without a `version`, is the only
+/// version so far. Later additions like distractor feedback and `programFile` kept the older
+/// shapes valid, so there is nothing to migrate yet.
+const MIGRATIONS: &[Migration] = &[];
+
+/// Returns the version of the quiz `doc`. Quizzes without a version are version 1.
+fn version(doc: &Document) -> Result<u32> {
+  match doc.get("version") {
+    Some(item) => {
+      let version = item
+        .as_integer()
+        .and_then(|n| u32::try_from(n).ok())
+        .context("`version` must be a positive integer")?;
+      ensure!(version > 0, "Quiz versions start at 1");
+      Ok(version)
+    }
+    None => Ok(1),
+  }
+}
+
+fn apply_migrations(
+  contents: &str,
+  migrations: &[Migration],
+  latest: u32,
+) -> Result<Option<String>> {
+  let mut doc = contents.parse::<Document>()?;
+  let version = version(&doc)?;
+  ensure!(
+    version <= latest,
+    "Quiz version {version} is newer than the latest supported version, {latest}"
+  );
+  if version == latest {
+    return Ok(None);
+  }
+
+  for migration in migrations.iter().filter(|m| m.from >= version) {
+    (migration.apply)(&mut doc)
+      .with_context(|| format!("Failed to migrate quiz from version {}", migration.from))?;
+  }
+
+  doc["version"] = value(i64::from(latest));
+  // Keep the version at the top of the file.
+  doc
+    .as_table_mut()
+    .sort_values_by(|k1, _, k2, _| (k2.get() == "version").cmp(&(k1.get() == "version")));
+  Ok(Some(doc.to_string()))
+}
+
+/// Upgrades the quiz `contents` to [`QUIZ_VERSION`] and sets its `version`, returning the new
+/// contents, or `None` if the quiz is already in the latest version.
+pub fn migrate(contents: &str) -> Result<Option<String>> {
+  apply_migrations(contents, MIGRATIONS, QUIZ_VERSION)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const QUIZ: &str = r#"spellcheckIgnore = ["rustc"]
+
+# A question
+[[questions]]
+type = "ShortAnswer"
+prompt.prompt = "Hello"
+answer.answer = "world"
+"#;
+
+  #[test]
+  fn migrate_sets_version() -> Result<()> {
+    // Quizzes without a version are in version 1, so they only need a migration after version 1.
+    assert_eq!(migrate(QUIZ)?, None);
+    let migrated = apply_migrations(QUIZ, &[], 2)?.unwrap();
+    assert_eq!(
+      migrated,
+      format!("version = 2\n{QUIZ}"),
+      "version should be added without changing the rest of the file"
+    );
+    assert_eq!(apply_migrations(&migrated, &[], 2)?, None);
+
+    let newer = format!("version = {}\n{QUIZ}", QUIZ_VERSION + 1);
+    assert!(migrate(&newer).is_err());
+    assert!(migrate(&format!("version = 0\n{QUIZ}")).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn migrate_applies_migrations_in_order() -> Result<()> {
+    // Version 2 renames `spellcheckIgnore`, and version 3 wraps it in a table.
+    let migrations = [
+      Migration {
+        from: 1,
+        apply: |doc| {
+          let item = doc.remove("spellcheckIgnore").context("missing key")?;
+          doc["ignore"] = item;
+          Ok(())
+        },
+      },
+      Migration {
+        from: 2,
+        apply: |doc| {
+          let item = doc.remove("ignore").context("missing key")?;
+          doc["spellcheck"] = toml_edit::table();
+          doc["spellcheck"]["ignore"] = item;
+          Ok(())
+        },
+      },
+    ];
+
+    let migrated = apply_migrations(QUIZ, &migrations, 3)?.unwrap();
+    assert!(migrated.starts_with("version = 3\n"));
+    let quiz: toml::Value = toml::from_str(&migrated)?;
+    assert_eq!(quiz["spellcheck"]["ignore"][0].as_str(), Some("rustc"));
+    assert!(quiz.get("spellcheckIgnore").is_none());
+
+    // Only the migrations after the quiz's version are applied.
+    let from_v2 = apply_migrations("version = 2\nignore = [\"rustc\"]\n", &migrations, 3)?.unwrap();
+    let quiz: toml::Value = toml::from_str(&from_v2)?;
+    assert_eq!(quiz["spellcheck"]["ignore"][0].as_str(), Some("rustc"));
+    assert_eq!(quiz["version"].as_integer(), Some(3));
+    Ok(())
+  }
+}
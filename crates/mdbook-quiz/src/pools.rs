@@ -0,0 +1,231 @@
+//! Samples questions from the [`QuestionPool`]s of a quiz.
+//!
+//! Pools sampled at build time are drawn here. Pools sampled per learner are embedded with all of
+//! their candidates, and drawn by the frontend with the same algorithm.
+
+use anyhow::{Result, bail};
+use mdbook_quiz_schema::QuestionPool;
+use rand::{SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+/// A pool whose sample is drawn in each learner's browser.
+#[derive(Debug, Serialize)]
+pub struct LearnerPool {
+  pub count: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tags: Option<Vec<String>>,
+  pub questions: Vec<toml::Value>,
+}
+
+fn has_tag(question: &toml::Value, tag: &str) -> bool {
+  question
+    .get("tags")
+    .and_then(toml::Value::as_array)
+    .is_some_and(|tags| tags.iter().any(|t| t.as_str() == Some(tag)))
+}
+
+/// Returns the candidates that can be sampled by `pool`, i.e. those with one of its tags, if any.
+pub fn candidates(pool: &QuestionPool, questions: Vec<toml::Value>) -> Vec<toml::Value> {
+  match &pool.tags {
+    Some(tags) => questions
+      .into_iter()
+      .filter(|q| tags.iter().any(|tag| has_tag(q, tag)))
+      .collect(),
+    None => questions,
+  }
+}
+
+/// A matching between the slots of a pool's sample and its candidates.
+struct Matching<'a, F> {
+  order: &'a [usize],
+  has_tag: F,
+  /// The tag of each slot, if any.
+  slots: Vec<Option<&'a str>>,
+  /// The slot filled by each candidate.
+  owner: Vec<Option<usize>>,
+}
+
+impl<F: Fn(usize, &str) -> bool> Matching<'_, F> {
+  /// Fills `slot` with a candidate, moving the candidates of other slots if needed.
+  fn fill(&mut self, slot: usize, visited: &mut [bool]) -> bool {
+    for &i in self.order {
+      let fits = self.slots[slot].is_none_or(|tag| (self.has_tag)(i, tag));
+      if visited[i] || !fits {
+        continue;
+      }
+      visited[i] = true;
+      let free = match self.owner[i] {
+        Some(other) => self.fill(other, visited),
+        None => true,
+      };
+      if free {
+        self.owner[i] = Some(slot);
+        return true;
+      }
+    }
+    false
+  }
+}
+
+/// Chooses the candidates that make up the sample of `pool`, trying them in the order of `order`,
+/// where `has_tag(i, tag)` returns true if candidate `i` has `tag`.
+///
+/// Each tag gets its share of [`QuestionPool::tag_counts`]. A candidate with several of the
+/// pool's tags is only chosen once, so it may be moved to another tag to make room for one
+/// that only it can fill. Returns the chosen candidates in increasing order, or an error for the
+/// first tag whose share can't be filled.
+fn allocate(
+  pool: &QuestionPool,
+  order: &[usize],
+  has_tag: impl Fn(usize, &str) -> bool,
+) -> Result<Vec<usize>> {
+  let groups = match &pool.tags {
+    Some(_) => pool
+      .tag_counts()
+      .into_iter()
+      .map(|(tag, n)| (Some(tag), n))
+      .collect(),
+    None => vec![(None, pool.count)],
+  };
+
+  let mut matching = Matching {
+    order,
+    has_tag,
+    slots: Vec::new(),
+    owner: vec![None; order.len()],
+  };
+  for (tag, count) in groups {
+    for available in 0..count {
+      matching.slots.push(tag);
+      let slot = matching.slots.len() - 1;
+      if !matching.fill(slot, &mut vec![false; order.len()]) {
+        bail!(
+          "Pool samples {count} question(s){}, but only {available} are available",
+          tag
+            .map(|tag| format!(" tagged `{tag}`"))
+            .unwrap_or_default(),
+        );
+      }
+    }
+  }
+
+  Ok(
+    (0..order.len())
+      .filter(|&i| matching.owner[i].is_some())
+      .collect(),
+  )
+}
+
+/// Checks that a sample of `pool` can be drawn from the `candidates`, whatever order they are
+/// tried in, for pools that are sampled by each learner.
+pub fn check(pool: &QuestionPool, candidates: &[toml::Value]) -> Result<()> {
+  let order = (0..candidates.len()).collect::<Vec<_>>();
+  allocate(pool, &order, |i, tag| has_tag(&candidates[i], tag))?;
+  Ok(())
+}
+
+/// Chooses `pool.count` of the `candidates` with a seeded RNG, keeping their order.
+///
+/// If the pool has tags, then each tag gets its share of the sample from the candidates with
+/// that tag, see [`allocate`].
+pub fn sample(
+  pool: &QuestionPool,
+  candidates: &[toml::Value],
+  seed: u64,
+) -> Result<Vec<toml::Value>> {
+  let mut rng = ChaCha8Rng::seed_from_u64(seed);
+  let mut order = (0..candidates.len()).collect::<Vec<_>>();
+  order.shuffle(&mut rng);
+
+  let chosen = allocate(pool, &order, |i, tag| has_tag(&candidates[i], tag))?;
+  Ok(chosen.into_iter().map(|i| candidates[i].clone()).collect())
+}
+
+#[test]
+fn sample_pool() -> Result<()> {
+  let questions = (0..10)
+    .map(|i| {
+      let tag = if i < 3 { "a" } else { "b" };
+      format!("prompt = {i}\ntags = [\"{tag}\"]").parse::<toml::Value>()
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  let pool = |count: usize, tags: Option<&[&str]>| QuestionPool {
+    files: Vec::new(),
+    count,
+    tags: tags.map(|tags| tags.iter().map(|t| t.to_string()).collect()),
+    sample: None,
+    seed: None,
+  };
+
+  let sampled = sample(&pool(4, None), &questions, 0)?;
+  assert_eq!(sampled.len(), 4);
+  assert_eq!(sampled, sample(&pool(4, None), &questions, 0)?);
+  assert_ne!(sampled, sample(&pool(4, None), &questions, 1)?);
+  let prompts = sampled.iter().map(|q| q["prompt"].as_integer().unwrap());
+  assert!(prompts.clone().zip(prompts.skip(1)).all(|(a, b)| a < b));
+
+  let stratified = pool(5, Some(&["a", "b"]));
+  let sampled = sample(&stratified, &questions, 0)?;
+  assert_eq!(sampled.iter().filter(|q| has_tag(q, "a")).count(), 3);
+  assert_eq!(sampled.iter().filter(|q| has_tag(q, "b")).count(), 2);
+
+  assert_eq!(
+    candidates(&pool(1, Some(&["a"])), questions.clone()).len(),
+    3
+  );
+  assert!(sample(&pool(7, Some(&["a", "b"])), &questions, 0).is_err());
+
+  // A question with both tags is only sampled once, and only if the other tag can spare it.
+  let overlapping = ["a", "a\", \"b", "b"]
+    .iter()
+    .enumerate()
+    .map(|(i, tags)| format!("prompt = {i}\ntags = [\"{tags}\"]").parse::<toml::Value>())
+    .collect::<Result<Vec<_>, _>>()?;
+  for seed in 0..10 {
+    let sampled = sample(&pool(3, Some(&["a", "b"])), &overlapping, seed)?;
+    assert_eq!(sampled, overlapping);
+  }
+  let err = sample(&pool(4, Some(&["a", "b"])), &overlapping, 0).unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "Pool samples 2 question(s) tagged `b`, but only 1 are available"
+  );
+
+  Ok(())
+}
+
+#[test]
+fn allocate_pool() -> Result<()> {
+  let pool = |count: usize, tags: Option<&[&str]>| QuestionPool {
+    files: Vec::new(),
+    count,
+    tags: tags.map(|tags| tags.iter().map(|t| t.to_string()).collect()),
+    sample: None,
+    seed: None,
+  };
+  // The second candidate has both tags.
+  let tags: [&[&str]; 3] = [&["a"], &["a", "b"], &["b"]];
+  let has_tag = |i: usize, tag: &str| tags[i].contains(&tag);
+
+  let both = pool(2, Some(&["a", "b"]));
+  assert_eq!(allocate(&both, &[1, 0, 2], has_tag)?, vec![0, 1]);
+  assert_eq!(
+    allocate(&pool(3, Some(&["a", "b"])), &[1, 2, 0], has_tag)?,
+    vec![0, 1, 2]
+  );
+  assert_eq!(allocate(&pool(2, None), &[2, 0, 1], has_tag)?, vec![0, 2]);
+
+  let err = allocate(&pool(4, Some(&["a", "b"])), &[0, 1, 2], has_tag).unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "Pool samples 2 question(s) tagged `b`, but only 1 are available"
+  );
+  let err = allocate(&pool(4, None), &[0, 1, 2], has_tag).unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "Pool samples 4 question(s), but only 3 are available"
+  );
+
+  Ok(())
+}
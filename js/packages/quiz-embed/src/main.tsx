@@ -8,6 +8,8 @@ import { ErrorBoundary } from "react-error-boundary";
 import hljs from "highlight.js";
 
 import "./index.scss";
+import { type LearnerPool, learnerSeed, samplePools } from "./pools";
+import { shuffleQuestions } from "./shuffle";
 
 let onError = ({ error }: { error: Error }) => {
   document.body.style.overflowY = "auto";
@@ -45,6 +47,10 @@ let initQuizzes = () => {
 
     let maybeParseJson = <T,>(s: string | undefined): T | undefined =>
       s !== undefined ? JSON.parse(s) : undefined;
+    let pools = maybeParseJson<LearnerPool[]>(divEl.dataset.quizPools);
+    if (pools) quiz = samplePools(quiz, pools, learnerSeed(name));
+    if (maybeParseJson<boolean>(divEl.dataset.quizShuffle) === true)
+      quiz = shuffleQuestions(quiz, learnerSeed(name));
     let fullscreen =
       maybeParseJson<boolean>(divEl.dataset.quizFullscreen) === true;
     let cacheAnswers =
@@ -52,6 +58,9 @@ let initQuizzes = () => {
     let showBugReporter =
       maybeParseJson<boolean>(divEl.dataset.quizShowBugReporter) === true;
     let initialText = maybeParseJson<string>(divEl.dataset.quizInitialText);
+    let allowRetry =
+      maybeParseJson<boolean>(divEl.dataset.quizAllowRetry) !== false;
+    let passingScore = maybeParseJson<number>(divEl.dataset.quizPassingScore);
 
     let syntaxHighlighter = hljs.highlightElement;
 
@@ -65,7 +74,8 @@ let initQuizzes = () => {
           showBugReporter={showBugReporter}
           initialText={initialText}
           syntaxHighlighter={syntaxHighlighter}
-          allowRetry
+          allowRetry={allowRetry}
+          passingScore={passingScore}
         />
       </ErrorBoundary>
     );
@@ -0,0 +1,88 @@
+import type { Quiz } from "@wcrichto/quiz";
+
+type Question = Quiz["questions"][number];
+
+/** A pool of questions that is sampled separately for each learner. */
+export interface LearnerPool {
+  count: number;
+  tags?: string[];
+  questions: Question[];
+}
+
+/** A small seeded PRNG (mulberry32), returning floats in [0, 1). */
+export let seededRandom = (seed: number) => () => {
+  seed = (seed + 0x6d2b79f5) | 0;
+  let t = Math.imul(seed ^ (seed >>> 15), 1 | seed);
+  t = (t + Math.imul(t ^ (t >>> 7), 61 | t)) ^ t;
+  return ((t ^ (t >>> 14)) >>> 0) / 4294967296;
+};
+
+/**
+ * Returns a random seed for the learner and quiz, which is stored so that
+ * the learner sees the same sample when they revisit the quiz.
+ */
+export let learnerSeed = (quizName: string): number => {
+  let key = `mdbook-quiz:${quizName}:pool-seed`;
+  let stored = localStorage.getItem(key);
+  if (stored !== null) return Number.parseInt(stored);
+  let seed = Math.floor(Math.random() * 2 ** 32);
+  localStorage.setItem(key, seed.toString());
+  return seed;
+};
+
+/**
+ * Chooses `pool.count` of the pool's questions, keeping their order.
+ *
+ * Mirrors the build-time sampling in the preprocessor: if the pool has tags,
+ * then the count is spread evenly across the tags, with earlier tags getting any extra.
+ * A question with several of the pool's tags is only chosen once, so it may be
+ * moved to another tag to make room for a question that only it can fill.
+ */
+let samplePool = (pool: LearnerPool, random: () => number): Question[] => {
+  let slots: (string | undefined)[] = pool.tags
+    ? pool.tags.flatMap((tag, i) =>
+        Array(
+          Math.floor(pool.count / pool.tags!.length) +
+            (i < pool.count % pool.tags!.length ? 1 : 0)
+        ).fill(tag)
+      )
+    : Array(pool.count).fill(undefined);
+
+  // Fisher-Yates shuffle of the order in which questions are tried.
+  let order = pool.questions.map((_, i) => i);
+  for (let j = order.length - 1; j > 0; j--) {
+    let k = Math.floor(random() * (j + 1));
+    [order[j], order[k]] = [order[k], order[j]];
+  }
+
+  // The slot filled by each question, found with augmenting paths.
+  let owner: (number | undefined)[] = pool.questions.map(() => undefined);
+  let fill = (slot: number, visited: boolean[]): boolean => {
+    let tag = slots[slot];
+    for (let i of order) {
+      if (visited[i] || (tag && !pool.questions[i].tags?.includes(tag))) continue;
+      visited[i] = true;
+      let other = owner[i];
+      if (other === undefined || fill(other, visited)) {
+        owner[i] = slot;
+        return true;
+      }
+    }
+    return false;
+  };
+  // The build validates that every slot can be filled, so a slot that can't is skipped.
+  slots.forEach((_, slot) => fill(slot, pool.questions.map(() => false)));
+
+  return pool.questions.filter((_, i) => owner[i] !== undefined);
+};
+
+/** Adds a sample of each pool to the end of the quiz's questions. */
+export let samplePools = (
+  quiz: Quiz,
+  pools: LearnerPool[],
+  seed: number
+): Quiz => {
+  let random = seededRandom(seed);
+  let sampled = pools.flatMap(pool => samplePool(pool, random));
+  return { ...quiz, questions: [...quiz.questions, ...sampled] };
+};
@@ -0,0 +1,27 @@
+import type { Quiz } from "@wcrichto/quiz";
+
+import { seededRandom } from "./pools";
+
+type Question = Quiz["questions"][number];
+
+/**
+ * Shuffles the questions of a quiz with a seeded Fisher-Yates shuffle.
+ *
+ * The questions of a multipart group are kept together and in order.
+ */
+export let shuffleQuestions = (quiz: Quiz, seed: number): Quiz => {
+  let groups: Question[][] = [];
+  for (let q of quiz.questions) {
+    let last = groups[groups.length - 1];
+    if (q.multipart && last && last[0].multipart === q.multipart) last.push(q);
+    else groups.push([q]);
+  }
+
+  let random = seededRandom(seed);
+  for (let i = groups.length - 1; i > 0; i--) {
+    let j = Math.floor(random() * (i + 1));
+    [groups[i], groups[j]] = [groups[j], groups[i]];
+  }
+
+  return { ...quiz, questions: groups.flat() };
+};
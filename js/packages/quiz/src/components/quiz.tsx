@@ -197,13 +197,40 @@ interface AnswerReviewProps {
   onGiveUp: () => void;
 }
 
+/** Returns the percentage of the quiz's points that were answered correctly. */
+export let weightedScore = (quiz: Quiz, answers: TaggedAnswer[]): number => {
+  let points = quiz.questions.map(q => q.points ?? 1);
+  let total = _.sum(points);
+  let earned = _.sum(points.filter((_p, i) => answers[i]?.correct));
+  return total > 0 ? (100 * earned) / total : 100;
+};
+
+let PassingScore = ({
+  state,
+  passingScore
+}: {
+  state: QuizState;
+  passingScore: number;
+}) => {
+  let { quiz } = useContext(QuizConfigContext)!;
+  let score = weightedScore(quiz, state.answers);
+  let passed = score >= passingScore;
+  return (
+    <p className={`passing-score ${passed ? "passed" : "failed"}`}>
+      You scored <strong>{Math.round(score)}%</strong>, so you{" "}
+      {passed ? "passed" : "did not pass"} the quiz. A passing score is{" "}
+      {passingScore}%.
+    </p>
+  );
+};
+
 let AnswerReview = ({
   state,
   nCorrect,
   onRetry,
   onGiveUp
 }: AnswerReviewProps) => {
-  let { quiz, name } = useContext(QuizConfigContext)!;
+  let { quiz, name, passingScore } = useContext(QuizConfigContext)!;
   let confirm = !state.confirmedDone && (
     <p style={{ marginBottom: "1em" }}>
       You can either{" "}
@@ -228,6 +255,9 @@ let AnswerReview = ({
         </strong>{" "}
         questions correctly.
       </p>
+      {passingScore !== undefined && (
+        <PassingScore state={state} passingScore={passingScore} />
+      )}
       {confirm}
       {quiz.questions.map((question, i) => {
         let { answer, correct } = state.answers[i];
@@ -303,6 +333,8 @@ export interface QuizViewConfig {
   fullscreen?: boolean;
   cacheAnswers?: boolean;
   allowRetry?: boolean;
+  /** The percentage of points needed to pass the quiz, if it can be passed. */
+  passingScore?: number;
   showBugReporter?: boolean;
   autoStart?: boolean;
   initialText?: string;
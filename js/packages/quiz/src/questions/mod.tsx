@@ -3,6 +3,7 @@ import _ from "lodash";
 import React, { useContext, useId, useMemo, useRef, useState } from "react";
 import { type RegisterOptions, useForm } from "react-hook-form";
 
+import type { Markdown } from "../bindings/Markdown";
 import type { Question } from "../bindings/Question";
 import type { Quiz } from "../bindings/Quiz";
 import { MarkdownView } from "../components/markdown";
@@ -102,6 +103,8 @@ export interface TaggedAnswer {
   start: number;
   end: number;
   explanation?: string;
+  /** The number of hints opened before answering, if the question has hints. */
+  hintsOpened?: number;
 }
 
 let now = () => new Date().getTime();
@@ -112,6 +115,27 @@ This explanation helps us understand *why* a reader answers a particular way, so
 we can better improve the surrounding text.
 `.trim();
 
+interface HintsProps {
+  hints: Markdown[];
+  opened: number;
+  onOpen: () => void;
+}
+
+let Hints = ({ hints, opened, onOpen }: HintsProps) => (
+  <div className="hints">
+    {hints.slice(0, opened).map((hint, i) => (
+      <div key={i} className="hint">
+        <MarkdownView markdown={`**Hint ${i + 1}**: ${hint}`} />
+      </div>
+    ))}
+    {opened < hints.length && (
+      <button type="button" onClick={onOpen}>
+        Show hint ({opened + 1} of {hints.length})
+      </button>
+    )}
+  </div>
+);
+
 interface QuestionViewProps {
   multipart: Quiz["multipart"];
   question: Question;
@@ -157,6 +181,7 @@ export let QuestionView: React.FC<QuestionViewProps> = ({
   let start = useMemo(now, [quizName, question, index]);
   let ref = useRef<HTMLFormElement>(null);
   let [showExplanation, setShowExplanation] = useState(false);
+  let [hintsOpened, setHintsOpened] = useState(0);
   let methods = getQuestionMethods(question.type);
   if (!methods) {
     return (
@@ -188,7 +213,8 @@ export let QuestionView: React.FC<QuestionViewProps> = ({
       correct,
       start,
       end: now(),
-      explanation: data.explanation
+      explanation: data.explanation,
+      hintsOpened: question.hints ? hintsOpened : undefined
     });
   });
 
@@ -208,6 +234,13 @@ export let QuestionView: React.FC<QuestionViewProps> = ({
           />
         )}
         <methods.PromptView prompt={question.prompt} />
+        {question.hints && (
+          <Hints
+            hints={question.hints}
+            opened={hintsOpened}
+            onOpen={() => setHintsOpened(hintsOpened + 1)}
+          />
+        )}
         {window.telemetry && showBugReporter && (
           <BugReporter quizName={quizName} question={index} />
         )}
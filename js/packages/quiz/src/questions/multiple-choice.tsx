@@ -2,6 +2,7 @@ import classNames from "classnames";
 import _ from "lodash";
 import React, { useId } from "react";
 
+import type { Distractor } from "../bindings/Distractor";
 import type { Markdown } from "../bindings/Markdown";
 import type { MultipleChoiceAnswer } from "../bindings/MultipleChoiceAnswer";
 import type { MultipleChoicePrompt } from "../bindings/MultipleChoicePrompt";
@@ -12,6 +13,23 @@ interface MultipleChoiceState {
   choices: string[];
 }
 
+let distractorText = (distractor: Distractor): Markdown =>
+  typeof distractor === "string" ? distractor : distractor.text;
+
+/** Returns the feedback for each distractor among the chosen answers. */
+let distractorFeedback = (
+  prompt: MultipleChoicePrompt,
+  chosen: Markdown | Markdown[]
+): Markdown[] =>
+  (Array.isArray(chosen) ? chosen : [chosen]).flatMap(choice => {
+    let distractor = prompt.distractors.find(
+      d => typeof d !== "string" && d.text === choice
+    );
+    return distractor && typeof distractor !== "string"
+      ? [distractor.feedback]
+      : [];
+  });
+
 export let MultipleChoiceMethods: QuestionMethods<
   MultipleChoicePrompt,
   MultipleChoiceAnswer,
@@ -30,10 +48,10 @@ export let MultipleChoiceMethods: QuestionMethods<
       ? answer.answer
       : [answer.answer];
     if (prompt.answerIndex !== undefined) {
-      choices = [...prompt.distractors];
+      choices = prompt.distractors.map(distractorText);
       choices.splice(prompt.answerIndex, 0, ...answers);
     } else {
-      choices = [...answers, ...prompt.distractors];
+      choices = [...answers, ...prompt.distractors.map(distractorText)];
       if (prompt.sortAnswers) {
         choices = _.sortBy(choices);
       } else {
@@ -80,26 +98,33 @@ export let MultipleChoiceMethods: QuestionMethods<
     return _.isEqual(toList(provided.answer), toList(user.answer));
   },
 
-  AnswerView: ({ answer, baseline }) => (
-    <div
-      className={classNames(
-        "md-flex",
-        MultipleChoiceMethods.compareAnswers!(baseline, answer)
-          ? "correct"
-          : "incorrect"
-      )}
-    >
-      {Array.isArray(answer.answer) ? (
-        <ul>
-          {answer.answer.map((a, i) => (
-            <li key={i}>
-              <MarkdownView markdown={a} />
-            </li>
-          ))}
-        </ul>
-      ) : (
-        <MarkdownView markdown={answer.answer} />
-      )}
-    </div>
+  AnswerView: ({ answer, baseline, prompt }) => (
+    <>
+      <div
+        className={classNames(
+          "md-flex",
+          MultipleChoiceMethods.compareAnswers!(baseline, answer)
+            ? "correct"
+            : "incorrect"
+        )}
+      >
+        {Array.isArray(answer.answer) ? (
+          <ul>
+            {answer.answer.map((a, i) => (
+              <li key={i}>
+                <MarkdownView markdown={a} />
+              </li>
+            ))}
+          </ul>
+        ) : (
+          <MarkdownView markdown={answer.answer} />
+        )}
+      </div>
+      {distractorFeedback(prompt, answer.answer).map((feedback, i) => (
+        <div className="distractor-feedback" key={i}>
+          <MarkdownView markdown={feedback} />
+        </div>
+      ))}
+    </>
   )
 };
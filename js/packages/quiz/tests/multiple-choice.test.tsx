@@ -113,3 +113,33 @@ describe("MultipleChoice multi-answer", () => {
     });
   });
 });
+
+describe("MultipleChoice distractor feedback", () => {
+  let prompt: MultipleChoice["prompt"] = {
+    prompt: "Hello world",
+    distractors: ["B", { text: "C", feedback: "C is not it" }]
+  };
+
+  it("shows feedback for a chosen distractor", async () => {
+    render(
+      <MultipleChoiceMethods.AnswerView
+        answer={{ answer: "C" }}
+        baseline={{ answer: "A" }}
+        prompt={prompt}
+      />
+    );
+    await waitFor(() => screen.getByText("C is not it"));
+  });
+
+  it("shows no feedback for other answers", async () => {
+    render(
+      <MultipleChoiceMethods.AnswerView
+        answer={{ answer: "B" }}
+        baseline={{ answer: "A" }}
+        prompt={prompt}
+      />
+    );
+    await waitFor(() => screen.getByText("B"));
+    expect(screen.queryByText("C is not it")).toBeNull();
+  });
+});
@@ -47,3 +47,47 @@ describe("Question prompt for explanation", () => {
     expect(submitted.answer).toMatchObject({ explanation: "Because" });
   });
 });
+
+describe("Question hints", () => {
+  let question: Question = {
+    type: "ShortAnswer",
+    prompt: { prompt: "Hello world" },
+    answer: { answer: "Yes" },
+    hints: ["First hint", "Second hint"]
+  };
+
+  let submitted: any | null = null;
+  beforeEach(async () => {
+    submitted = null;
+    render(
+      <QuizConfigContext.Provider value={{ name: "Foobar", quiz: {} as any }}>
+        <QuestionView
+          question={question}
+          multipart={{}}
+          index={1}
+          title="1"
+          attempt={0}
+          onSubmit={answer => {
+            submitted = answer;
+          }}
+        />
+      </QuizConfigContext.Provider>
+    );
+    await waitFor(() => screen.getByText("Hello world"));
+  });
+
+  it("shows hints one at a time and records how many were opened", async () => {
+    expect(screen.queryByText(/First hint/)).toBeNull();
+    await user.click(
+      screen.getByRole("button", { name: "Show hint (1 of 2)" })
+    );
+    await waitFor(() => screen.getByText(/First hint/));
+    expect(screen.queryByText(/Second hint/)).toBeNull();
+
+    let input = screen.getByRole("textbox");
+    await user.type(input, "Yes");
+    await user.click(submitButton());
+
+    expect(submitted.hintsOpened).toBe(1);
+  });
+});
@@ -5,7 +5,11 @@ import { beforeEach, describe, expect, it } from "vitest";
 
 import type { Question } from "../src/bindings/Question";
 import type { Quiz } from "../src/bindings/Quiz";
-import { QuizView, generateQuestionTitles } from "../src/lib";
+import {
+  QuizView,
+  generateQuestionTitles,
+  weightedScore
+} from "../src/lib";
 import { startButton, submitButton } from "./utils";
 
 let quiz: Quiz = {
@@ -118,6 +122,57 @@ describe("Quiz retry", () => {
   });
 });
 
+describe("Quiz passing score", () => {
+  let weightedQuiz: Quiz = {
+    questions: [
+      {
+        type: "ShortAnswer",
+        prompt: { prompt: "Hey" },
+        answer: { answer: "Yes" },
+        points: 3
+      },
+      {
+        type: "ShortAnswer",
+        prompt: { prompt: "Hello" },
+        answer: { answer: "No" }
+      }
+    ]
+  };
+
+  it("weights the score by points", () => {
+    let answer = (correct: boolean) => ({
+      answer: "",
+      correct,
+      start: 0,
+      end: 0
+    });
+    expect(weightedScore(weightedQuiz, [answer(true), answer(false)])).toBe(
+      75
+    );
+    expect(weightedScore(weightedQuiz, [answer(false), answer(true)])).toBe(
+      25
+    );
+  });
+
+  it("tells the user whether they passed", async () => {
+    render(
+      <QuizView name="the-quiz" quiz={weightedQuiz} passingScore={70} />
+    );
+    await waitFor(() => screen.getByText("Quiz"));
+    await user.click(startButton());
+
+    let input = await waitFor(() => screen.getByRole("textbox"));
+    await user.type(input, "Yes");
+    await user.click(submitButton());
+    input = await waitFor(() => screen.getByRole("textbox"));
+    await user.type(input, "Yes");
+    await user.click(submitButton());
+
+    await waitFor(() => screen.getByText("75%"));
+    screen.getByText(/so you passed the quiz/);
+  });
+});
+
 describe("generateQuestionTitles", () => {
   it("handles multi-part questions", () => {
     let template: Question = {